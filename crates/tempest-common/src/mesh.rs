@@ -69,7 +69,7 @@ impl Mesh {
         if index >= self.triangle_count() {
             None
         } else {
-            let v1 = self.vertices[3 * index + 0];
+            let v1 = self.vertices[3 * index];
             let v2 = self.vertices[3 * index + 1];
             let v3 = self.vertices[3 * index + 2];
             Some(Triangle::new(v1, v2, v3))
@@ -33,10 +33,7 @@ impl<T> Copy for WeakResourceHandle<T> {}
 
 impl<T> Clone for WeakResourceHandle<T> {
     fn clone(&self) -> Self {
-        Self {
-            idx: self.idx,
-            _type: PhantomData,
-        }
+        *self
     }
 }
 
@@ -50,7 +47,7 @@ impl<T> Eq for WeakResourceHandle<T> {}
 
 impl<T> PartialOrd for WeakResourceHandle<T> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.idx.partial_cmp(&other.idx)
+        Some(self.cmp(other))
     }
 }
 
@@ -115,7 +112,7 @@ impl<T> Eq for ResourceHandle<T> {}
 
 impl<T> PartialOrd for ResourceHandle<T> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.internal.idx.partial_cmp(&other.internal.idx)
+        Some(self.cmp(other))
     }
 }
 
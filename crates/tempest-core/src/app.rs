@@ -1,4 +1,7 @@
-use std::collections::{hash_map::RandomState, HashMap};
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    path::PathBuf,
+};
 
 use winit::{
     event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
@@ -6,7 +9,13 @@ use winit::{
     window::{WindowBuilder, WindowId},
 };
 
-use tempest_ecs::world::World;
+use tempest_ecs::{
+    event::Event as EcsEvent,
+    scene::{SceneFormat, SceneWatcher},
+    schedule::{Schedule, System},
+    transformation::TransformPropagation,
+    world::World,
+};
 use tempest_render::renderer::Renderer;
 
 /// Callback to be invoked on the start of an application.
@@ -23,20 +32,24 @@ struct WindowInfo {
 }
 
 /// Struct representing an application and its current state.
+#[derive(Default)]
 pub struct App {
     world: World,
-    on_start: Vec<Box<ApplicationStartCallback>>,
-    on_update: Vec<Box<ApplicationUpdateCallback>>,
-    on_stop: Vec<Box<ApplicationStopCallback>>,
+    schedule: Schedule,
+    transforms: TransformPropagation,
+    scenes: Vec<(SceneFormat, SceneWatcher)>,
+    on_start: Vec<ApplicationStartCallback>,
+    on_update: Vec<ApplicationUpdateCallback>,
+    on_stop: Vec<ApplicationStopCallback>,
     window_titles: Vec<WindowInfo>,
 }
 
 /// Builder used to create an application.
-/// 
+///
 /// # Example
 /// ```no_run
 /// use tempest_core::app::AppBuilder;
-/// 
+///
 /// AppBuilder::default().with_window("Hello, World!")
 ///     .on_app_start(|_| { println!("Started!"); })
 ///     .on_app_update(|ctx| {
@@ -48,14 +61,17 @@ pub struct App {
 /// ```
 #[derive(Default)]
 pub struct AppBuilder {
-    on_start: Vec<Box<ApplicationStartCallback>>,
-    on_update: Vec<Box<ApplicationUpdateCallback>>,
-    on_stop: Vec<Box<ApplicationStopCallback>>,
+    on_start: Vec<ApplicationStartCallback>,
+    on_update: Vec<ApplicationUpdateCallback>,
+    on_stop: Vec<ApplicationStopCallback>,
+    systems: Vec<System>,
+    events: Vec<fn(&mut World)>,
+    scenes: Vec<(SceneFormat, SceneWatcher)>,
     windows: Vec<WindowInfo>,
 }
 
 /// Struct wrapping application context.  This is used to provide data from the app to user-defined callbacks.
-/// 
+///
 /// ## Lifetimes
 /// - `<'a>` - Lifetime of the [App](App) created from
 pub struct AppContext<'a> {
@@ -73,10 +89,10 @@ impl<'a> AppContext<'a> {
         renderers: &'a mut HashMap<WindowId, Renderer>,
     ) -> Self {
         Self {
-            world: world,
-            events: events,
-            renderers: renderers,
-            shutdown_requested: false
+            world,
+            events,
+            renderers,
+            shutdown_requested: false,
         }
     }
 
@@ -125,19 +141,38 @@ impl<'a> AppContext<'a> {
 impl AppBuilder {
     /// Adds a callback to the built application to be invoked before entering the main application loop, but after the initialization of the application
     pub fn on_app_start(&mut self, start: ApplicationStartCallback) -> &mut Self {
-        self.on_start.push(Box::new(start));
+        self.on_start.push(start);
         self
     }
 
     /// Adds a callback to the built application to be invoked after leaving the main application loop, but before destruction of the application
     pub fn on_app_close(&mut self, close: ApplicationStopCallback) -> &mut Self {
-        self.on_stop.push(Box::new(close));
+        self.on_stop.push(close);
         self
     }
 
     /// Adds a callback to the built application to be invoked on every tick of the application
     pub fn on_app_update(&mut self, update: ApplicationUpdateCallback) -> &mut Self {
-        self.on_update.push(Box::new(update));
+        self.on_update.push(update);
+        self
+    }
+
+    /// Adds a system to the schedule run on every tick of the application, before the update callbacks
+    pub fn with_system(&mut self, system: System) -> &mut Self {
+        self.systems.push(system);
+        self
+    }
+
+    /// Registers an event type in the world of the built application.  Events expire after two ticks of the application
+    pub fn with_event<T: EcsEvent>(&mut self) -> &mut Self {
+        self.events.push(World::add_event::<T>);
+        self
+    }
+
+    /// Loads a scene file into the world of the built application, and applies it again on every tick where the file
+    /// was modified.  Scenes that fail to load are logged, leaving the previously applied scene in place
+    pub fn with_scene(&mut self, path: impl Into<PathBuf>, format: SceneFormat) -> &mut Self {
+        self.scenes.push((format, SceneWatcher::new(path)));
         self
     }
 
@@ -152,9 +187,38 @@ impl AppBuilder {
     }
 
     /// Builds an application from the contents of the builder
+    ///
+    /// # Panics
+    ///
+    /// Panics if the systems of the application cannot be scheduled
     pub fn build(&mut self) -> App {
+        let mut schedule = Schedule::default();
+        for system in self.systems.drain(..) {
+            schedule.add_system(system);
+        }
+
+        match schedule.build() {
+            Ok(ambiguities) => ambiguities.iter().for_each(|ambiguity| {
+                log::warn!(
+                    "Systems {} and {} conflict on {} without an explicit order, running them in the order they were added",
+                    ambiguity.first,
+                    ambiguity.second,
+                    ambiguity.conflict
+                )
+            }),
+            Err(err) => panic!("{}", err),
+        }
+
+        let mut world = World::default();
+        for add_event in self.events.drain(..) {
+            add_event(&mut world);
+        }
+
         App {
-            world: World::default(),
+            world,
+            schedule,
+            transforms: TransformPropagation::default(),
+            scenes: self.scenes.drain(..).collect(),
             on_start: self.on_start.drain(..).collect(),
             on_update: self.on_update.drain(..).collect(),
             on_stop: self.on_stop.drain(..).collect(),
@@ -163,18 +227,6 @@ impl AppBuilder {
     }
 }
 
-impl Default for App {
-    fn default() -> Self {
-        Self {
-            world: World::default(),
-            on_start: Vec::default(),
-            on_update: Vec::default(),
-            on_stop: Vec::default(),
-            window_titles: Vec::default(),
-        }
-    }
-}
-
 impl App {
     /// Runs the application.  This call assumes indefinite control the calling thread until destruction of the thread of application.
     pub fn run(self) {
@@ -222,7 +274,7 @@ impl App {
                             ..
                         } => {
                             let mut ctx =
-                                AppContext::new(&mut self.world, &event_loop, &mut renderers);
+                                AppContext::new(&mut self.world, event_loop, &mut renderers);
 
                             for cb in &self.on_stop {
                                 cb(&mut ctx);
@@ -241,28 +293,37 @@ impl App {
             }
             Event::RedrawRequested(window_id) => {
                 if let Some(renderer) = renderers.get_mut(&window_id) {
-                    match renderer.draw(&self.world.entities()) {
+                    match renderer.draw(self.world.entities()) {
                         Ok(_) => {}
                         Err(e) => eprintln!("{:?}", e),
                     }
                 }
             }
-            Event::MainEventsCleared => {
-                if *control_flow != ControlFlow::Exit {
-                    let mut ctx = AppContext::new(&mut self.world, &event_loop, &mut renderers);
-
-                    for cb in &self.on_update {
-                        cb(&mut ctx);
+            Event::MainEventsCleared if *control_flow != ControlFlow::Exit => {
+                self.world.increment_tick();
+                self.world.update_events();
+                for (format, watcher) in &mut self.scenes {
+                    if let Err(err) = watcher.poll(format, self.world.entitites_mut()) {
+                        log::warn!("Failed to load scene {}: {}", watcher.path().display(), err);
                     }
+                }
+                self.schedule.run(&mut self.world);
 
-                    if ctx.shutdown_requested {
-                        *control_flow = ControlFlow::Exit;
-                    }
+                let mut ctx = AppContext::new(&mut self.world, event_loop, &mut renderers);
 
-                    renderers.iter_mut().for_each(|(_, renderer)| {
-                        renderer.window().request_redraw();
-                    });
+                for cb in &self.on_update {
+                    cb(&mut ctx);
                 }
+
+                if ctx.shutdown_requested {
+                    *control_flow = ControlFlow::Exit;
+                }
+
+                self.transforms.run(self.world.entitites_mut());
+
+                renderers.iter_mut().for_each(|(_, renderer)| {
+                    renderer.window().request_redraw();
+                });
             }
             _ => {}
         });
@@ -0,0 +1,38 @@
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, Data, DeriveInput};
+
+use crate::persist::{bindings, fields_pattern};
+
+pub fn derive_bundle_impl(input: TokenStream) -> TokenStream {
+    let tokens = parse_macro_input!(input as DeriveInput);
+    let type_name = &tokens.ident;
+
+    let fields = match &tokens.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return syn::Error::new_spanned(type_name, "Only structs can be bundles.")
+                .to_compile_error()
+                .into()
+        }
+    };
+    let types = fields.iter().map(|field| &field.ty);
+    let bindings = bindings(fields);
+    let pattern = fields_pattern(fields, &bindings);
+
+    let generated = quote! {
+        impl tempest_ecs::bundle::Bundle for #type_name {
+            #[allow(unused_variables)]
+            fn component_types(types: &mut tempest_ecs::bundle::BundleTypes) {
+                #(types.add::<#types>();)*
+            }
+
+            #[allow(unused_variables)]
+            fn write(self, writer: &mut tempest_ecs::bundle::BundleWriter<'_>) {
+                let #type_name #pattern = self;
+                #(writer.write(#bindings);)*
+            }
+        }
+    };
+
+    generated.into()
+}
@@ -1,78 +1,161 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
-
 use proc_macro::TokenStream;
 use syn::{
-    parse::Parse, parse_macro_input, Data, DeriveInput, Fields, LitInt, Token, Type, TypePath,
+    parse::Parse, parse_macro_input, Data, DeriveInput, Index, LitInt, Token, Type, TypePath,
 };
 
-static TYPE_IDS: AtomicUsize = AtomicUsize::new(0);
+/// Arguments of the `component` attribute placed on the derived type.
+struct ComponentArgs {
+    page_size: Option<LitInt>,
+}
+
+impl ComponentArgs {
+    fn from_input(input: &DeriveInput) -> syn::Result<Self> {
+        let mut args = Self { page_size: None };
+
+        for attr in input
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("component"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("page_size") {
+                    let page_size: LitInt = meta.value()?.parse()?;
+                    if !page_size.base10_parse::<usize>()?.is_power_of_two() {
+                        return Err(syn::Error::new_spanned(
+                            page_size,
+                            "Page size must be a power of two.",
+                        ));
+                    }
+                    args.page_size = Some(page_size);
+                    Ok(())
+                } else {
+                    Err(meta.error("Expected `page_size`."))
+                }
+            })?;
+        }
+
+        Ok(args)
+    }
+}
 
 pub fn derive_component_impl(input: TokenStream) -> TokenStream {
     let ast: DeriveInput = syn::parse(input).unwrap();
+    let args = match ComponentArgs::from_input(&ast) {
+        Ok(args) => args,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let page_size = args
+        .page_size
+        .map(|page_size| quote!(const PAGE_SIZE: usize = #page_size;));
 
     // Get the name of the type being derived for
     let type_name = &ast.ident;
 
-    // Generate the type ID for this type
-    let type_id = TYPE_IDS.fetch_add(1, Ordering::SeqCst);
-
-    // Generate the implementation of the TypeId trait
-    let gen = match ast.data {
-        Data::Struct(ref data) => match data.fields {
-            Fields::Named(_) => {
-                quote! {
-                    impl Component for #type_name {
-                        fn id() -> usize {
-                            #type_id
-                        }
-                    }
+    // Reflected fields, with their names and accessors
+    let fields: Vec<(String, proc_macro2::TokenStream, &Type)> = match &ast.data {
+        Data::Struct(data) => data
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| match &field.ident {
+                Some(ident) => (ident.to_string(), quote!(#ident), &field.ty),
+                None => {
+                    let idx = Index::from(idx);
+                    (idx.index.to_string(), quote!(#idx), &field.ty)
+                }
+            })
+            .collect(),
+        Data::Enum(_) => Vec::new(),
+        Data::Union(_) => panic!("Cannot derive TypeId for unions"),
+    };
+    let names: Vec<&String> = fields.iter().map(|(name, _, _)| name).collect();
+    let accessors: Vec<&proc_macro2::TokenStream> =
+        fields.iter().map(|(_, accessor, _)| accessor).collect();
+    let types: Vec<&Type> = fields.iter().map(|(_, _, ty)| *ty).collect();
+
+    // Generate the implementations of the TypeId and Reflect traits
+    let gen = quote! {
+        impl Component for #type_name {
+            #page_size
+
+            fn id() -> usize {
+                static ID: ::std::sync::atomic::AtomicUsize =
+                    ::std::sync::atomic::AtomicUsize::new(usize::MAX);
+                tempest_ecs::reflect::cached_reflected_component_id::<Self>(&ID)
+            }
 
-                    impl Clone for #type_name {
-                        fn clone(&self) -> Self {
-                            *self
-                        }
-                    }
+            fn cloner() -> Option<fn(&Self) -> Self> {
+                // Method resolution prefers the impl on the probe itself, which only applies if the type is Clone,
+                // over the one on a reference to the probe
+                struct Probe<T>(::std::marker::PhantomData<T>);
 
-                    impl Copy for #type_name {}
+                trait Cloneable<T> {
+                    fn cloner(&self) -> Option<fn(&T) -> T>;
                 }
-            }
-            Fields::Unnamed(_) => {
-                quote! {
-                    impl Component for #type_name {
-                        fn id() -> usize {
-                            #type_id
-                        }
-                    }
 
-                    impl Clone for #type_name {
-                        fn clone(&self) -> Self {
-                            *self
-                        }
+                impl<T: Clone> Cloneable<T> for Probe<T> {
+                    fn cloner(&self) -> Option<fn(&T) -> T> {
+                        Some(T::clone)
                     }
+                }
 
-                    impl Copy for #type_name {}
+                trait NotCloneable<T> {
+                    fn cloner(&self) -> Option<fn(&T) -> T>;
                 }
-            }
-            Fields::Unit => {
-                quote! {
-                    impl Component for #type_name {
-                        fn id() -> usize {
-                            #type_id
-                        }
+
+                impl<T> NotCloneable<T> for &Probe<T> {
+                    fn cloner(&self) -> Option<fn(&T) -> T> {
+                        None
                     }
+                }
 
-                    impl Clone for #type_name {
-                        fn clone(&self) -> Self {
-                            *self
+                (&Probe::<Self>(::std::marker::PhantomData)).cloner()
+            }
+        }
+
+        impl tempest_ecs::reflect::Reflect for #type_name {
+            #[allow(unused_variables)]
+            fn type_info(&self) -> tempest_ecs::reflect::TypeInfo {
+                let uninit = ::std::mem::MaybeUninit::<Self>::uninit();
+                let base = uninit.as_ptr();
+                tempest_ecs::reflect::TypeInfo {
+                    name: ::std::any::type_name::<Self>(),
+                    fields: vec![#(
+                        tempest_ecs::reflect::FieldInfo {
+                            name: #names,
+                            type_name: ::std::any::type_name::<#types>(),
+                            type_id: ::std::any::TypeId::of::<#types>(),
+                            // Only the address of the field is taken, the uninitialized value is never read
+                            offset: unsafe {
+                                ::std::ptr::addr_of!((*base).#accessors) as usize - base as usize
+                            },
                         }
-                    }
+                    ),*],
+                }
+            }
 
-                    impl Copy for #type_name {}
+            fn field(&self, name: &str) -> Option<&dyn ::std::any::Any> {
+                match name {
+                    #(#names => Some(&self.#accessors),)*
+                    _ => None,
                 }
             }
-        },
-        Data::Enum(_) => panic!("Cannot derive TypeId for enums"),
-        Data::Union(_) => panic!("Cannot derive TypeId for unions"),
+
+            fn field_mut(&mut self, name: &str) -> Option<&mut dyn ::std::any::Any> {
+                match name {
+                    #(#names => Some(&mut self.#accessors),)*
+                    _ => None,
+                }
+            }
+
+            fn as_any(&self) -> &dyn ::std::any::Any {
+                self
+            }
+
+            fn as_any_mut(&mut self) -> &mut dyn ::std::any::Any {
+                self
+            }
+        }
     };
 
     // Return the generated implementation
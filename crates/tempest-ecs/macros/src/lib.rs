@@ -3,17 +3,35 @@ use proc_macro::TokenStream;
 use registry::derive_registry_query_impl;
 use syn::{parse_macro_input, Type};
 
+mod bundle;
 mod component;
+mod persist;
 mod registry;
+mod scene;
 
 #[macro_use]
 extern crate quote;
 
-#[proc_macro_derive(Component)]
+#[proc_macro_derive(Component, attributes(component))]
 pub fn derive_component(input: TokenStream) -> TokenStream {
     component::derive_component_impl(input)
 }
 
+#[proc_macro_derive(Bundle)]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+    bundle::derive_bundle_impl(input)
+}
+
+#[proc_macro_derive(Persist, attributes(persist))]
+pub fn derive_persist(input: TokenStream) -> TokenStream {
+    persist::derive_persist_impl(input)
+}
+
+#[proc_macro_derive(SceneValue)]
+pub fn derive_scene_value(input: TokenStream) -> TokenStream {
+    scene::derive_scene_value_impl(input)
+}
+
 #[proc_macro]
 pub fn component_tuple_arity(input: TokenStream) -> TokenStream {
     let tp = parse_macro_input!(input as Type);
@@ -39,7 +57,10 @@ pub fn component_tuple_nth_element(input: TokenStream) -> TokenStream {
     component_tuple_nth_element_impl(input)
 }
 
-#[proc_macro_derive(RegistryQuery, attributes(read_only, read_write))]
+#[proc_macro_derive(
+    RegistryQuery,
+    attributes(read_only, read_write, optional, with, without, any_of, added, changed)
+)]
 pub fn derive_registry_query(input: TokenStream) -> TokenStream {
     derive_registry_query_impl(input)
 }
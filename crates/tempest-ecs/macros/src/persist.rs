@@ -0,0 +1,170 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitInt, LitStr};
+
+/// Arguments of the `persist` attribute placed on the derived type.
+struct PersistArgs {
+    name: LitStr,
+    version: LitInt,
+}
+
+impl PersistArgs {
+    fn from_input(input: &DeriveInput) -> syn::Result<Self> {
+        let mut args = Self {
+            name: LitStr::new(&input.ident.to_string(), input.ident.span()),
+            version: LitInt::new("1", Span::call_site()),
+        };
+
+        for attr in input
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("persist"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    args.name = meta.value()?.parse()?;
+                    Ok(())
+                } else if meta.path.is_ident("version") {
+                    args.version = meta.value()?.parse()?;
+                    args.version.base10_parse::<u32>().map(|_| ())
+                } else {
+                    Err(meta.error("Expected `name` or `version`."))
+                }
+            })?;
+        }
+
+        Ok(args)
+    }
+}
+
+/// Bindings of the fields of a struct or variant, named `field_0`, `field_1`, ... in declaration order.
+pub(crate) fn bindings(fields: &Fields) -> Vec<Ident> {
+    (0..fields.len())
+        .map(|idx| Ident::new(&format!("field_{}", idx), Span::call_site()))
+        .collect()
+}
+
+/// Pattern destructuring the fields into their bindings, or constructing the fields from them.
+pub(crate) fn fields_pattern(fields: &Fields, bindings: &[Ident]) -> proc_macro2::TokenStream {
+    match fields {
+        Fields::Named(named) => {
+            let names = named.named.iter().map(|field| &field.ident);
+            quote! { { #(#names: #bindings),* } }
+        }
+        Fields::Unnamed(_) => quote! { ( #(#bindings),* ) },
+        Fields::Unit => quote! {},
+    }
+}
+
+fn encode_fields(bindings: &[Ident]) -> proc_macro2::TokenStream {
+    quote! {
+        #(tempest_ecs::save::Encode::encode(#bindings, encoder);)*
+    }
+}
+
+fn decode_fields(bindings: &[Ident]) -> proc_macro2::TokenStream {
+    quote! {
+        #(let #bindings = tempest_ecs::save::Decode::decode(decoder)?;)*
+    }
+}
+
+pub fn derive_persist_impl(input: TokenStream) -> TokenStream {
+    let tokens = parse_macro_input!(input as DeriveInput);
+    let type_name = &tokens.ident;
+    let args = match PersistArgs::from_input(&tokens) {
+        Ok(args) => args,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let (encode_body, decode_body) = match &tokens.data {
+        Data::Struct(data) => {
+            let bindings = bindings(&data.fields);
+            let pattern = fields_pattern(&data.fields, &bindings);
+            let encode = encode_fields(&bindings);
+            let decode = decode_fields(&bindings);
+
+            (
+                quote! {
+                    let #type_name #pattern = self;
+                    #encode
+                },
+                quote! {
+                    #decode
+                    Ok(#type_name #pattern)
+                },
+            )
+        }
+        Data::Enum(data) => {
+            let mut encode_arms = Vec::new();
+            let mut decode_arms = Vec::new();
+
+            for (idx, variant) in data.variants.iter().enumerate() {
+                let idx = idx as u32;
+                let variant_name = &variant.ident;
+                let bindings = bindings(&variant.fields);
+                let pattern = fields_pattern(&variant.fields, &bindings);
+                let encode = encode_fields(&bindings);
+                let decode = decode_fields(&bindings);
+
+                encode_arms.push(quote! {
+                    #type_name::#variant_name #pattern => {
+                        tempest_ecs::save::Encode::encode(&#idx, encoder);
+                        #encode
+                    }
+                });
+                decode_arms.push(quote! {
+                    #idx => {
+                        #decode
+                        Ok(#type_name::#variant_name #pattern)
+                    }
+                });
+            }
+
+            (
+                quote! {
+                    match self {
+                        #(#encode_arms)*
+                    }
+                },
+                quote! {
+                    match <u32 as tempest_ecs::save::Decode>::decode(decoder)? {
+                        #(#decode_arms)*
+                        _ => Err(tempest_ecs::save::SaveError::InvalidData("Unknown enum variant.")),
+                    }
+                },
+            )
+        }
+        Data::Union(_) => {
+            return syn::Error::new_spanned(type_name, "Unions cannot be persisted.")
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    let name = &args.name;
+    let version = &args.version;
+    let generated = quote! {
+        impl tempest_ecs::save::Encode for #type_name {
+            #[allow(unused_variables)]
+            fn encode(&self, encoder: &mut tempest_ecs::save::Encoder) {
+                #encode_body
+            }
+        }
+
+        impl tempest_ecs::save::Decode for #type_name {
+            #[allow(unused_variables)]
+            fn decode(
+                decoder: &mut tempest_ecs::save::Decoder,
+            ) -> Result<Self, tempest_ecs::save::SaveError> {
+                #decode_body
+            }
+        }
+
+        impl tempest_ecs::save::Persist for #type_name {
+            const NAME: &'static str = #name;
+            const VERSION: u32 = #version;
+        }
+    };
+
+    generated.into()
+}
@@ -14,224 +14,284 @@ impl Parse for TypeListArgs {
             Ok(types) => Ok(TypeListArgs {
                 types: types.into_iter().collect(),
             }),
-            Err(_) => panic!("Failed to parse types: {}", input.to_string()),
+            Err(_) => panic!("Failed to parse types: {}", input),
         }
     }
 }
 
-fn create_registry_query(
-    ro_args: Option<TypeListArgs>,
-    rw_args: Option<TypeListArgs>,
-) -> proc_macro2::TokenStream {
-    // TODO: Optimize for single component case
-
-    let mut rw_types = rw_args.clone().unwrap_or_default().types;
-    let mut all_types = ro_args.clone().unwrap_or_default().types;
-    all_types.append(&mut rw_types);
-
-    let mut contains_quote = quote! {};
-    for tp in &all_types {
-        let ident = tp.get_ident();
-        if contains_quote.is_empty() {
-            contains_quote = quote! { reg.contains_component_from_iter::<#ident>(it) };
-        } else {
-            contains_quote =
-                quote! { #contains_quote && reg.contains_component_from_iter::<#ident>(it) };
+/// Component lists of every query attribute placed on the derived type.
+#[derive(Default)]
+struct QueryArgs {
+    read_only: Vec<Path>,
+    read_write: Vec<Path>,
+    optional: Vec<Path>,
+    with: Vec<Path>,
+    without: Vec<Path>,
+    any_of: Vec<Path>,
+    added: Vec<Path>,
+    changed: Vec<Path>,
+}
+
+impl QueryArgs {
+    fn from_attributes(attrs: &[syn::Attribute]) -> Self {
+        let mut args = Self::default();
+
+        for attr in attrs {
+            if attr.path().segments.len() != 1 {
+                continue;
+            }
+
+            let list = match attr.path().segments[0].ident.to_string().as_str() {
+                "read_only" => &mut args.read_only,
+                "read_write" => &mut args.read_write,
+                "optional" => &mut args.optional,
+                "with" => &mut args.with,
+                "without" => &mut args.without,
+                "any_of" => &mut args.any_of,
+                "added" => &mut args.added,
+                "changed" => &mut args.changed,
+                _ => continue,
+            };
+
+            list.append(&mut attr.parse_args::<TypeListArgs>().unwrap().types);
         }
+
+        args
     }
 
-    let mut var_idx = 0;
-    let mut fetch_quote = quote! {};
-    let mut check_exists_quote = quote! {};
-    let mut extract_ref_quote = quote! {};
-    let mut extract_mut_quote = quote! {};
+    /// Finds a component that is written by the query, and also read or written a second time.
+    fn find_alias(&self) -> Option<&Path> {
+        let name = |tp: &Path| quote! { #tp }.to_string();
 
-    let ro_count = ro_args.map(|ro| ro.types.len()).unwrap_or(0);
-    let rw_count = rw_args.map(|rw| rw.types.len()).unwrap_or(0);
+        self.read_write
+            .iter()
+            .enumerate()
+            .find(|(idx, tp)| {
+                self.read_only
+                    .iter()
+                    .chain(self.optional.iter())
+                    .chain(self.read_write[idx + 1..].iter())
+                    .any(|other| name(other) == name(tp))
+            })
+            .map(|(_, tp)| tp)
+    }
 
-    for tp in &all_types {
-        let ident = tp.get_ident();
-        let var_name = syn::Ident::new(&format! {"var_{}", var_idx}, Span::call_site());
+    /// Components that every entity matched by the query is required to have.
+    fn required(&self) -> impl Iterator<Item = &Path> {
+        self.read_only
+            .iter()
+            .chain(self.read_write.iter())
+            .chain(self.with.iter())
+            .chain(self.added.iter())
+            .chain(self.changed.iter())
+    }
+}
 
-        if var_idx < ro_count {
-            fetch_quote = quote! {
-                #fetch_quote
-                let #var_name = reg.get_component_ref_from_iter::<#ident>(it);
-            };
-        } else {
-            fetch_quote = quote! {
-                #fetch_quote
-                let mut #var_name = reg.get_component_mut_from_iter::<#ident>(it);
-            };
-        }
+/// Joins the non-empty groups of a query result.  A single group is returned as is, several groups are nested in a
+/// tuple, in the order they are provided.
+fn join_groups(groups: Vec<Vec<proc_macro2::TokenStream>>) -> proc_macro2::TokenStream {
+    let groups: Vec<_> = groups
+        .into_iter()
+        .filter(|group| !group.is_empty())
+        .map(|group| quote! { (#(#group),*) })
+        .collect();
 
-        if var_idx == 0 {
-            check_exists_quote = quote! {
-                #check_exists_quote #var_name.is_some()
-            };
+    match groups.len() {
+        0 => quote! { () },
+        1 => groups[0].clone(),
+        _ => quote! { (#(#groups),*) },
+    }
+}
 
-            if var_idx < ro_count {
-                extract_ref_quote = quote! {
-                    unsafe { #var_name.unwrap_unchecked() }
-                }
-            } else {
-                extract_mut_quote = quote! {
-                    unsafe { #var_name.unwrap_unchecked() }
-                }
-            }
-        } else {
-            check_exists_quote = quote! {
-                #check_exists_quote && #var_name.is_some()
-            };
+fn create_result_type(args: &QueryArgs) -> proc_macro2::TokenStream {
+    let read_only = args.read_only.iter().map(|tp| quote! { &'r #tp }).collect();
+    let read_write = args
+        .read_write
+        .iter()
+        .map(|tp| quote! { &'r mut #tp })
+        .collect();
+    let optional = args
+        .optional
+        .iter()
+        .map(|tp| quote! { Option<&'r #tp> })
+        .collect();
 
-            if var_idx < ro_count {
-                extract_ref_quote = quote! {
-                    #extract_ref_quote, unsafe { #var_name.unwrap_unchecked() }
-                };
-            } else if var_idx == ro_count {
-                extract_mut_quote = quote! {
-                    unsafe { #var_name.unwrap_unchecked() }
-                }
-            } else {
-                extract_mut_quote = quote! {
-                    #extract_mut_quote, unsafe { #var_name.unwrap_unchecked() }
-                };
-            }
-        }
+    join_groups(vec![read_only, read_write, optional])
+}
 
-        var_idx += 1;
+fn create_registry_query(args: &QueryArgs) -> proc_macro2::TokenStream {
+    let mut filter_quote = quote! { true };
+    for tp in &args.with {
+        filter_quote = quote! { #filter_quote && reg.contains_component_from_iter::<#tp>(it) };
+    }
+    for tp in &args.without {
+        filter_quote = quote! { #filter_quote && !reg.contains_component_from_iter::<#tp>(it) };
+    }
+    if !args.any_of.is_empty() {
+        let any_of = args
+            .any_of
+            .iter()
+            .map(|tp| quote! { reg.contains_component_from_iter::<#tp>(it) });
+        filter_quote = quote! { #filter_quote && (#(#any_of)||*) };
     }
 
-    if ro_count > 0 && rw_count == 0 {
-        fetch_quote = quote! {
-            #fetch_quote
-            let exists = #check_exists_quote;
-            if exists {
-                Some((#extract_ref_quote))
-            } else {
-                None
-            }
-        };
-    } else if ro_count == 0 && rw_count > 0 {
-        fetch_quote = quote! {
-            #fetch_quote
-            let exists = #check_exists_quote;
-            if exists {
-                Some((#extract_mut_quote))
-            } else {
-                None
-            }
-        };
-    } else if ro_count > 0 && rw_count > 0 {
+    let mut contains_quote = filter_quote.clone();
+    for tp in args.read_only.iter().chain(args.read_write.iter()) {
+        contains_quote = quote! { #contains_quote && reg.contains_component_from_iter::<#tp>(it) };
+    }
+    for tp in args.added.iter().chain(args.changed.iter()) {
+        contains_quote = quote! { #contains_quote && reg.contains_component_from_iter::<#tp>(it) };
+    }
+
+    // Ticks differ between the rows of an archetype, so they are only checked when fetching
+    let mut tick_filter_quote = filter_quote.clone();
+    for tp in &args.added {
+        tick_filter_quote =
+            quote! { #tick_filter_quote && reg.is_component_added_from_iter::<#tp>(it) };
+    }
+    for tp in &args.changed {
+        tick_filter_quote =
+            quote! { #tick_filter_quote && reg.is_component_changed_from_iter::<#tp>(it) };
+    }
+
+    // Iterate a group owning only required components if there is one, or else the smallest pool among them, and only
+    // probe the others for its keys
+    let driver_quote = if args.required().next().is_none() {
+        quote! { None }
+    } else {
+        let pools = args.required().map(|tp| quote! { reg.pool_keys::<#tp>() });
+        let ids = args
+            .required()
+            .map(|tp| quote! { <#tp as tempest_ecs::component::Component>::id() });
+        quote! {
+            reg.group_keys(&[#(#ids),*])
+                .or_else(|| tempest_ecs::registry::PoolKeys::smallest([#(#pools),*]))
+        }
+    };
+
+    let mut fetch_quote = quote! {
+        if !(#tick_filter_quote) {
+            return None;
+        }
+    };
+    let mut check_exists_quote = quote! { true };
+    let mut read_only = Vec::new();
+    let mut read_write = Vec::new();
+    let mut optional = Vec::new();
+
+    for (var_idx, tp) in args.read_only.iter().enumerate() {
+        let var_name = syn::Ident::new(&format!("ro_{}", var_idx), Span::call_site());
         fetch_quote = quote! {
             #fetch_quote
-            let exists = #check_exists_quote;
-            if exists {
-                Some(((#extract_ref_quote), (#extract_mut_quote)))
-            } else {
-                None
-            }
+            let #var_name = reg.get_component_ref_from_iter::<#tp>(it);
         };
-    } else {
+        check_exists_quote = quote! { #check_exists_quote && #var_name.is_some() };
+        read_only.push(quote! { unsafe { #var_name.unwrap_unchecked() } });
+    }
+
+    for (var_idx, tp) in args.read_write.iter().enumerate() {
+        let var_name = syn::Ident::new(&format!("rw_{}", var_idx), Span::call_site());
         fetch_quote = quote! {
             #fetch_quote
-            let exists = #check_exists_quote;
-            if exists {
-                Some(())
-            } else {
-                None
-            }
+            let #var_name = reg.get_component_mut_from_iter::<#tp>(it);
         };
+        check_exists_quote = quote! { #check_exists_quote && #var_name.is_some() };
+        read_write.push(quote! { unsafe { #var_name.unwrap_unchecked() } });
     }
 
+    for tp in &args.optional {
+        optional.push(quote! { reg.get_component_ref_from_iter::<#tp>(it) });
+    }
+
+    let result_quote = join_groups(vec![read_only, read_write, optional]);
+
+    // Tick filters read the slot of their component, which is already covered when the query writes it
+    let written: Vec<_> = args
+        .read_write
+        .iter()
+        .map(|tp| quote! { #tp }.to_string())
+        .collect();
+    let tick_reads = args
+        .added
+        .iter()
+        .chain(args.changed.iter())
+        .filter(|tp| !written.contains(&quote! { #tp }.to_string()));
+
+    let reads = args
+        .read_only
+        .iter()
+        .chain(args.optional.iter())
+        .chain(tick_reads)
+        .map(|tp| quote! { access.read(<#tp as tempest_ecs::component::Component>::id()); });
+    let writes = args
+        .read_write
+        .iter()
+        .map(|tp| quote! { access.write(<#tp as tempest_ecs::component::Component>::id()); });
+
     quote! {
         fn contains(it: tempest_ecs::registry::QueryIterator, reg: &'r tempest_ecs::registry::Registry) -> bool
         {
             #contains_quote
         }
 
-        fn fetch(it: tempest_ecs::registry::QueryIterator, reg: &'r tempest_ecs::registry::Registry) -> Option<Self::Result>
+        unsafe fn fetch(it: tempest_ecs::registry::QueryIterator, reg: &'r tempest_ecs::registry::Registry) -> Option<Self::Result>
         {
             #fetch_quote
-        }
-    }
-}
-
-pub fn derive_registry_query_impl(input: TokenStream) -> TokenStream {
-    let tokens = parse_macro_input!(input as DeriveInput);
-    let attrs = &tokens.attrs;
-    let type_name = &tokens.ident;
-
-    let readonly_type_list = attrs
-        .iter()
-        .filter(|attr| {
-            attr.path().segments.len() == 1 && attr.path().segments[0].ident == "read_only"
-        })
-        .nth(0)
-        .map(|attr| attr.parse_args::<TypeListArgs>().unwrap());
-
-    let readwrite_type_list = attrs
-        .iter()
-        .filter(|attr| {
-            attr.path().segments.len() == 1 && attr.path().segments[0].ident == "read_write"
-        })
-        .nth(0)
-        .map(|attr| attr.parse_args::<TypeListArgs>().unwrap());
-
-    let readonly_attr = readonly_type_list.as_ref().map(|args| {
-        let mut list = quote! {};
-        for elem in 0..args.types.len() {
-            let tp = &args.types[elem];
-            if elem == args.types.len() - 1 {
-                list = quote! { #list &'r #tp };
+            if #check_exists_quote {
+                Some(#result_quote)
             } else {
-                list = quote! { #list &'r #tp, };
+                None
             }
         }
 
-        list
-    });
+        fn access(access: &mut tempest_ecs::access::Access)
+        {
+            #(#reads)*
+            #(#writes)*
+        }
 
-    let readwrite_attr = readwrite_type_list.as_ref().map(|args| {
-        let mut list = quote! {};
-        for elem in 0..args.types.len() {
-            let tp = &args.types[elem];
-            if elem == args.types.len() - 1 {
-                list = quote! { #list &'r mut #tp };
-            } else {
-                list = quote! { #list &'r mut #tp, };
-            }
+        fn driver(reg: &'r tempest_ecs::registry::Registry) -> Option<tempest_ecs::registry::PoolKeys<'r>>
+        {
+            #driver_quote
         }
+    }
+}
 
-        list
-    });
+pub fn derive_registry_query_impl(input: TokenStream) -> TokenStream {
+    let tokens = parse_macro_input!(input as DeriveInput);
+    let type_name = &tokens.ident;
+    let args = QueryArgs::from_attributes(&tokens.attrs);
 
-    let result_tokens = if readonly_attr.is_some() && readwrite_attr.is_some() {
-        quote! {
-            ((#readonly_attr), (#readwrite_attr))
-        }
-    } else if readonly_attr.is_some() && readwrite_attr.is_none() {
-        quote! {
-            (#readonly_attr)
-        }
-    } else if readonly_attr.is_none() && readwrite_attr.is_some() {
-        quote! {
-            (#readwrite_attr)
-        }
-    } else {
-        quote! {()}
-    };
+    if let Some(tp) = args.find_alias() {
+        return syn::Error::new_spanned(
+            tp,
+            "Component is borrowed mutably more than once by the query.",
+        )
+        .to_compile_error()
+        .into();
+    }
 
     let generated = match tokens.data {
         syn::Data::Struct(_) => {
-            let query = create_registry_query(readonly_type_list, readwrite_type_list);
+            let result_tokens = create_result_type(&args);
+            let query = create_registry_query(&args);
+
+            let read_only_quote = if args.read_write.is_empty() {
+                quote! {
+                    unsafe impl<'r> tempest_ecs::registry::ReadOnlyRegistryQuery<'r> for #type_name {}
+                }
+            } else {
+                quote! {}
+            };
 
             quote! {
                 impl<'r> tempest_ecs::registry::RegistryQuery<'r> for #type_name {
                     type Result = #result_tokens;
-                    
+
                     #query
                 }
+
+                #read_only_quote
             }
         }
         syn::Data::Enum(_) => unimplemented!("Enum type not supported."),
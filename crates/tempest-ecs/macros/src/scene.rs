@@ -0,0 +1,153 @@
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident};
+
+use crate::persist::{bindings, fields_pattern};
+
+/// Scene value of the fields, from their bindings.
+fn fields_to_value(fields: &Fields, bindings: &[Ident]) -> proc_macro2::TokenStream {
+    match fields {
+        Fields::Named(named) => {
+            let names = named
+                .named
+                .iter()
+                .map(|field| field.ident.as_ref().unwrap().to_string());
+            quote! {
+                tempest_ecs::scene::Value::Struct(vec![
+                    #((
+                        String::from(#names),
+                        tempest_ecs::scene::SceneValue::to_value(#bindings, names),
+                    )),*
+                ])
+            }
+        }
+        Fields::Unnamed(_) => quote! {
+            tempest_ecs::scene::Value::Tuple(vec![
+                #(tempest_ecs::scene::SceneValue::to_value(#bindings, names)),*
+            ])
+        },
+        Fields::Unit => quote! { tempest_ecs::scene::Value::Unit },
+    }
+}
+
+/// Bindings of the fields, from the scene value held by `value`.
+fn fields_from_value(fields: &Fields, bindings: &[Ident]) -> proc_macro2::TokenStream {
+    match fields {
+        Fields::Named(named) => {
+            let names: Vec<String> = named
+                .named
+                .iter()
+                .map(|field| field.ident.as_ref().unwrap().to_string())
+                .collect();
+            quote! {
+                let fields = value.expect_struct(&[#(#names),*])?;
+                #(let #bindings = tempest_ecs::scene::field(fields, #names, names)?;)*
+            }
+        }
+        Fields::Unnamed(unnamed) => {
+            let len = unnamed.unnamed.len();
+            let indices = 0..len;
+            quote! {
+                let values = value.expect_tuple(#len)?;
+                #(let #bindings = tempest_ecs::scene::SceneValue::from_value(&values[#indices], names)?;)*
+            }
+        }
+        Fields::Unit => quote! {
+            value.expect_unit()?;
+        },
+    }
+}
+
+pub fn derive_scene_value_impl(input: TokenStream) -> TokenStream {
+    let tokens = parse_macro_input!(input as DeriveInput);
+    let type_name = &tokens.ident;
+
+    let (to_value_body, from_value_body) = match &tokens.data {
+        Data::Struct(data) => {
+            let bindings = bindings(&data.fields);
+            let pattern = fields_pattern(&data.fields, &bindings);
+            let to_value = fields_to_value(&data.fields, &bindings);
+            let from_value = fields_from_value(&data.fields, &bindings);
+
+            (
+                quote! {
+                    let #type_name #pattern = self;
+                    #to_value
+                },
+                quote! {
+                    #from_value
+                    Ok(#type_name #pattern)
+                },
+            )
+        }
+        Data::Enum(data) => {
+            let mut to_value_arms = Vec::new();
+            let mut from_value_arms = Vec::new();
+
+            for variant in &data.variants {
+                let variant_name = &variant.ident;
+                let variant_str = variant_name.to_string();
+                let bindings = bindings(&variant.fields);
+                let pattern = fields_pattern(&variant.fields, &bindings);
+                let to_value = fields_to_value(&variant.fields, &bindings);
+                let from_value = fields_from_value(&variant.fields, &bindings);
+
+                to_value_arms.push(quote! {
+                    #type_name::#variant_name #pattern => tempest_ecs::scene::Value::Variant(
+                        String::from(#variant_str),
+                        Box::new(#to_value),
+                    ),
+                });
+                from_value_arms.push(quote! {
+                    #variant_str => {
+                        #from_value
+                        Ok(#type_name::#variant_name #pattern)
+                    }
+                });
+            }
+
+            (
+                quote! {
+                    match self {
+                        #(#to_value_arms)*
+                    }
+                },
+                quote! {
+                    let (variant, value) = value.expect_variant()?;
+                    match variant {
+                        #(#from_value_arms)*
+                        _ => Err(tempest_ecs::scene::SceneError::InvalidValue(
+                            format!("Unknown variant {}.", variant),
+                        )),
+                    }
+                },
+            )
+        }
+        Data::Union(_) => {
+            return syn::Error::new_spanned(type_name, "Unions cannot be used in scenes.")
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    let generated = quote! {
+        impl tempest_ecs::scene::SceneValue for #type_name {
+            #[allow(unused_variables)]
+            fn to_value(
+                &self,
+                names: &tempest_ecs::scene::EntityNames,
+            ) -> tempest_ecs::scene::Value {
+                #to_value_body
+            }
+
+            #[allow(unused_variables)]
+            fn from_value(
+                value: &tempest_ecs::scene::Value,
+                names: &tempest_ecs::scene::EntityNames,
+            ) -> Result<Self, tempest_ecs::scene::SceneError> {
+                #from_value_body
+            }
+        }
+    };
+
+    generated.into()
+}
@@ -0,0 +1,268 @@
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicIsize, Ordering},
+};
+
+/// Set of components read and written by a query.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Access {
+    reads: Vec<usize>,
+    writes: Vec<usize>,
+}
+
+impl Access {
+    pub fn read(&mut self, component_id: usize) -> &mut Self {
+        self.reads.push(component_id);
+        self
+    }
+
+    pub fn write(&mut self, component_id: usize) -> &mut Self {
+        self.writes.push(component_id);
+        self
+    }
+
+    pub fn reads(&self) -> &[usize] {
+        &self.reads
+    }
+
+    pub fn writes(&self) -> &[usize] {
+        &self.writes
+    }
+
+    /// Finds a component accessed mutably more than once, or both mutably and immutably, by the same query.
+    pub fn find_alias(&self) -> Option<usize> {
+        self.writes.iter().enumerate().find_map(|(idx, id)| {
+            let aliased = self.writes[idx + 1..].contains(id) || self.reads.contains(id);
+            aliased.then_some(*id)
+        })
+    }
+
+    /// Finds a component written by one of the accesses and accessed by the other.
+    pub fn find_conflict(&self, other: &Access) -> Option<usize> {
+        self.writes
+            .iter()
+            .find(|id| other.reads.contains(id) || other.writes.contains(id))
+            .or_else(|| self.reads.iter().find(|id| other.writes.contains(id)))
+            .copied()
+    }
+
+    pub fn is_compatible(&self, other: &Access) -> bool {
+        self.find_conflict(other).is_none()
+    }
+
+    /// Checks if every component accessed here is accessed at least as strongly by the other access.
+    pub fn is_covered_by(&self, other: &Access) -> bool {
+        self.reads
+            .iter()
+            .all(|id| other.reads.contains(id) || other.writes.contains(id))
+            && self.writes.iter().all(|id| other.writes.contains(id))
+    }
+
+    pub fn extend(&mut self, other: &Access) {
+        self.reads.extend_from_slice(&other.reads);
+        self.writes.extend_from_slice(&other.writes);
+    }
+}
+
+/// Error returned when the components of a query cannot be borrowed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BorrowError {
+    /// The component is already borrowed mutably.
+    AlreadyMutablyBorrowed(usize),
+
+    /// The component is already borrowed, and cannot be borrowed mutably.
+    AlreadyBorrowed(usize),
+
+    /// The query itself accesses the component mutably more than once.
+    Aliased(usize),
+}
+
+impl Display for BorrowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AlreadyMutablyBorrowed(id) => {
+                write!(f, "Component {} is already mutably borrowed.", id)
+            }
+            Self::AlreadyBorrowed(id) => write!(f, "Component {} is already borrowed.", id),
+            Self::Aliased(id) => write!(f, "Component {} is aliased mutably within a query.", id),
+        }
+    }
+}
+
+impl std::error::Error for BorrowError {}
+
+/// Runtime borrow state of every component pool.  A positive flag counts the shared borrows of a pool, and a flag of
+/// `-1` marks an exclusive borrow.
+#[derive(Default)]
+pub(crate) struct BorrowFlags {
+    flags: Vec<AtomicIsize>,
+}
+
+impl BorrowFlags {
+    pub(crate) fn new(len: usize) -> Self {
+        Self {
+            flags: (0..len).map(|_| AtomicIsize::new(0)).collect(),
+        }
+    }
+
+    /// Acquires every borrow of the access, or none of them.
+    pub(crate) fn acquire(&self, access: &Access) -> Result<(), BorrowError> {
+        if let Some(id) = access.find_alias() {
+            return Err(BorrowError::Aliased(id));
+        }
+
+        for (idx, id) in access.reads.iter().enumerate() {
+            if let Err(err) = self.acquire_shared(*id) {
+                access.reads[..idx]
+                    .iter()
+                    .for_each(|id| self.release_shared(*id));
+                return Err(err);
+            }
+        }
+
+        for (idx, id) in access.writes.iter().enumerate() {
+            if let Err(err) = self.acquire_exclusive(*id) {
+                access.reads.iter().for_each(|id| self.release_shared(*id));
+                access.writes[..idx]
+                    .iter()
+                    .for_each(|id| self.release_exclusive(*id));
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn release(&self, access: &Access) {
+        access.reads.iter().for_each(|id| self.release_shared(*id));
+        access
+            .writes
+            .iter()
+            .for_each(|id| self.release_exclusive(*id));
+    }
+
+    fn acquire_shared(&self, id: usize) -> Result<(), BorrowError> {
+        // Components without a pool have no data to alias
+        let flag = match self.flags.get(id) {
+            Some(flag) => flag,
+            None => return Ok(()),
+        };
+
+        let mut current = flag.load(Ordering::Relaxed);
+        loop {
+            if current < 0 {
+                return Err(BorrowError::AlreadyMutablyBorrowed(id));
+            }
+
+            match flag.compare_exchange_weak(
+                current,
+                current + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Ok(()),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    fn acquire_exclusive(&self, id: usize) -> Result<(), BorrowError> {
+        let flag = match self.flags.get(id) {
+            Some(flag) => flag,
+            None => return Ok(()),
+        };
+
+        match flag.compare_exchange(0, -1, Ordering::Acquire, Ordering::Relaxed) {
+            Ok(_) => Ok(()),
+            Err(actual) if actual < 0 => Err(BorrowError::AlreadyMutablyBorrowed(id)),
+            Err(_) => Err(BorrowError::AlreadyBorrowed(id)),
+        }
+    }
+
+    fn release_shared(&self, id: usize) {
+        if let Some(flag) = self.flags.get(id) {
+            flag.fetch_sub(1, Ordering::Release);
+        }
+    }
+
+    fn release_exclusive(&self, id: usize) {
+        if let Some(flag) = self.flags.get(id) {
+            flag.store(0, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_alias() {
+        let mut access = Access::default();
+        access.read(0).write(1);
+        assert_eq!(access.find_alias(), None);
+
+        access.write(1);
+        assert_eq!(access.find_alias(), Some(1));
+
+        let mut mixed = Access::default();
+        mixed.read(2).write(2);
+        assert_eq!(mixed.find_alias(), Some(2));
+    }
+
+    #[test]
+    fn test_access_conflict() {
+        let mut reader = Access::default();
+        reader.read(0).read(1);
+
+        let mut other_reader = Access::default();
+        other_reader.read(1);
+        assert!(reader.is_compatible(&other_reader));
+
+        let mut writer = Access::default();
+        writer.write(1);
+        assert_eq!(reader.find_conflict(&writer), Some(1));
+        assert_eq!(writer.find_conflict(&reader), Some(1));
+    }
+
+    #[test]
+    fn test_access_coverage() {
+        let mut declared = Access::default();
+        declared.read(0).write(1);
+
+        let mut query = Access::default();
+        query.read(0).read(1);
+        assert!(query.is_covered_by(&declared));
+
+        query.write(0);
+        assert!(!query.is_covered_by(&declared));
+    }
+
+    #[test]
+    fn test_borrow_flags() {
+        let flags = BorrowFlags::new(3);
+
+        let mut read = Access::default();
+        read.read(0).read(1);
+
+        let mut write = Access::default();
+        write.read(2).write(1);
+
+        assert!(flags.acquire(&read).is_ok());
+        assert!(flags.acquire(&read).is_ok());
+        assert_eq!(flags.acquire(&write), Err(BorrowError::AlreadyBorrowed(1)));
+
+        flags.release(&read);
+        flags.release(&read);
+
+        // the failed acquisition must not leave the shared borrow of component 2 behind
+        assert!(flags.acquire(&write).is_ok());
+        assert_eq!(
+            flags.acquire(&read),
+            Err(BorrowError::AlreadyMutablyBorrowed(1))
+        );
+
+        flags.release(&write);
+        assert!(flags.acquire(&read).is_ok());
+    }
+}
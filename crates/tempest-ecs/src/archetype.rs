@@ -0,0 +1,502 @@
+use std::{any::Any, cell::UnsafeCell, cmp::Ordering, collections::HashMap};
+
+use super::{
+    component::{snapshot_cloner, Component},
+    registry::EntityKey,
+    snapshot::SnapshotError,
+    sparse_index::SparseTableIndex,
+    tick::Tracked,
+};
+
+/// Type-erased, contiguous storage for a single component type within an archetype.
+pub(crate) trait Column: Send + Sync {
+    fn erase(&mut self, row: usize);
+    fn move_row(&mut self, row: usize, dst: &mut dyn Column);
+    fn new_empty(&self) -> Box<dyn Column>;
+    fn reserve(&mut self, additional: usize);
+    fn shrink_to_fit(&mut self);
+    /// Reorders the rows so that row `i` holds the row previously at `order[i]`.
+    fn permute(&mut self, order: &[usize]);
+    /// Copies the column, cloning components with `cloner` if their type does not implement [Clone].  The cloner is
+    /// only looked up if the column holds rows.
+    fn snapshot(
+        &self,
+        cloner: Option<&(dyn Any + Send + Sync)>,
+    ) -> Result<Box<dyn Column>, SnapshotError>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Constructor of an empty column.
+pub(crate) type NewColumn = fn() -> Box<dyn Column>;
+
+/// Rows of a column.  Values are wrapped in an [UnsafeCell], as queries hand out mutable references to the rows of a
+/// column while only holding a shared reference to the storage.
+pub(crate) struct ColumnData<T>(Vec<UnsafeCell<Tracked<T>>>);
+
+// Concurrent access to the cells is ruled out by the borrow checks of the registry.
+unsafe impl<T: Component> Sync for ColumnData<T> {}
+
+impl<T> Default for ColumnData<T> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<T: Component> Column for ColumnData<T> {
+    fn erase(&mut self, row: usize) {
+        self.0.swap_remove(row);
+    }
+
+    fn move_row(&mut self, row: usize, dst: &mut dyn Column) {
+        let value = self.0.swap_remove(row);
+        dst.as_any_mut()
+            .downcast_mut::<ColumnData<T>>()
+            .expect("Column type mismatch.")
+            .0
+            .push(value);
+    }
+
+    fn new_empty(&self) -> Box<dyn Column> {
+        Box::<ColumnData<T>>::default()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.0.reserve(additional);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.0.shrink_to_fit();
+    }
+
+    fn permute(&mut self, order: &[usize]) {
+        self.0 = permuted(std::mem::take(&mut self.0), order);
+    }
+
+    fn snapshot(
+        &self,
+        cloner: Option<&(dyn Any + Send + Sync)>,
+    ) -> Result<Box<dyn Column>, SnapshotError> {
+        if self.0.is_empty() {
+            return Ok(self.new_empty());
+        }
+
+        let clone = snapshot_cloner::<T>(cloner)?;
+        Ok(Box::new(ColumnData(
+            self.0
+                .iter()
+                // Snapshots are taken through a shared reference to the registry, so no query is writing to the cells
+                .map(|cell| UnsafeCell::new(unsafe { &*cell.get() }.clone_with(clone)))
+                .collect(),
+        )))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Rows reordered so that row `i` holds the row previously at `order[i]`.
+fn permuted<T>(rows: Vec<T>, order: &[usize]) -> Vec<T> {
+    let mut rows: Vec<Option<T>> = rows.into_iter().map(Some).collect();
+    order
+        .iter()
+        .map(|row| rows[*row].take().expect("Row is moved twice."))
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct EntityLocation {
+    pub(crate) archetype: usize,
+    pub(crate) row: usize,
+}
+
+/// Table of all entities sharing the exact same set of components.  Each component is stored in its own column, and
+/// row `i` of every column belongs to `entities[i]`.
+pub(crate) struct Archetype {
+    components: Vec<usize>,
+    columns: Vec<Box<dyn Column>>,
+    entities: Vec<EntityKey>,
+}
+
+impl Archetype {
+    fn column_index(&self, component_id: usize) -> Option<usize> {
+        self.components.binary_search(&component_id).ok()
+    }
+
+    pub(crate) fn contains(&self, component_id: usize) -> bool {
+        self.column_index(component_id).is_some()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub(crate) fn entity(&self, row: usize) -> Option<EntityKey> {
+        self.entities.get(row).copied()
+    }
+
+    pub(crate) fn column<T: Component>(&self) -> Option<&Vec<UnsafeCell<Tracked<T>>>> {
+        self.column_index(T::id())
+            .and_then(|idx| self.columns[idx].as_any().downcast_ref::<ColumnData<T>>())
+            .map(|column| &column.0)
+    }
+
+    fn column_mut<T: Component>(&mut self) -> Option<&mut Vec<UnsafeCell<Tracked<T>>>> {
+        self.column_index(T::id())
+            .and_then(|idx| {
+                self.columns[idx]
+                    .as_any_mut()
+                    .downcast_mut::<ColumnData<T>>()
+            })
+            .map(|column| &mut column.0)
+    }
+
+    /// Removes the row from the entity list, returning the entity that was swapped into its place, if any.
+    fn swap_remove_entity(&mut self, row: usize) -> Option<EntityKey> {
+        self.entities.swap_remove(row);
+        self.entities.get(row).copied()
+    }
+}
+
+/// Component storage grouping entities into archetypes.
+pub(crate) struct ArchetypeStorage {
+    archetypes: Vec<Archetype>,
+    index: HashMap<Vec<usize>, usize>,
+    locations: Vec<Option<EntityLocation>>,
+}
+
+impl Default for ArchetypeStorage {
+    fn default() -> Self {
+        let empty = Archetype {
+            components: Vec::new(),
+            columns: Vec::new(),
+            entities: Vec::new(),
+        };
+
+        Self {
+            archetypes: vec![empty],
+            index: HashMap::from([(Vec::new(), 0)]),
+            locations: Vec::new(),
+        }
+    }
+}
+
+impl ArchetypeStorage {
+    /// Copies every archetype, keeping the rows of each in the same order.  `cloner` returns the cloner registered for
+    /// a component identifier, if any.
+    pub(crate) fn snapshot<'a>(
+        &self,
+        cloner: impl Fn(usize) -> Option<&'a (dyn Any + Send + Sync)>,
+    ) -> Result<Self, SnapshotError> {
+        let archetypes = self
+            .archetypes
+            .iter()
+            .map(|archetype| {
+                Ok(Archetype {
+                    components: archetype.components.clone(),
+                    columns: archetype
+                        .components
+                        .iter()
+                        .zip(archetype.columns.iter())
+                        .map(|(id, column)| column.snapshot(cloner(*id)))
+                        .collect::<Result<_, SnapshotError>>()?,
+                    entities: archetype.entities.clone(),
+                })
+            })
+            .collect::<Result<_, SnapshotError>>()?;
+
+        Ok(Self {
+            archetypes,
+            index: self.index.clone(),
+            locations: self.locations.clone(),
+        })
+    }
+
+    pub(crate) fn archetypes(&self) -> &[Archetype] {
+        &self.archetypes
+    }
+
+    /// Upper bound of the identifiers of the components stored in any archetype.
+    pub(crate) fn component_id_bound(&self) -> usize {
+        self.archetypes
+            .iter()
+            .filter_map(|archetype| archetype.components.last())
+            .max()
+            .map_or(0, |id| id + 1)
+    }
+
+    pub(crate) fn location(&self, entity: EntityKey) -> Option<EntityLocation> {
+        self.locations
+            .get(entity.index() as usize)
+            .copied()
+            .flatten()
+    }
+
+    pub(crate) fn spawn(&mut self, entity: EntityKey) {
+        self.spawn_in(entity, 0);
+    }
+
+    /// Places the entity in the archetype, whose columns the caller must then push a component to.
+    pub(crate) fn spawn_in(&mut self, entity: EntityKey, archetype: usize) {
+        let row = self.archetypes[archetype].entities.len();
+        self.archetypes[archetype].entities.push(entity);
+        self.set_location(entity, Some(EntityLocation { archetype, row }));
+    }
+
+    /// Finds the archetype storing exactly the components, creating it with the provided columns if needed.
+    pub(crate) fn find_or_create(&mut self, mut components: Vec<(usize, NewColumn)>) -> usize {
+        components.sort_unstable_by_key(|(id, _)| *id);
+        let ids: Vec<usize> = components.iter().map(|(id, _)| *id).collect();
+
+        match self.index.get(&ids) {
+            Some(idx) => *idx,
+            None => {
+                let columns = components.iter().map(|(_, column)| column()).collect();
+                self.create_archetype(ids, columns)
+            }
+        }
+    }
+
+    /// Reserves room for `additional` more entities in the archetype, whose keys are below `key_bound`.
+    pub(crate) fn reserve(&mut self, archetype: usize, additional: usize, key_bound: usize) {
+        let archetype = &mut self.archetypes[archetype];
+        archetype.entities.reserve(additional);
+        for column in archetype.columns.iter_mut() {
+            column.reserve(additional);
+        }
+
+        if key_bound > self.locations.len() {
+            self.locations.resize(key_bound, None);
+        }
+    }
+
+    /// Releases the spare capacity of every archetype, and the locations past the last entity placed in one.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        for archetype in self.archetypes.iter_mut() {
+            archetype.entities.shrink_to_fit();
+            for column in archetype.columns.iter_mut() {
+                column.shrink_to_fit();
+            }
+        }
+
+        while let Some(None) = self.locations.last() {
+            self.locations.pop();
+        }
+        self.locations.shrink_to_fit();
+    }
+
+    /// Sorts the rows of every archetype storing `T` by their component, leaving the other archetypes untouched.
+    pub(crate) fn sort<T: Component>(&mut self, mut compare: impl FnMut(&T, &T) -> Ordering) {
+        for (idx, archetype) in self.archetypes.iter_mut().enumerate() {
+            let column = match archetype.column::<T>() {
+                Some(column) => column,
+                None => continue,
+            };
+
+            // Exclusive access to the storage rules out any query writing to the cells
+            let value = |row: usize| unsafe { &(*column[row].get()).value };
+            let mut order: Vec<usize> = (0..column.len()).collect();
+            order.sort_by(|a, b| compare(value(*a), value(*b)));
+            let sorted = order.iter().enumerate().all(|(row, other)| row == *other);
+            if sorted {
+                continue;
+            }
+
+            for column in archetype.columns.iter_mut() {
+                column.permute(&order);
+            }
+            archetype.entities = permuted(std::mem::take(&mut archetype.entities), &order);
+            for (row, entity) in archetype.entities.iter().enumerate() {
+                self.locations[entity.index() as usize] = Some(EntityLocation {
+                    archetype: idx,
+                    row,
+                });
+            }
+        }
+    }
+
+    /// Pushes a component of an entity spawned with [ArchetypeStorage::spawn_in].
+    pub(crate) fn push<T: Component>(&mut self, archetype: usize, component: Tracked<T>) {
+        self.archetypes[archetype]
+            .column_mut::<T>()
+            .expect("Archetype is missing spawned column.")
+            .push(UnsafeCell::new(component));
+    }
+
+    pub(crate) fn despawn(&mut self, entity: EntityKey) {
+        if let Some(loc) = self.location(entity) {
+            let archetype = &mut self.archetypes[loc.archetype];
+            for column in archetype.columns.iter_mut() {
+                column.erase(loc.row);
+            }
+
+            if let Some(moved) = archetype.swap_remove_entity(loc.row) {
+                self.set_location(moved, Some(loc));
+            }
+
+            self.set_location(entity, None);
+        }
+    }
+
+    pub(crate) fn contains(&self, entity: EntityKey, component_id: usize) -> bool {
+        self.location(entity)
+            .map(|loc| self.archetypes[loc.archetype].contains(component_id))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn get<T: Component>(&self, loc: EntityLocation) -> Option<&Tracked<T>> {
+        let cell = self
+            .archetypes
+            .get(loc.archetype)?
+            .column::<T>()?
+            .get(loc.row)?;
+
+        // Mutable references to the value are only created through `get_unchecked_mut`, whose callers guarantee they
+        // do not outlive any shared one.
+        unsafe { cell.get().as_ref() }
+    }
+
+    pub(crate) fn get_mut<T: Component>(&mut self, loc: EntityLocation) -> Option<&mut Tracked<T>> {
+        self.archetypes
+            .get_mut(loc.archetype)?
+            .column_mut::<T>()?
+            .get_mut(loc.row)
+            .map(UnsafeCell::get_mut)
+    }
+
+    /// Fetches a mutable reference to a value through a shared reference to the storage.
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that no other reference to the value is alive for the lifetime of the returned one.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn get_unchecked_mut<T: Component>(
+        &self,
+        loc: EntityLocation,
+    ) -> Option<&mut Tracked<T>> {
+        let cell = self
+            .archetypes
+            .get(loc.archetype)?
+            .column::<T>()?
+            .get(loc.row)?;
+        cell.get().as_mut()
+    }
+
+    pub(crate) fn insert<T: Component>(&mut self, entity: EntityKey, component: Tracked<T>) {
+        let loc = match self.location(entity) {
+            Some(loc) => loc,
+            None => return,
+        };
+
+        if self.archetypes[loc.archetype].contains(T::id()) {
+            return;
+        }
+
+        let mut components = self.archetypes[loc.archetype].components.clone();
+        let position = components.binary_search(&T::id()).unwrap_err();
+        components.insert(position, T::id());
+
+        let target = match self.index.get(&components) {
+            Some(idx) => *idx,
+            None => {
+                let source = &self.archetypes[loc.archetype];
+                let mut columns: Vec<Box<dyn Column>> =
+                    source.columns.iter().map(|c| c.new_empty()).collect();
+                columns.insert(position, Box::<ColumnData<T>>::default());
+                self.create_archetype(components, columns)
+            }
+        };
+
+        self.move_entity(entity, loc, target);
+        self.archetypes[target]
+            .column_mut::<T>()
+            .expect("Archetype is missing inserted column.")
+            .push(UnsafeCell::new(component));
+    }
+
+    pub(crate) fn remove<T: Component>(&mut self, entity: EntityKey) -> Option<T> {
+        let loc = self.location(entity)?;
+        let position = self.archetypes[loc.archetype].column_index(T::id())?;
+
+        let mut components = self.archetypes[loc.archetype].components.clone();
+        components.remove(position);
+
+        let target = match self.index.get(&components) {
+            Some(idx) => *idx,
+            None => {
+                let source = &self.archetypes[loc.archetype];
+                let columns = source
+                    .columns
+                    .iter()
+                    .enumerate()
+                    .filter(|(idx, _)| *idx != position)
+                    .map(|(_, c)| c.new_empty())
+                    .collect();
+                self.create_archetype(components, columns)
+            }
+        };
+
+        let value = self.archetypes[loc.archetype]
+            .column_mut::<T>()
+            .expect("Archetype is missing removed column.")
+            .swap_remove(loc.row)
+            .into_inner()
+            .value;
+        self.move_entity(entity, loc, target);
+
+        Some(value)
+    }
+
+    fn create_archetype(&mut self, components: Vec<usize>, columns: Vec<Box<dyn Column>>) -> usize {
+        let idx = self.archetypes.len();
+        self.index.insert(components.clone(), idx);
+        self.archetypes.push(Archetype {
+            components,
+            columns,
+            entities: Vec::new(),
+        });
+        idx
+    }
+
+    /// Moves every column shared between the source and target archetype.  Columns that only exist in the source must
+    /// already have had the row removed by the caller.
+    fn move_entity(&mut self, entity: EntityKey, loc: EntityLocation, target: usize) {
+        let (source, destination) = if loc.archetype < target {
+            let (lhs, rhs) = self.archetypes.split_at_mut(target);
+            (&mut lhs[loc.archetype], &mut rhs[0])
+        } else {
+            let (lhs, rhs) = self.archetypes.split_at_mut(loc.archetype);
+            (&mut rhs[0], &mut lhs[target])
+        };
+
+        for (component, column) in source.components.iter().zip(source.columns.iter_mut()) {
+            if let Some(dst) = destination.column_index(*component) {
+                column.move_row(loc.row, destination.columns[dst].as_mut());
+            }
+        }
+
+        let new_loc = EntityLocation {
+            archetype: target,
+            row: destination.entities.len(),
+        };
+        destination.entities.push(entity);
+
+        if let Some(moved) = source.swap_remove_entity(loc.row) {
+            self.set_location(moved, Some(loc));
+        }
+        self.set_location(entity, Some(new_loc));
+    }
+
+    fn set_location(&mut self, entity: EntityKey, loc: Option<EntityLocation>) {
+        let idx = entity.index() as usize;
+        if idx >= self.locations.len() {
+            self.locations.resize(idx + 1, None);
+        }
+        self.locations[idx] = loc;
+    }
+}
@@ -0,0 +1,256 @@
+use super::{
+    archetype::{ColumnData, NewColumn},
+    component::Component,
+    registry::{Entity, EntityKey, Registry},
+    tick::Tracked,
+};
+
+pub use tempest_ecs_macros::Bundle;
+
+/// Set of components spawned together.  Derived for structs whose fields are components, and implemented for tuples
+/// of components.
+pub trait Bundle: Send + Sync + 'static {
+    /// Declares the component types of the bundle.
+    fn component_types(types: &mut BundleTypes);
+
+    /// Writes every component declared by [Bundle::component_types] exactly once, in the order they were declared.
+    fn write(self, writer: &mut BundleWriter<'_>);
+}
+
+/// Component type of a bundle, along with the operations spawning needs on its storage.
+#[derive(Clone, Copy)]
+pub(crate) struct BundleType {
+    pub(crate) id: usize,
+    pub(crate) name: &'static str,
+    pub(crate) reserve: fn(&mut Registry, usize),
+    pub(crate) column: NewColumn,
+    pub(crate) added: fn(&mut Registry, Entity),
+}
+
+/// Component types of a bundle, in declaration order.
+#[derive(Default)]
+pub struct BundleTypes {
+    types: Vec<BundleType>,
+}
+
+impl BundleTypes {
+    pub(crate) fn of<B: Bundle>() -> Vec<BundleType> {
+        let mut types = Self::default();
+        B::component_types(&mut types);
+        types.types
+    }
+
+    pub fn add<T: Component>(&mut self) {
+        let ty = BundleType {
+            id: T::id(),
+            name: std::any::type_name::<T>(),
+            reserve: Registry::reserve_pool::<T>,
+            column: || Box::<ColumnData<T>>::default(),
+            added: run_added_hooks::<T>,
+        };
+
+        if let Some(duplicate) = self.types.iter().find(|other| other.id == ty.id) {
+            panic!("Bundle contains component {} twice.", duplicate.name);
+        }
+        self.types.push(ty);
+    }
+}
+
+fn run_added_hooks<T: Component>(reg: &mut Registry, ent: Entity) {
+    reg.with_hooks::<T>(|reg, hooks| {
+        if let Some(component) = reg.get_component_ref::<T>(ent) {
+            hooks.added(ent, component);
+        }
+    });
+}
+
+/// Writes the components of a bundle to the entity being spawned.
+pub struct BundleWriter<'a> {
+    pub(crate) reg: &'a mut Registry,
+    pub(crate) key: EntityKey,
+    /// Archetype the entity was placed in, with archetype storage.
+    pub(crate) archetype: Option<usize>,
+    pub(crate) tick: u64,
+    /// Component types declared by the bundle, and the number of them written so far.
+    pub(crate) types: &'a [BundleType],
+    pub(crate) written: usize,
+}
+
+impl BundleWriter<'_> {
+    /// # Panics
+    ///
+    /// Panics if the component is not the next one declared by the bundle.
+    pub fn write<T: Component>(&mut self, component: T) {
+        let expected = self.types.get(self.written);
+        assert!(
+            expected.map_or(false, |ty| ty.id == T::id()),
+            "Bundle wrote component {} where it declared {}.",
+            std::any::type_name::<T>(),
+            expected.map_or("nothing", |ty| ty.name)
+        );
+
+        self.reg
+            .write_spawned(self.key, self.archetype, Tracked::new(component, self.tick));
+        self.written += 1;
+    }
+}
+
+macro_rules! bundle_tuple_impl {
+    (
+        $($T:ident ,)*
+    ) => (
+        impl<$($T: Component),*> Bundle for ($($T ,)*) {
+            #[allow(unused_variables)]
+            fn component_types(types: &mut BundleTypes) {
+                $(types.add::<$T>();)*
+            }
+
+            #[allow(unused_variables, non_snake_case)]
+            fn write(self, writer: &mut BundleWriter<'_>) {
+                let ($($T ,)*) = self;
+                $(writer.write($T);)*
+            }
+        }
+    );
+}
+
+macro_rules! bundle_tuple_impls {
+    (
+        $N:ident, $($k:ident ,)*
+    ) => (
+        bundle_tuple_impl!($N, $($k ,)*);
+        bundle_tuple_impls!($($k ,)*);
+    );
+
+    () => (
+        bundle_tuple_impl!();
+    );
+}
+
+bundle_tuple_impls! {
+    _16, _15, _14, _13, _12, _11, _10, _9, _8, _7, _6, _5, _4, _3, _2, _1,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use tempest_ecs_macros::RegistryQuery;
+
+    use super::*;
+    use crate::registry::StorageKind;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Position(i32, i32);
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Velocity(i32, i32);
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Mesh(&'static str);
+
+    #[derive(Bundle)]
+    struct Renderable {
+        position: Position,
+        velocity: Velocity,
+        mesh: Mesh,
+    }
+
+    #[derive(Bundle)]
+    struct Twice(Position, Position);
+
+    /// Declares a position, but writes whatever it holds.
+    struct Mislabeled<T>(Option<T>);
+
+    impl<T: Component> Bundle for Mislabeled<T> {
+        fn component_types(types: &mut BundleTypes) {
+            types.add::<Position>();
+        }
+
+        fn write(self, writer: &mut BundleWriter<'_>) {
+            if let Some(component) = self.0 {
+                writer.write(component);
+            }
+        }
+    }
+
+    #[derive(RegistryQuery)]
+    #[read_only(Position, Mesh)]
+    struct DrawnQuery;
+
+    #[derive(RegistryQuery)]
+    #[read_only(Position)]
+    struct PositionQuery;
+
+    fn spawning(kind: StorageKind) {
+        let mut reg = Registry::new(kind);
+        let added = Arc::new(AtomicUsize::new(0));
+        let counter = added.clone();
+        reg.on_add::<Mesh>(move |_, _| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let ent = reg.spawn(Renderable {
+            position: Position(1, 2),
+            velocity: Velocity(3, 4),
+            mesh: Mesh("cube"),
+        });
+        assert_eq!(reg.get_component::<Position>(ent), Some(Position(1, 2)));
+        assert_eq!(reg.get_component::<Velocity>(ent), Some(Velocity(3, 4)));
+        assert_eq!(reg.get_component::<Mesh>(ent), Some(Mesh("cube")));
+
+        let empty = reg.spawn(());
+        assert!(reg.is_alive(empty));
+        reg.destroy_entity(&empty);
+
+        let spawned = reg.spawn_batch((0..10_000).map(|i| (Position(i, -i), Mesh("quad"))));
+        assert_eq!(spawned.len(), 10_000);
+        for (i, ent) in spawned.iter().enumerate() {
+            assert_eq!(
+                reg.get_component::<Position>(*ent),
+                Some(Position(i as i32, -(i as i32)))
+            );
+            assert!(!reg.has_component::<Velocity>(*ent));
+        }
+        assert_eq!(added.load(Ordering::Relaxed), 10_001);
+
+        // spawned components are seen by queries and can be removed like assigned ones
+        assert_eq!(reg.query_registry::<DrawnQuery>().count(), 10_001);
+        assert_eq!(reg.remove_component::<Mesh>(spawned[5]), Some(Mesh("quad")));
+        reg.assign_component(spawned[5], Velocity(0, 0));
+        assert_eq!(
+            reg.get_component::<Position>(spawned[5]),
+            Some(Position(5, -5))
+        );
+        assert!(reg.destroy_entity(&spawned[6]));
+        assert_eq!(reg.query_registry::<PositionQuery>().count(), 10_000);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_spawning() {
+        spawning(StorageKind::Sparse);
+        spawning(StorageKind::Archetype);
+    }
+
+    #[test]
+    #[should_panic(expected = "twice")]
+    fn test_duplicate_component() {
+        Registry::new(StorageKind::Sparse).spawn(Twice(Position(0, 0), Position(1, 1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "where it declared")]
+    fn test_undeclared_component() {
+        Registry::new(StorageKind::Archetype).spawn(Mislabeled(Some(Velocity(0, 0))));
+    }
+
+    #[test]
+    #[should_panic(expected = "wrote 0 of its 1 components")]
+    fn test_missing_component() {
+        Registry::new(StorageKind::Sparse).spawn(Mislabeled::<Position>(None));
+    }
+}
@@ -0,0 +1,286 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::{
+    component::Component,
+    registry::{Entity, Registry},
+};
+
+/// Entity targeted by a command, either already alive in the registry or reserved by a [Commands::spawn] of the same
+/// buffer.
+#[derive(Clone, Copy)]
+pub enum CommandEntity {
+    Existing(Entity),
+    Reserved(Reservation),
+}
+
+/// Entity reserved by [Commands::spawn].  It can only be used with the buffer that reserved it, until that buffer is
+/// applied.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Reservation {
+    buffer: u64,
+    index: usize,
+}
+
+impl From<Entity> for CommandEntity {
+    fn from(entity: Entity) -> Self {
+        Self::Existing(entity)
+    }
+}
+
+type Operation = Box<dyn FnOnce(&mut Registry, Entity) + Send>;
+
+enum Command {
+    Spawn(usize),
+    Apply(CommandEntity, Operation),
+}
+
+/// Buffer of structural changes recorded while the registry is borrowed, and applied later in a single flush.
+pub struct Commands {
+    commands: Vec<Command>,
+    reserved: usize,
+    buffer: u64,
+}
+
+/// Identifies the reservations of a buffer between two flushes.
+fn next_buffer_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+impl Default for Commands {
+    fn default() -> Self {
+        Self {
+            commands: Vec::new(),
+            reserved: 0,
+            buffer: next_buffer_id(),
+        }
+    }
+}
+
+impl Commands {
+    /// Reserves a new entity, created when the buffer is applied.
+    pub fn spawn(&mut self) -> CommandEntity {
+        let index = self.reserved;
+        self.reserved += 1;
+        self.commands.push(Command::Spawn(index));
+        CommandEntity::Reserved(Reservation {
+            buffer: self.buffer,
+            index,
+        })
+    }
+
+    pub fn despawn(&mut self, entity: impl Into<CommandEntity>) -> &mut Self {
+        self.push(entity.into(), |reg, ent| {
+            reg.destroy_entity(&ent);
+        })
+    }
+
+    pub fn insert<T: Component>(
+        &mut self,
+        entity: impl Into<CommandEntity>,
+        component: T,
+    ) -> &mut Self {
+        self.push(entity.into(), move |reg, ent| {
+            reg.assign_component(ent, component);
+        })
+    }
+
+    pub fn remove<T: Component>(&mut self, entity: impl Into<CommandEntity>) -> &mut Self {
+        self.push(entity.into(), |reg, ent| {
+            reg.remove_component::<T>(ent);
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Applies every recorded command in order, leaving the buffer empty.  Commands targeting entities that are no
+    /// longer alive when they are applied are skipped.
+    ///
+    /// Returns the entities created for the reservations of the buffer, in the order they were reserved.
+    pub fn apply(&mut self, reg: &mut Registry) -> Vec<Entity> {
+        let mut spawned = Vec::with_capacity(self.reserved);
+
+        for command in self.commands.drain(..) {
+            match command {
+                Command::Spawn(index) => {
+                    debug_assert_eq!(index, spawned.len());
+                    spawned.push(reg.create_entity());
+                }
+                Command::Apply(entity, operation) => {
+                    let entity = match entity {
+                        CommandEntity::Existing(entity) => entity,
+                        CommandEntity::Reserved(reservation) => spawned[reservation.index],
+                    };
+                    operation(reg, entity);
+                }
+            }
+        }
+
+        self.reserved = 0;
+        self.buffer = next_buffer_id();
+        spawned
+    }
+
+    fn push<F: FnOnce(&mut Registry, Entity) + Send + 'static>(
+        &mut self,
+        entity: CommandEntity,
+        operation: F,
+    ) -> &mut Self {
+        if let CommandEntity::Reserved(reservation) = entity {
+            assert!(
+                reservation.buffer == self.buffer && reservation.index < self.reserved,
+                "Entity was not reserved by this buffer."
+            );
+        }
+
+        self.commands
+            .push(Command::Apply(entity, Box::new(operation)));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempest_ecs_macros::{Component, RegistryQuery};
+
+    use super::*;
+    use crate::registry::StorageKind;
+
+    #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    struct Health(u32);
+
+    #[derive(Component)]
+    struct Dead;
+
+    #[derive(RegistryQuery)]
+    #[read_only(Health)]
+    struct HealthQuery;
+
+    fn deferred_changes(kind: StorageKind) {
+        let mut reg = Registry::new(kind);
+        let entities: Vec<Entity> = (0..4).map(|_| reg.create_entity()).collect();
+        for (i, ent) in entities.iter().enumerate() {
+            reg.assign_component(*ent, Health(i as u32));
+        }
+
+        let mut commands = Commands::default();
+        for health in reg.query_registry::<HealthQuery>() {
+            let ent = &entities[health.0 as usize];
+            match health.0 {
+                3 => {
+                    commands.despawn(*ent);
+                }
+                2 => {
+                    commands.remove::<Health>(*ent).insert(*ent, Dead);
+                }
+                _ => {
+                    let child = commands.spawn();
+                    commands.insert(child, Health(health.0 + 10));
+                }
+            }
+        }
+        assert_eq!(commands.len(), 7);
+
+        let spawned = commands.apply(&mut reg);
+        assert!(commands.is_empty());
+        assert_eq!(spawned.len(), 2);
+        assert_eq!(reg.num_entities(), 5);
+
+        assert!(!reg.has_component::<Health>(entities[3]));
+        assert!(!reg.has_component::<Health>(entities[2]));
+        assert!(reg.has_component::<Dead>(entities[2]));
+
+        let mut children: Vec<u32> = spawned
+            .iter()
+            .map(|ent| reg.get_component::<Health>(*ent).unwrap().0)
+            .collect();
+        children.sort();
+        assert_eq!(children, vec![10, 11]);
+    }
+
+    #[test]
+    fn test_deferred_changes() {
+        deferred_changes(StorageKind::Sparse);
+        deferred_changes(StorageKind::Archetype);
+    }
+
+    #[test]
+    fn test_reserved_entity_lifecycle() {
+        let mut reg = Registry::default();
+        let mut commands = Commands::default();
+
+        let short_lived = commands.spawn();
+        commands.insert(short_lived, Health(1)).despawn(short_lived);
+        let kept = commands.spawn();
+        commands.insert(kept, Health(2));
+
+        let spawned = commands.apply(&mut reg);
+        assert_eq!(reg.num_entities(), 1);
+        assert!(!reg.has_component::<Health>(spawned[0]));
+        assert_eq!(reg.get_component::<Health>(spawned[1]), Some(Health(2)));
+
+        // the buffer is reusable, and reservations start over after a flush
+        let again = commands.spawn();
+        assert!(matches!(
+            again,
+            CommandEntity::Reserved(Reservation { index: 0, .. })
+        ));
+        assert_eq!(commands.apply(&mut reg).len(), 1);
+    }
+
+    #[test]
+    fn test_commands_on_destroyed_entity() {
+        let mut reg = Registry::default();
+        let ent = reg.create_entity();
+
+        let mut commands = Commands::default();
+        commands
+            .insert(ent, Health(5))
+            .despawn(ent)
+            .insert(ent, Dead);
+        commands.apply(&mut reg);
+
+        assert_eq!(reg.num_entities(), 0);
+        assert!(!reg.has_component::<Dead>(ent));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_foreign_reservation() {
+        let mut other = Commands::default();
+        let reserved = other.spawn();
+
+        let mut commands = Commands::default();
+        commands.insert(reserved, Dead);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_foreign_reservation_with_valid_index() {
+        let mut other = Commands::default();
+        let reserved = other.spawn();
+
+        // the index is in range for this buffer too, but belongs to another one
+        let mut commands = Commands::default();
+        commands.spawn();
+        commands.insert(reserved, Dead);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_reservation_after_flush() {
+        let mut reg = Registry::default();
+        let mut commands = Commands::default();
+        let reserved = commands.spawn();
+        commands.apply(&mut reg);
+
+        commands.spawn();
+        commands.insert(reserved, Dead);
+    }
+}
@@ -1,7 +1,46 @@
+use std::{
+    any::{Any, TypeId},
+    collections::BTreeMap,
+    sync::Mutex,
+};
+
 pub use tempest_ecs_macros::Component;
 
-pub trait Component: Clone + Copy + Send + Sync + 'static {
+use super::{snapshot::SnapshotError, sparse_map::DEFAULT_PAGE_SIZE};
+
+pub trait Component: Send + Sync + 'static {
+    /// Number of entities covered by each sparse page of the pool of this component type, a power of two.  Smaller
+    /// pages suit components held by few, scattered entities.  Set with `#[component(page_size = N)]`.
+    const PAGE_SIZE: usize = DEFAULT_PAGE_SIZE;
+
+    /// Identifier of the component type, unique across every crate linked into the program.
     fn id() -> usize;
+
+    /// Clones components of this type into snapshots, if the type implements [Clone].  Filled in by
+    /// `#[derive(Component)]`.
+    #[doc(hidden)]
+    fn cloner() -> Option<fn(&Self) -> Self> {
+        None
+    }
+}
+
+/// Cloner of the component type, either its own or the one registered on the registry for it.
+pub(crate) fn snapshot_cloner<T: Component>(
+    registered: Option<&(dyn Any + Send + Sync)>,
+) -> Result<fn(&T) -> T, SnapshotError> {
+    T::cloner()
+        .or_else(|| registered.and_then(|cloner| cloner.downcast_ref::<fn(&T) -> T>().copied()))
+        .ok_or(SnapshotError::Uncloneable(std::any::type_name::<T>()))
+}
+
+static COMPONENT_IDS: Mutex<BTreeMap<TypeId, usize>> = Mutex::new(BTreeMap::new());
+
+/// Returns the identifier assigned to `T`, assigning the next free identifier on first use.  Identifiers are dense
+/// and start at zero, so they can be used to index per-component storage.
+pub fn component_id<T: 'static>() -> usize {
+    let mut ids = COMPONENT_IDS.lock().unwrap();
+    let next = ids.len();
+    *ids.entry(TypeId::of::<T>()).or_insert(next)
 }
 
 pub trait ComponentTuple {
@@ -22,12 +61,12 @@ macro_rules! indexing {
         impl<$($T)*> ComponentTupleGetElement<{ $i }> for ($($T)*) {
             type T = $N;
         }
-        
+
         indexing! {
             [$($rest)*] $i + 1, $($T)*
         }
     );
-    
+
     (
         [] $($whatever:tt)*
     ) => (
@@ -45,12 +84,12 @@ macro_rules! component_tuple_arity_impl {
             type Rest = ($($k, )*);
             const EMPTY: bool = false;
         }
-        
+
         indexing! { [$N $($k)*] 0, $N, $($k ,)* }
-        
+
         component_tuple_arity_impl!($($k ,)*);
     );
-    
+
     () => (
         impl ComponentTuple for () {
             const ARITY: usize = 0;
@@ -66,19 +105,35 @@ component_tuple_arity_impl! {
 }
 
 #[cfg(test)]
+#[allow(dead_code)]
 mod tests {
     use super::*;
     use tempest_ecs_macros::Component;
-    
+
     #[derive(Component)]
     struct TestComponent(u32);
-    
+
     #[derive(Component)]
     struct TestComponent2(u32);
-    
+
     #[test]
     fn test_component_id() {
         assert_ne!(TestComponent::id(), TestComponent2::id());
         assert_eq!(TestComponent::id(), TestComponent::id());
     }
+
+    #[test]
+    fn test_manual_component_id() {
+        struct Manual;
+
+        impl Component for Manual {
+            fn id() -> usize {
+                component_id::<Self>()
+            }
+        }
+
+        assert_eq!(Manual::id(), component_id::<Manual>());
+        assert_ne!(Manual::id(), TestComponent::id());
+        assert_ne!(Manual::id(), TestComponent2::id());
+    }
 }
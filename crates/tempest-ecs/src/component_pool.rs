@@ -1,16 +1,38 @@
 use std::any::Any;
 
-use super::{component::Component, sparse_index::SparseTableIndex, sparse_map::SparseMap};
+use super::{
+    component::{snapshot_cloner, Component},
+    snapshot::SnapshotError,
+    sparse_index::SparseTableIndex,
+    sparse_map::SparseMap,
+    tick::Tracked,
+};
 
-pub trait ComponentPool<E: SparseTableIndex> {
+pub trait ComponentPool<E: SparseTableIndex>: Send + Sync {
     fn erase(&mut self, entity: E) -> bool;
     fn contains(&self, entity: E) -> bool;
+    fn is_empty(&self) -> bool;
+    /// Packed keys of the pool, in iteration order.
+    fn keys(&self) -> &[E];
+    /// Index of the entity in the packed keys.
+    fn position(&self, entity: E) -> Option<usize>;
+    /// Swaps two entities of the pool, given by their index in the packed keys.
+    fn swap(&mut self, a: usize, b: usize);
+    /// Releases the memory the pool holds beyond its components.
+    fn shrink_to_fit(&mut self);
+    /// Copies the pool, with the same layout, so that it iterates in the same order.
+    /// Copies the pool, cloning components with `cloner` if their type does not implement [Clone].  The cloner is only
+    /// looked up if the pool holds components.
+    fn snapshot(
+        &self,
+        cloner: Option<&(dyn Any + Send + Sync)>,
+    ) -> Result<Box<dyn ComponentPool<E>>, SnapshotError>;
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
-impl<K: SparseTableIndex + 'static, V: Component + 'static, const PAGE_SIZE: usize> ComponentPool<K>
-    for SparseMap<K, V, PAGE_SIZE>
+impl<K: SparseTableIndex + Send + Sync + 'static, V: Component> ComponentPool<K>
+    for SparseMap<K, Tracked<V>>
 {
     fn erase(&mut self, entity: K) -> bool {
         self.remove(entity).is_some()
@@ -20,6 +42,40 @@ impl<K: SparseTableIndex + 'static, V: Component + 'static, const PAGE_SIZE: usi
         self.contains(entity)
     }
 
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn keys(&self) -> &[K] {
+        self.as_keys_slice()
+    }
+
+    fn position(&self, entity: K) -> Option<usize> {
+        self.position(entity)
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.swap(a, b)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.shrink_to_fit()
+    }
+
+    fn snapshot(
+        &self,
+        cloner: Option<&(dyn Any + Send + Sync)>,
+    ) -> Result<Box<dyn ComponentPool<K>>, SnapshotError> {
+        if self.is_empty() {
+            return Ok(Box::new(self.clone_with(|_| unreachable!())));
+        }
+
+        let clone = snapshot_cloner::<V>(cloner)?;
+        Ok(Box::new(
+            self.clone_with(|tracked| tracked.clone_with(clone)),
+        ))
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
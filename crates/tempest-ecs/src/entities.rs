@@ -1,3 +1,6 @@
+/// Handle to an entity of a [crate::registry::Registry].  Indices are recycled once their entity is destroyed, and
+/// the generation tells the entities that used the same index apart, so that handles to destroyed entities are never
+/// mistaken for the entities that replaced them.
 #[derive(Debug, Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Entity {
     id: u32,
@@ -5,6 +8,12 @@ pub struct Entity {
 }
 
 impl Entity {
+    /// Handle that never refers to an alive entity.
+    pub const DANGLING: Entity = Entity {
+        id: u32::MAX,
+        generation: u32::MAX,
+    };
+
     pub fn new(id: u32, generation: u32) -> Self {
         Self { id, generation }
     }
@@ -24,3 +33,202 @@ impl Entity {
         }
     }
 }
+
+const FREE: u32 = u32::MAX;
+
+/// Allocator of the entities of a registry.  Freed indices are reused last in, first out, with their generation
+/// incremented.  An index whose generation is exhausted is retired instead of being reused.
+#[derive(Clone, Default)]
+pub(crate) struct EntityAllocator {
+    /// Generation of the entity using each index, or of the next entity to use it for free indices.
+    generations: Vec<u32>,
+    /// Position of each index in `alive`, or [FREE].
+    positions: Vec<u32>,
+    /// Indices of the alive entities, packed.
+    alive: Vec<u32>,
+    free: Vec<u32>,
+}
+
+impl EntityAllocator {
+    pub(crate) fn allocate(&mut self) -> Entity {
+        let id = match self.free.pop() {
+            Some(id) => id,
+            None => {
+                let id = self.generations.len() as u32;
+                assert!(id != FREE, "Entity indices are exhausted.");
+                self.generations.push(0);
+                self.positions.push(FREE);
+                id
+            }
+        };
+
+        self.positions[id as usize] = self.alive.len() as u32;
+        self.alive.push(id);
+        Entity::new(id, self.generations[id as usize])
+    }
+
+    /// Reserves room for allocating at least `additional` more entities without reallocating.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        let fresh = additional.saturating_sub(self.free.len());
+        self.generations.reserve(fresh);
+        self.positions.reserve(fresh);
+        self.alive.reserve(additional);
+    }
+
+    /// Releases the spare capacity of the alive and free lists.  Generations are kept for every index ever allocated,
+    /// so that stale entities are never mistaken for alive ones.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.alive.shrink_to_fit();
+        self.free.shrink_to_fit();
+    }
+
+    pub(crate) fn free(&mut self, ent: Entity) -> bool {
+        if !self.is_alive(ent) {
+            return false;
+        }
+
+        let id = ent.index() as usize;
+        let position = self.positions[id] as usize;
+        self.alive.swap_remove(position);
+        if let Some(moved) = self.alive.get(position) {
+            self.positions[*moved as usize] = position as u32;
+        }
+        self.positions[id] = FREE;
+
+        // The last generation is that of the dangling entity, which is never alive
+        self.generations[id] += 1;
+        if self.generations[id] != u32::MAX {
+            self.free.push(ent.index());
+        }
+
+        true
+    }
+
+    pub(crate) fn is_alive(&self, ent: Entity) -> bool {
+        let id = ent.index() as usize;
+        id < self.generations.len()
+            && self.positions[id] != FREE
+            && self.generations[id] == ent.generation()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.alive.len()
+    }
+
+    /// Number of indices allocated so far, alive or not.
+    pub(crate) fn capacity(&self) -> usize {
+        self.generations.len()
+    }
+
+    /// Alive entity at the position in the packed list of alive entities.
+    pub(crate) fn at(&self, position: usize) -> Option<Entity> {
+        let id = *self.alive.get(position)?;
+        Some(Entity::new(id, self.generations[id as usize]))
+    }
+
+    /// Alive entity using the index, if any.
+    pub(crate) fn alive_at_index(&self, id: usize) -> Option<Entity> {
+        (*self.positions.get(id)? != FREE).then(|| Entity::new(id as u32, self.generations[id]))
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.alive
+            .iter()
+            .map(|id| Entity::new(*id, self.generations[*id as usize]))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    /// Deterministic xorshift generator, so that failures can be reproduced from the seed.
+    pub(crate) struct Rng(pub(crate) u64);
+
+    impl Rng {
+        pub(crate) fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        pub(crate) fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+    }
+
+    #[test]
+    fn test_recycling() {
+        let mut allocator = EntityAllocator::default();
+        let a = allocator.allocate();
+        let b = allocator.allocate();
+        assert!(allocator.free(a));
+        assert!(!allocator.free(a));
+
+        let c = allocator.allocate();
+        assert_eq!(c.index(), a.index());
+        assert_eq!(c.generation(), a.generation() + 1);
+        assert!(!allocator.is_alive(a));
+        assert!(allocator.is_alive(b));
+        assert!(allocator.is_alive(c));
+        assert!(!allocator.is_alive(Entity::DANGLING));
+        assert_eq!(allocator.capacity(), 2);
+    }
+
+    #[test]
+    fn test_exhausted_generation() {
+        let mut allocator = EntityAllocator::default();
+        let ent = allocator.allocate();
+        allocator.generations[ent.index() as usize] = u32::MAX - 1;
+        let last = Entity::new(ent.index(), u32::MAX - 1);
+        assert!(allocator.free(last));
+
+        // the index is retired rather than reused with the generation of the dangling entity
+        assert_ne!(allocator.allocate().index(), ent.index());
+        assert!(!allocator.is_alive(Entity::new(ent.index(), u32::MAX)));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_model() {
+        for seed in 1..=16u64 {
+            let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+            let mut allocator = EntityAllocator::default();
+            let mut model: HashSet<Entity> = HashSet::new();
+            let mut dead = Vec::new();
+
+            for _ in 0..2000 {
+                match rng.below(3) {
+                    0 | 1 if model.len() < 64 => {
+                        let ent = allocator.allocate();
+                        assert!(model.insert(ent), "{:?} reused", ent);
+                        assert!(!dead.contains(&ent), "{:?} resurrected", ent);
+                    }
+                    _ if !model.is_empty() => {
+                        let ent = *model.iter().nth(rng.below(model.len())).unwrap();
+                        assert!(allocator.free(ent));
+                        model.remove(&ent);
+                        dead.push(ent);
+                    }
+                    _ => {}
+                }
+
+                if let Some(ent) = dead.get(rng.below(dead.len().max(1))) {
+                    assert!(!allocator.is_alive(*ent));
+                    assert!(!allocator.free(*ent));
+                }
+                assert_eq!(allocator.len(), model.len());
+            }
+
+            let mut alive: Vec<Entity> = allocator.iter().collect();
+            let mut expected: Vec<Entity> = model.iter().copied().collect();
+            alive.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(alive, expected);
+            assert!((0..allocator.len()).all(|pos| model.contains(&allocator.at(pos).unwrap())));
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::{marker::PhantomData, ops::Deref};
+
+use super::resource::{ResMut, Resource};
+
+/// Message sent between systems through the [Events] resource of its type.
+pub trait Event: Resource {}
+
+impl<T: Resource> Event for T {}
+
+/// Double-buffered queue of events.  Events are kept for two updates of the queue, so that every reader running once
+/// per update sees them, whether it runs before or after the writer.
+pub struct Events<T> {
+    previous: Vec<T>,
+    current: Vec<T>,
+    previous_start: usize,
+    current_start: usize,
+    count: usize,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            previous_start: 0,
+            current_start: 0,
+            count: 0,
+        }
+    }
+}
+
+impl<T: Event> Events<T> {
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+        self.count += 1;
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        let len = self.current.len();
+        self.current.extend(events);
+        self.count += self.current.len() - len;
+    }
+
+    /// Swaps the buffers, dropping the events sent before the previous update.
+    pub fn update(&mut self) {
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+        self.previous_start = self.current_start;
+        self.current_start = self.count;
+    }
+
+    /// Drops every event of both buffers.
+    pub fn clear(&mut self) {
+        self.previous.clear();
+        self.current.clear();
+        self.previous_start = self.count;
+        self.current_start = self.count;
+    }
+
+    /// Iterates over the events of both buffers, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.previous.iter().chain(self.current.iter())
+    }
+
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over the events sent after the event with the provided index, counting every event ever sent.
+    fn iter_since(&self, index: usize) -> impl Iterator<Item = &T> {
+        let previous = index
+            .saturating_sub(self.previous_start)
+            .min(self.previous.len());
+        let current = index
+            .saturating_sub(self.current_start)
+            .min(self.current.len());
+        self.previous[previous..]
+            .iter()
+            .chain(self.current[current..].iter())
+    }
+}
+
+/// Cursor of a consumer of events, which sees every event once.  Each consumer keeps its own reader.
+pub struct EventReader<T> {
+    cursor: usize,
+    type_phantom: PhantomData<T>,
+}
+
+impl<T> Default for EventReader<T> {
+    fn default() -> Self {
+        Self {
+            cursor: 0,
+            type_phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Event> EventReader<T> {
+    /// Iterates over the events sent since the last read.  Events that expired in the meantime are skipped.
+    pub fn read<'e>(&mut self, events: &'e Events<T>) -> impl Iterator<Item = &'e T> {
+        let since = self.cursor;
+        self.cursor = events.count;
+        events.iter_since(since)
+    }
+
+    /// Number of events the next read returns.
+    pub fn len(&self, events: &Events<T>) -> usize {
+        events.iter_since(self.cursor).count()
+    }
+
+    pub fn is_empty(&self, events: &Events<T>) -> bool {
+        self.len(events) == 0
+    }
+
+    /// Skips every event sent so far.
+    pub fn clear(&mut self, events: &Events<T>) {
+        self.cursor = events.count;
+    }
+}
+
+/// Producer of events, borrowing the [Events] resource of the type mutably.
+pub struct EventWriter<'c, T: Event> {
+    events: ResMut<'c, Events<T>>,
+}
+
+impl<'c, T: Event> EventWriter<'c, T> {
+    pub fn new(events: ResMut<'c, Events<T>>) -> Self {
+        Self { events }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.events.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        self.events.send_batch(events);
+    }
+}
+
+impl<'c, T: Event> Deref for EventWriter<'c, T> {
+    type Target = Events<T>;
+
+    fn deref(&self) -> &Events<T> {
+        &self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Died(u32);
+
+    fn read(reader: &mut EventReader<Died>, events: &Events<Died>) -> Vec<Died> {
+        reader.read(events).copied().collect()
+    }
+
+    #[test]
+    fn test_readers_see_events_once() {
+        let mut events = Events::default();
+        let mut early = EventReader::default();
+        let mut late = EventReader::default();
+
+        events.send(Died(0));
+        events.send_batch([Died(1), Died(2)]);
+        assert_eq!(early.len(&events), 3);
+        assert_eq!(read(&mut early, &events), [Died(0), Died(1), Died(2)]);
+        assert!(early.is_empty(&events));
+
+        events.update();
+        events.send(Died(3));
+        assert_eq!(read(&mut early, &events), [Died(3)]);
+        assert_eq!(
+            read(&mut late, &events),
+            [Died(0), Died(1), Died(2), Died(3)]
+        );
+        assert!(read(&mut late, &events).is_empty());
+
+        let mut skipping = EventReader::default();
+        skipping.clear(&events);
+        events.send(Died(4));
+        assert_eq!(read(&mut skipping, &events), [Died(4)]);
+    }
+
+    #[test]
+    fn test_events_expire() {
+        let mut events = Events::default();
+        let mut reader = EventReader::default();
+
+        events.send(Died(0));
+        events.update();
+        events.send(Died(1));
+        assert_eq!(events.len(), 2);
+
+        // the first event is dropped on the second update after it was sent
+        events.update();
+        assert_eq!(events.iter().copied().collect::<Vec<_>>(), [Died(1)]);
+        assert_eq!(read(&mut reader, &events), [Died(1)]);
+
+        events.update();
+        assert!(events.is_empty());
+
+        events.send(Died(2));
+        events.clear();
+        assert!(events.is_empty());
+        assert!(read(&mut reader, &events).is_empty());
+    }
+}
@@ -1,142 +1,314 @@
 use std::{
-    collections::{HashMap, VecDeque, HashSet},
-    hash::Hash,
+    collections::BTreeSet,
+    fmt::{Display, Write},
 };
 
-pub struct Node<'a, T> {
-    data: &'a T,
-    adjacencies: Vec<&'a Node<'a, T>>,
-}
+/// Index of a node in the [Graph] that created it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct NodeIndex(usize);
+
+impl NodeIndex {
+    pub fn new(index: usize) -> Self {
+        Self(index)
+    }
 
-impl<'a, T: PartialEq> PartialEq for Node<'a, T> {
-    fn eq(&self, other: &Self) -> bool {
-        self.data == other.data
+    pub fn index(&self) -> usize {
+        self.0
     }
 }
 
-impl<'a, T: Eq> Eq for Node<'a, T> {}
+/// Error returned when sorting a graph with a cycle.  The path lists the nodes of the cycle in order, and ends with
+/// the node it started from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Cycle {
+    path: Vec<NodeIndex>,
+}
 
-impl<'a, T: std::hash::Hash> std::hash::Hash for Node<'a, T> {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.data.hash(state);
+impl Cycle {
+    pub fn path(&self) -> &[NodeIndex] {
+        &self.path
     }
 }
 
-impl<'a, T> Node<'a, T> {
-    pub fn add_edge(&'a mut self, neighbor: &'a mut Node<'a, T>) -> &'a mut Self {
-        self.adjacencies.push(neighbor);
-        self
+impl Display for Cycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = self
+            .path
+            .iter()
+            .map(|node| node.0.to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        write!(f, "Graph contains a cycle: {}.", path)
     }
 }
 
-impl<'a, T: PartialEq> Node<'a, T> {
-    pub fn remove_edge(&mut self, neighbor: &Node<'a, T>) {
-        if let Some(index) = self.adjacencies.iter().position(|n| *n == neighbor) {
-            self.adjacencies.remove(index);
-        }
-    }
+impl std::error::Error for Cycle {}
+
+struct Node<T> {
+    data: T,
+    adjacencies: Vec<NodeIndex>,
 }
 
-pub struct Graph<'a, T> {
-    nodes: Vec<Node<'a, T>>,
+/// Directed graph owning the data of its nodes.  Edges are unique, and point from a node to the nodes depending on it.
+pub struct Graph<T> {
+    nodes: Vec<Node<T>>,
 }
 
-impl<'a, T> Graph<'a, T> {
+impl<T> Default for Graph<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Graph<T> {
     pub fn new() -> Self {
         Graph { nodes: Vec::new() }
     }
 
-    pub fn add_node(&mut self, data: &'a T) -> &mut Node<'a, T> {
-        let node = Node {
+    pub fn add_node(&mut self, data: T) -> NodeIndex {
+        self.nodes.push(Node {
             data,
             adjacencies: Vec::new(),
-        };
-        self.nodes.push(node);
-        self.nodes.last_mut().unwrap()
+        });
+        NodeIndex(self.nodes.len() - 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&self, node: NodeIndex) -> &T {
+        &self.nodes[node.0].data
+    }
+
+    pub fn node_mut(&mut self, node: NodeIndex) -> &mut T {
+        &mut self.nodes[node.0].data
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = (NodeIndex, &T)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(idx, node)| (NodeIndex(idx), &node.data))
+    }
+
+    /// Adds an edge between two nodes, returning false if the edge already exists.
+    pub fn add_edge(&mut self, from: NodeIndex, to: NodeIndex) -> bool {
+        assert!(to.0 < self.nodes.len(), "Node {} does not exist.", to.0);
+
+        let adjacencies = &mut self.nodes[from.0].adjacencies;
+        if adjacencies.contains(&to) {
+            return false;
+        }
+
+        adjacencies.push(to);
+        true
+    }
+
+    /// Removes an edge between two nodes, returning false if there is no such edge.
+    pub fn remove_edge(&mut self, from: NodeIndex, to: NodeIndex) -> bool {
+        let adjacencies = &mut self.nodes[from.0].adjacencies;
+        match adjacencies.iter().position(|node| *node == to) {
+            Some(idx) => {
+                adjacencies.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn has_edge(&self, from: NodeIndex, to: NodeIndex) -> bool {
+        self.nodes[from.0].adjacencies.contains(&to)
+    }
+
+    /// Nodes reached by the edges leaving the node, in the order the edges were added.
+    pub fn neighbors(&self, node: NodeIndex) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.nodes[node.0].adjacencies.iter().copied()
+    }
+
+    /// Number of edges reaching every node.
+    pub fn in_degrees(&self) -> Vec<usize> {
+        let mut in_degree = vec![0; self.nodes.len()];
+        self.nodes
+            .iter()
+            .flat_map(|node| &node.adjacencies)
+            .for_each(|target| in_degree[target.0] += 1);
+        in_degree
+    }
+
+    /// Sorts the nodes so that every node comes after the nodes with an edge to it, preferring the nodes added first
+    /// when several are ready.
+    pub fn topo_sort(&self) -> Result<Vec<NodeIndex>, Cycle> {
+        let mut in_degree = self.in_degrees();
+        let mut ready: BTreeSet<usize> = (0..self.nodes.len())
+            .filter(|idx| in_degree[*idx] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(self.nodes.len());
+
+        while let Some(idx) = ready.pop_first() {
+            order.push(NodeIndex(idx));
+            for target in &self.nodes[idx].adjacencies {
+                in_degree[target.0] -= 1;
+                if in_degree[target.0] == 0 {
+                    ready.insert(target.0);
+                }
+            }
+        }
+
+        if order.len() == self.nodes.len() {
+            Ok(order)
+        } else {
+            Err(self.find_cycle(&in_degree))
+        }
+    }
+
+    pub fn has_cycle(&self) -> bool {
+        self.topo_sort().is_err()
+    }
+
+    /// Extracts a cycle from the nodes left unsorted, which are the nodes with a remaining in-degree.
+    fn find_cycle(&self, in_degree: &[usize]) -> Cycle {
+        // Every node left has a predecessor that is left as well, so walking predecessors eventually loops
+        let mut predecessor = vec![None; self.nodes.len()];
+        for (source, node) in self.nodes.iter().enumerate() {
+            for target in &node.adjacencies {
+                if in_degree[source] > 0 && in_degree[target.0] > 0 {
+                    predecessor[target.0] = Some(source);
+                }
+            }
+        }
+
+        let mut path = Vec::new();
+        let mut idx = in_degree.iter().position(|degree| *degree > 0).unwrap();
+        while !path.contains(&idx) {
+            path.push(idx);
+            idx = predecessor[idx].unwrap();
+        }
+
+        let start = path.iter().position(|node| *node == idx).unwrap();
+        let mut path: Vec<NodeIndex> = path[start..]
+            .iter()
+            .rev()
+            .map(|idx| NodeIndex(*idx))
+            .collect();
+        path.push(path[0]);
+        Cycle { path }
     }
 }
 
-impl<'a, T: std::fmt::Display> Graph<'a, T> {
+impl<T: Display> Graph<T> {
     pub fn pretty_print(&self) {
         for node in &self.nodes {
             let adjacencies = node
                 .adjacencies
                 .iter()
-                .map(|n| n.data.to_string())
+                .map(|n| self.nodes[n.0].data.to_string())
                 .collect::<Vec<_>>()
                 .join(", ");
             println!("{}: [{}]", node.data, adjacencies);
         }
     }
-}
 
-impl<'a, T: Eq + Hash> Graph<'a, T> {
-    pub fn topo_sort(&'a self) -> Vec<&'a Node<'a, T>> {
-        #[cfg(debug_assertions)] {
-            if self.has_cycle() {
-                panic!("Topologic sort is not defined on graphs with cycles.");
-            }
-        }
+    /// Writes the graph in the DOT language of Graphviz, labelling the nodes with their data.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n");
 
-        let mut result = Vec::new();
-        let mut in_degree = HashMap::new();
+        for (idx, node) in self.nodes.iter().enumerate() {
+            let label = node
+                .data
+                .to_string()
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"");
+            writeln!(dot, "    {} [label=\"{}\"];", idx, label).unwrap();
+        }
 
-        // Calculate the in-degree of each node
-        for node in self.nodes.iter() {
-            in_degree.entry(node).or_insert(0);
-            for neighbor in node.adjacencies.iter() {
-                *in_degree.entry(neighbor).or_insert(0) += 1;
+        for (idx, node) in self.nodes.iter().enumerate() {
+            for target in &node.adjacencies {
+                writeln!(dot, "    {} -> {};", idx, target.0).unwrap();
             }
         }
 
-        // Perform topological sort using Kahn's algorithm
-        let mut queue = VecDeque::new();
+        dot.push_str("}\n");
+        dot
+    }
+}
 
-        for node in self.nodes.iter() {
-            if in_degree.get(node) == Some(&0) {
-                queue.push_back(node);
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        while let Some(node) = queue.pop_front() {
-            result.push(node);
-            for neighbor in node.adjacencies.iter() {
-                let neighbor_in_degree = in_degree.get_mut(neighbor).unwrap();
-                *neighbor_in_degree -= 1;
-                if *neighbor_in_degree == 0 {
-                    queue.push_back(neighbor);
-                }
-            }
-        }
+    fn diamond() -> (Graph<&'static str>, [NodeIndex; 4]) {
+        let mut graph = Graph::new();
+        let nodes = ["input", "physics", "audio", "render"].map(|name| graph.add_node(name));
+        graph.add_edge(nodes[0], nodes[1]);
+        graph.add_edge(nodes[0], nodes[2]);
+        graph.add_edge(nodes[1], nodes[3]);
+        graph.add_edge(nodes[2], nodes[3]);
+        (graph, nodes)
+    }
 
-        result
+    #[test]
+    fn test_edges() {
+        let (mut graph, [input, physics, audio, render]) = diamond();
+        assert!(!graph.add_edge(input, physics));
+        assert!(graph.has_edge(input, physics));
+        assert_eq!(graph.neighbors(input).collect::<Vec<_>>(), [physics, audio]);
+        assert_eq!(graph.in_degrees(), [0, 1, 1, 2]);
+
+        assert!(graph.remove_edge(input, physics));
+        assert!(!graph.remove_edge(input, physics));
+        assert!(!graph.has_edge(input, physics));
+        assert_eq!(graph.neighbors(input).collect::<Vec<_>>(), [audio]);
+
+        *graph.node_mut(render) = "present";
+        assert_eq!(*graph.node(render), "present");
+        assert_eq!(graph.len(), 4);
     }
 
-    pub fn has_cycle(&self) -> bool {
-        let mut stack: Vec<&Node<T>> = Vec::new();
-        let mut visited: HashSet<&Node<T>> = HashSet::new();
-    
-        for node in self.nodes.iter() {
-            if visited.contains(node) {
-                // If we've already visited this node, we can safely ignore it
-                continue;
-            }
-    
-            stack.push(node);
-    
-            while let Some(current) = stack.pop() {
-                visited.insert(current);
-    
-                for neighbor in current.adjacencies.iter() {
-                    if !visited.contains(neighbor) {
-                        stack.push(neighbor);
-                    } else if stack.contains(&neighbor) {
-                        return true;
-                    }
-                }
-            }
-        }
-    
-        false
+    #[test]
+    fn test_topo_sort() {
+        let (mut graph, [input, physics, audio, render]) = diamond();
+        assert_eq!(graph.topo_sort(), Ok(vec![input, physics, audio, render]));
+
+        // ties are broken in insertion order, whatever the order of the edges
+        graph.remove_edge(physics, render);
+        graph.add_edge(audio, physics);
+        assert_eq!(graph.topo_sort(), Ok(vec![input, audio, physics, render]));
+        assert!(!graph.has_cycle());
+    }
+
+    #[test]
+    fn test_cycle() {
+        let (mut graph, [input, physics, audio, render]) = diamond();
+        let extra = graph.add_node("extra");
+        graph.add_edge(render, physics);
+        graph.add_edge(render, extra);
+
+        let cycle = graph.topo_sort().unwrap_err();
+        assert_eq!(cycle.path(), [render, physics, render]);
+        assert!(graph.has_cycle());
+
+        graph.remove_edge(physics, render);
+        assert_eq!(
+            graph.topo_sort(),
+            Ok(vec![input, audio, render, physics, extra])
+        );
+    }
+
+    #[test]
+    fn test_dot() {
+        let mut graph = Graph::new();
+        let quoted = graph.add_node("say \"hi\"");
+        let other = graph.add_node("other");
+        graph.add_edge(quoted, other);
+
+        assert_eq!(
+            graph.to_dot(),
+            "digraph {\n    0 [label=\"say \\\"hi\\\"\"];\n    1 [label=\"other\"];\n    0 -> 1;\n}\n"
+        );
     }
 }
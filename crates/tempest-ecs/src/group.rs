@@ -0,0 +1,333 @@
+use super::{
+    bundle::{Bundle, BundleTypes},
+    component_pool::ComponentPool,
+    registry::{EntityKey, PoolKeys, Registry},
+};
+
+pub(crate) type Pools = [Option<Box<dyn ComponentPool<EntityKey>>>];
+
+/// Components owned by a group.  The entities having every one of them are packed at the front of each of their pools,
+/// in the same order, so that the component of the entity at a given index is at the same index in every pool.
+#[derive(Clone)]
+struct OwningGroup {
+    components: Vec<usize>,
+    len: usize,
+}
+
+impl OwningGroup {
+    fn is_member(&self, pools: &Pools, key: EntityKey) -> bool {
+        pool(pools, self.components[0])
+            .and_then(|pool| pool.position(key))
+            .map_or(false, |position| position < self.len)
+    }
+
+    /// Moves the entity to the back of the group if it has every owned component.
+    fn enter(&mut self, pools: &mut Pools, key: EntityKey) {
+        let complete = self
+            .components
+            .iter()
+            .all(|id| pool(pools, *id).map_or(false, |pool| pool.contains(key)));
+        if !complete || self.is_member(pools, key) {
+            return;
+        }
+
+        for id in self.components.iter() {
+            let pool = pools[*id].as_mut().expect("Owned pool is missing.");
+            let position = pool
+                .position(key)
+                .expect("Entity is missing from owned pool.");
+            pool.swap(position, self.len);
+        }
+        self.len += 1;
+    }
+
+    /// Moves the entity out of the group, ahead of the removal of one of its owned components.
+    fn leave(&mut self, pools: &mut Pools, key: EntityKey) {
+        if !self.is_member(pools, key) {
+            return;
+        }
+
+        self.len -= 1;
+        for id in self.components.iter() {
+            let pool = pools[*id].as_mut().expect("Owned pool is missing.");
+            let position = pool
+                .position(key)
+                .expect("Entity is missing from owned pool.");
+            pool.swap(position, self.len);
+        }
+    }
+}
+
+fn pool(pools: &Pools, id: usize) -> Option<&dyn ComponentPool<EntityKey>> {
+    pools.get(id)?.as_deref()
+}
+
+/// Owning groups of a registry with sparse storage.  Each component type is owned by at most one group.
+#[derive(Clone, Default)]
+pub(crate) struct OwningGroups {
+    groups: Vec<OwningGroup>,
+}
+
+impl OwningGroups {
+    fn owner(&mut self, component_id: usize) -> Option<&mut OwningGroup> {
+        self.groups
+            .iter_mut()
+            .find(|group| group.components.contains(&component_id))
+    }
+
+    pub(crate) fn owns(&self, component_id: usize) -> bool {
+        self.groups
+            .iter()
+            .any(|group| group.components.contains(&component_id))
+    }
+
+    /// Updates the group owning the component after it was inserted for the entity.
+    pub(crate) fn inserted(&mut self, pools: &mut Pools, key: EntityKey, component_id: usize) {
+        if let Some(group) = self.owner(component_id) {
+            group.enter(pools, key);
+        }
+    }
+
+    /// Updates the group owning the component before it is removed from the entity.
+    pub(crate) fn removing(&mut self, pools: &mut Pools, key: EntityKey, component_id: usize) {
+        if let Some(group) = self.owner(component_id) {
+            group.leave(pools, key);
+        }
+    }
+
+    /// Updates every group before the components of the entity are erased.
+    pub(crate) fn erasing(&mut self, pools: &mut Pools, key: EntityKey) {
+        for group in self.groups.iter_mut() {
+            group.leave(pools, key);
+        }
+    }
+
+    /// Keys driving a query over entities that have at least the components, taken from the first group owning a
+    /// subset of them.
+    pub(crate) fn keys<'a>(&self, pools: &'a Pools, required: &[usize]) -> Option<PoolKeys<'a>> {
+        let group = self
+            .groups
+            .iter()
+            .find(|group| group.components.iter().all(|id| required.contains(id)))?;
+
+        let keys = pool(pools, group.components[0]).map_or(&[][..], |pool| pool.keys());
+        Some(PoolKeys::grouped(&keys[..group.len]))
+    }
+}
+
+impl Registry {
+    /// Creates an owning group over the components of `G`, such as `(Position, Velocity)`, if it does not exist yet.
+    ///
+    /// The pools of an owning group keep the entities having every owned component packed at their front, in the same
+    /// order.  Queries requiring every component of a group iterate that front, and read the owned components at the
+    /// same index in each pool instead of looking the entity up.  Adding and removing owned components costs a swap in
+    /// each owned pool.
+    ///
+    /// # Panics
+    ///
+    /// Panics with archetype storage, which already packs entities with the same components together, if the group
+    /// has fewer than two components, or if one of the components is owned by another group.
+    pub fn group<G: Bundle>(&mut self) {
+        let types = BundleTypes::of::<G>();
+        assert!(
+            types.len() >= 2,
+            "Group {} must own at least two components.",
+            std::any::type_name::<G>()
+        );
+        let components: Vec<usize> = types.iter().map(|ty| ty.id).collect();
+        let (groups, _) = self.groups_and_pools_mut();
+        if groups
+            .groups
+            .iter()
+            .any(|group| same_components(&group.components, &components))
+        {
+            return;
+        }
+        if let Some(owned) = types.iter().find(|ty| groups.owns(ty.id)) {
+            panic!(
+                "Component {} is already owned by another group.",
+                owned.name
+            );
+        }
+
+        for ty in types.iter() {
+            (ty.reserve)(self, 0);
+        }
+
+        let (groups, pools) = self.groups_and_pools_mut();
+        let mut group = OwningGroup { components, len: 0 };
+        let smallest = group
+            .components
+            .iter()
+            .filter_map(|id| pool(pools, *id))
+            .min_by_key(|pool| pool.keys().len())
+            .map(|pool| pool.keys().to_vec())
+            .unwrap_or_default();
+        for key in smallest {
+            group.enter(pools, key);
+        }
+        groups.groups.push(group);
+    }
+
+    /// Number of entities in the owning group over the components of `G`, or `None` if there is no such group.
+    pub fn group_len<G: Bundle>(&self) -> Option<usize> {
+        let components: Vec<usize> = BundleTypes::of::<G>().iter().map(|ty| ty.id).collect();
+        self.groups()
+            .groups
+            .iter()
+            .find(|group| same_components(&group.components, &components))
+            .map(|group| group.len)
+    }
+}
+
+fn same_components(lhs: &[usize], rhs: &[usize]) -> bool {
+    lhs.len() == rhs.len() && lhs.iter().all(|id| rhs.contains(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::prelude::*;
+    use tempest_ecs_macros::RegistryQuery;
+
+    use super::*;
+    use crate::{
+        component::Component,
+        registry::{Entity, StorageKind},
+    };
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Velocity(i32);
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Frozen;
+
+    #[derive(RegistryQuery)]
+    #[read_write(Position)]
+    #[read_only(Velocity)]
+    struct Movement;
+
+    #[derive(RegistryQuery)]
+    #[read_only(Position, Velocity)]
+    #[without(Frozen)]
+    struct Moving;
+
+    /// Checks that the owned pools start with the same entities, and that no other entity has both components.
+    fn assert_packed(reg: &Registry, len: usize) {
+        assert_eq!(reg.group_len::<(Position, Velocity)>(), Some(len));
+        let positions = reg.fetch_pool::<Position>().unwrap();
+        let velocities = reg.fetch_pool::<Velocity>().unwrap();
+        assert!(positions.as_keys_slice()[..len] == velocities.as_keys_slice()[..len]);
+        assert!(positions.as_keys_slice()[len..]
+            .iter()
+            .all(|key| !velocities.contains(*key)));
+    }
+
+    fn populate(reg: &mut Registry, range: std::ops::Range<i32>) -> Vec<Entity> {
+        range
+            .map(|i| {
+                let ent = reg.create_entity();
+                if i % 3 == 0 {
+                    reg.assign_component(ent, Velocity(i));
+                }
+                if i % 2 == 0 {
+                    reg.assign_component(ent, Position(i));
+                }
+                ent
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_owning_group() {
+        let mut reg = Registry::new(StorageKind::Sparse);
+        let mut entities = populate(&mut reg, 0..60);
+        reg.group::<(Position, Velocity)>();
+        assert_packed(&reg, 10);
+
+        // entities join the group once they have every owned component, and leave it as soon as they lose one
+        entities.extend(populate(&mut reg, 60..100));
+        assert_packed(&reg, 17);
+        reg.remove_component::<Velocity>(entities[0]);
+        reg.destroy_entity(&entities[6]);
+        assert_packed(&reg, 15);
+        reg.assign_component(entities[3], Position(3));
+        reg.assign_component(entities[12], Position(12));
+        assert_packed(&reg, 16);
+        reg.group::<(Velocity, Position)>();
+        assert_packed(&reg, 16);
+        let required = [Frozen::id(), Velocity::id(), Position::id()];
+        assert_eq!(reg.group_keys(&required).map(|keys| keys.len()), Some(16));
+        assert!(reg.group_keys(&required[1..2]).is_none());
+
+        for (velocity, position) in reg.query_registry_mut::<Movement>() {
+            position.0 += velocity.0;
+        }
+        for (i, ent) in entities.iter().enumerate().filter(|(i, _)| i % 2 == 0) {
+            let i = i as i32;
+            let expected = if i % 3 == 0 && i != 0 { 2 * i } else { i };
+            if reg.is_alive(*ent) {
+                assert_eq!(
+                    reg.get_component::<Position>(*ent),
+                    Some(Position(expected))
+                );
+            }
+        }
+
+        reg.assign_component(entities[18], Frozen);
+        assert_eq!(reg.query_registry::<Moving>().count(), 15);
+        assert_eq!(reg.query_registry::<Moving>().par_iter(4).count(), 15);
+
+        let snapshot = reg.snapshot().unwrap();
+        for ent in entities.iter() {
+            reg.remove_component::<Position>(*ent);
+        }
+        assert_packed(&reg, 0);
+        reg.restore(snapshot);
+        assert_packed(&reg, 16);
+
+        for ent in entities.iter() {
+            reg.destroy_entity(ent);
+        }
+        reg.shrink_to_fit();
+        assert_eq!(reg.group_len::<(Position, Velocity)>(), Some(0));
+        populate(&mut reg, 0..12);
+        assert_packed(&reg, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "already owned by another group")]
+    fn test_component_owned_twice() {
+        let mut reg = Registry::new(StorageKind::Sparse);
+        reg.group::<(Position, Velocity)>();
+        reg.group::<(Frozen, Position)>();
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot be sorted on its own")]
+    fn test_sort_owned_component() {
+        let mut reg = Registry::new(StorageKind::Sparse);
+        reg.group::<(Position, Velocity)>();
+        reg.sort::<Position>(|a, b| a.0.cmp(&b.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "at least two components")]
+    fn test_single_component_group() {
+        Registry::new(StorageKind::Sparse).group::<(Position,)>();
+    }
+
+    #[test]
+    #[should_panic(expected = "at least two components")]
+    fn test_empty_group() {
+        Registry::new(StorageKind::Sparse).group::<()>();
+    }
+
+    #[test]
+    #[should_panic(expected = "not available with archetype storage")]
+    fn test_archetype_group() {
+        Registry::new(StorageKind::Archetype).group::<(Position, Velocity)>();
+    }
+}
@@ -0,0 +1,292 @@
+use std::{collections::VecDeque, ops::Deref};
+
+use super::{
+    component::Component,
+    hooks::SyncHooks,
+    registry::{Entity, Registry},
+    save::Persist,
+    scene::SceneValue,
+};
+
+/// Entity an entity is attached to.  Set with [Registry::set_parent] and [Registry::clear_parent].  Assigning or
+/// removing it otherwise, such as when loading a saved world, updates the [Children] of the parents too.  It does not
+/// implement [Clone], so that it cannot be copied from another entity and written in place.
+#[derive(Component, Persist, SceneValue, Debug, Eq, PartialEq)]
+#[persist(name = "tempest::Parent")]
+pub struct Parent(Entity);
+
+impl Parent {
+    pub fn get(&self) -> Entity {
+        self.0
+    }
+}
+
+/// Entities attached to an entity, in the order they were attached.  Maintained along with the [Parent] of the
+/// entities.
+#[derive(Component, Persist, Clone, Debug, Default, Eq, PartialEq)]
+#[persist(name = "tempest::Children")]
+pub struct Children(Vec<Entity>);
+
+impl Deref for Children {
+    type Target = [Entity];
+
+    fn deref(&self) -> &[Entity] {
+        &self.0
+    }
+}
+
+/// Panics if the parent is not alive, or is the entity itself or one of its descendants.
+fn check_parent(reg: &Registry, child: Entity, parent: &Parent) {
+    assert!(
+        reg.is_alive(parent.0)
+            && parent.0 != child
+            && !reg.ancestors(parent.0).any(|ancestor| ancestor == child),
+        "Entity {:?} cannot be attached to {:?}, which is not alive or is the entity itself or one of its \
+         descendants.",
+        child,
+        parent.0
+    );
+}
+
+fn parent_added(reg: &mut Registry, child: Entity) {
+    let parent = reg.parent(child).expect("Parent was just assigned.");
+    match reg.get_component_mut::<Children>(parent) {
+        // loaded children may already list the entity
+        Some(children) if children.contains(&child) => {}
+        Some(children) => children.0.push(child),
+        None => {
+            reg.assign_component(parent, Children(vec![child]));
+        }
+    }
+}
+
+fn parent_replaced(reg: &mut Registry, child: Entity, old: &Parent) {
+    parent_removed(reg, child, old);
+    parent_added(reg, child);
+}
+
+fn parent_removed(reg: &mut Registry, child: Entity, old: &Parent) {
+    if let Some(children) = reg.get_component_mut::<Children>(old.0) {
+        children.0.retain(|ent| *ent != child);
+        if children.is_empty() {
+            reg.remove_component::<Children>(old.0);
+        }
+    }
+}
+
+impl Registry {
+    /// Keeps the [Children] of entities in sync with the [Parent] of their children.
+    pub(crate) fn init_hierarchy(&mut self) {
+        self.set_cloner::<Parent>(|parent| Parent(parent.0));
+        self.set_sync_hooks(SyncHooks {
+            check: check_parent,
+            added: parent_added,
+            replaced: parent_replaced,
+            removed: parent_removed,
+        });
+    }
+
+    /// Attaches an entity to a parent, detaching it from its previous parent.
+    ///
+    /// Returns false if either entity is not alive, or if the parent is the entity itself or one of its descendants.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> bool {
+        if !self.is_alive(child) || !self.is_alive(parent) {
+            return false;
+        }
+
+        if child == parent || self.ancestors(parent).any(|ancestor| ancestor == child) {
+            return false;
+        }
+
+        self.assign_component(child, Parent(parent))
+    }
+
+    /// Detaches an entity from its parent, returning the parent it was attached to.
+    pub fn clear_parent(&mut self, child: Entity) -> Option<Entity> {
+        self.remove_component::<Parent>(child)
+            .map(|parent| parent.0)
+    }
+
+    pub fn parent(&self, ent: Entity) -> Option<Entity> {
+        self.get_component_ref::<Parent>(ent).map(Parent::get)
+    }
+
+    pub fn children(&self, ent: Entity) -> &[Entity] {
+        self.get_component_ref::<Children>(ent)
+            .map(|children| &children.0[..])
+            .unwrap_or(&[])
+    }
+
+    /// Iterates over the parent of the entity, then the parent of that parent, up to the root of its hierarchy.
+    pub fn ancestors(&self, ent: Entity) -> impl Iterator<Item = Entity> + '_ {
+        std::iter::successors(self.parent(ent), |ent| self.parent(*ent))
+    }
+
+    /// Iterates over the descendants of the entity depth-first, visiting each entity before its children.
+    pub fn descendants_depth_first(&self, ent: Entity) -> DepthFirstDescendants<'_> {
+        DepthFirstDescendants {
+            reg: self,
+            stack: self.children(ent).iter().rev().copied().collect(),
+        }
+    }
+
+    /// Iterates over the descendants of the entity breadth-first, visiting the entities closest to it first.
+    pub fn descendants_breadth_first(&self, ent: Entity) -> BreadthFirstDescendants<'_> {
+        BreadthFirstDescendants {
+            reg: self,
+            queue: self.children(ent).iter().copied().collect(),
+        }
+    }
+}
+
+pub struct DepthFirstDescendants<'r> {
+    reg: &'r Registry,
+    stack: Vec<Entity>,
+}
+
+impl<'r> Iterator for DepthFirstDescendants<'r> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        let ent = self.stack.pop()?;
+        self.stack
+            .extend(self.reg.children(ent).iter().rev().copied());
+        Some(ent)
+    }
+}
+
+pub struct BreadthFirstDescendants<'r> {
+    reg: &'r Registry,
+    queue: VecDeque<Entity>,
+}
+
+impl<'r> Iterator for BreadthFirstDescendants<'r> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        let ent = self.queue.pop_front()?;
+        self.queue.extend(self.reg.children(ent).iter().copied());
+        Some(ent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::StorageKind;
+
+    /// Builds the hierarchy `root -> [a -> [c, d], b -> [e]]`, returning `[root, a, b, c, d, e]`.
+    fn tree(reg: &mut Registry) -> [Entity; 6] {
+        let ents = [(); 6].map(|_| reg.create_entity());
+        let [root, a, b, c, d, e] = ents;
+        assert!(reg.set_parent(a, root));
+        assert!(reg.set_parent(b, root));
+        assert!(reg.set_parent(c, a));
+        assert!(reg.set_parent(d, a));
+        assert!(reg.set_parent(e, b));
+        ents
+    }
+
+    fn traversal(kind: StorageKind) {
+        let mut reg = Registry::new(kind);
+        let [root, a, b, c, d, e] = tree(&mut reg);
+
+        assert_eq!(reg.parent(c), Some(a));
+        assert_eq!(reg.parent(root), None);
+        assert_eq!(reg.children(a), [c, d]);
+        assert_eq!(reg.ancestors(e).collect::<Vec<_>>(), [b, root]);
+
+        assert_eq!(
+            reg.descendants_depth_first(root).collect::<Vec<_>>(),
+            [a, c, d, b, e]
+        );
+        assert_eq!(
+            reg.descendants_breadth_first(root).collect::<Vec<_>>(),
+            [a, b, c, d, e]
+        );
+        assert_eq!(reg.descendants_depth_first(c).count(), 0);
+    }
+
+    #[test]
+    fn test_traversal() {
+        traversal(StorageKind::Sparse);
+        traversal(StorageKind::Archetype);
+    }
+
+    #[test]
+    fn test_reparenting() {
+        let mut reg = Registry::default();
+        let [root, a, b, c, d, _] = tree(&mut reg);
+
+        // an entity cannot be attached to itself or below itself
+        assert!(!reg.set_parent(a, a));
+        assert!(!reg.set_parent(root, c));
+        assert_eq!(reg.parent(root), None);
+
+        assert!(reg.set_parent(c, b));
+        assert_eq!(reg.children(a), [d]);
+        assert_eq!(reg.children(b).last(), Some(&c));
+        assert_eq!(reg.parent(c), Some(b));
+
+        assert_eq!(reg.clear_parent(d), Some(a));
+        assert_eq!(reg.clear_parent(d), None);
+        assert!(!reg.has_component::<Children>(a));
+        assert!(reg.descendants_breadth_first(a).next().is_none());
+    }
+
+    fn direct_changes(kind: StorageKind) {
+        let mut reg = Registry::new(kind);
+        let [root, a, b, c, d, e] = tree(&mut reg);
+
+        // assigning and removing parents directly keeps the children of the parents in sync
+        reg.assign_component(e, Parent(a));
+        assert_eq!(reg.children(a), [c, d, e]);
+        assert!(!reg.has_component::<Children>(b));
+        assert_eq!(reg.remove_component::<Parent>(c), Some(Parent(a)));
+        assert_eq!(reg.children(a), [d, e]);
+
+        // so destroying the former parent leaves the detached entity alive
+        assert!(reg.destroy_entity(&a));
+        assert!(reg.is_alive(c));
+        assert_eq!(reg.children(root), [b]);
+
+        // parents are cloned into snapshots, though they do not implement Clone
+        assert!(reg.snapshot().is_ok());
+    }
+
+    #[test]
+    fn test_direct_changes() {
+        direct_changes(StorageKind::Sparse);
+        direct_changes(StorageKind::Archetype);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot be attached")]
+    fn test_cyclic_parent() {
+        let mut reg = Registry::default();
+        let [root, _, _, c, _, _] = tree(&mut reg);
+        reg.assign_component(root, Parent(c));
+    }
+
+    fn recursive_destroy(kind: StorageKind) {
+        let mut reg = Registry::new(kind);
+        let [root, a, b, c, d, e] = tree(&mut reg);
+
+        assert!(reg.destroy_entity(&a));
+        assert_eq!(reg.num_entities(), 3);
+        assert!(!reg.is_alive(c));
+        assert!(!reg.is_alive(d));
+        assert_eq!(reg.children(root), [b]);
+
+        assert!(reg.destroy_entity(&root));
+        assert_eq!(reg.num_entities(), 0);
+        assert!(!reg.is_alive(b));
+        assert!(!reg.is_alive(e));
+    }
+
+    #[test]
+    fn test_recursive_destroy() {
+        recursive_destroy(StorageKind::Sparse);
+        recursive_destroy(StorageKind::Archetype);
+    }
+}
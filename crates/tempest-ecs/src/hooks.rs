@@ -0,0 +1,230 @@
+use std::any::Any;
+
+use super::{
+    component::Component,
+    registry::{Entity, Registry},
+};
+
+type Hook<T> = Box<dyn FnMut(Entity, &T) + Send + Sync>;
+type ReplaceHook<T> = Box<dyn FnMut(Entity, &T, &T) + Send + Sync>;
+
+/// Hooks of the crate keeping other components consistent with a component type, such as the [Children] of the
+/// entity a [Parent] points to.  Unlike registered hooks, they write to the registry, and run after them.  `check` runs
+/// before a component is assigned, and panics if the component is invalid.
+///
+/// [Children]: crate::hierarchy::Children
+/// [Parent]: crate::hierarchy::Parent
+pub(crate) struct SyncHooks<T> {
+    pub(crate) check: fn(&Registry, Entity, &T),
+    pub(crate) added: fn(&mut Registry, Entity),
+    /// Receives the replaced component.
+    pub(crate) replaced: fn(&mut Registry, Entity, &T),
+    /// Receives the removed component.
+    pub(crate) removed: fn(&mut Registry, Entity, &T),
+}
+
+impl<T> Clone for SyncHooks<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SyncHooks<T> {}
+
+/// Hooks registered for a component type, run in the order they were registered.
+pub(crate) struct ComponentHooks<T> {
+    on_add: Vec<Hook<T>>,
+    on_replace: Vec<ReplaceHook<T>>,
+    on_remove: Vec<Hook<T>>,
+    sync: Option<SyncHooks<T>>,
+}
+
+impl<T> Default for ComponentHooks<T> {
+    fn default() -> Self {
+        Self {
+            on_add: Vec::new(),
+            on_replace: Vec::new(),
+            on_remove: Vec::new(),
+            sync: None,
+        }
+    }
+}
+
+impl<T> ComponentHooks<T> {
+    pub(crate) fn added(&mut self, ent: Entity, component: &T) {
+        self.on_add.iter_mut().for_each(|hook| hook(ent, component));
+    }
+
+    pub(crate) fn replaced(&mut self, ent: Entity, old: &T, new: &T) {
+        self.on_replace
+            .iter_mut()
+            .for_each(|hook| hook(ent, old, new));
+    }
+
+    pub(crate) fn removed(&mut self, ent: Entity, component: &T) {
+        self.on_remove
+            .iter_mut()
+            .for_each(|hook| hook(ent, component));
+    }
+}
+
+/// Type-erased hooks of a component type.
+pub(crate) struct HookEntry {
+    hooks: Box<dyn Any + Send + Sync>,
+    /// Removes the component from an entity about to be destroyed, running the removal hooks.
+    pub(crate) remove: fn(&mut Registry, Entity),
+}
+
+fn remove_component<T: Component>(reg: &mut Registry, ent: Entity) {
+    reg.remove_component::<T>(ent);
+}
+
+impl Registry {
+    /// Registers a hook run when a component of type `T` is assigned to an entity that did not have one.
+    pub fn on_add<T: Component>(&mut self, hook: impl FnMut(Entity, &T) + Send + Sync + 'static) {
+        self.hooks_mut::<T>().on_add.push(Box::new(hook));
+    }
+
+    /// Registers a hook run when a component of type `T` is assigned to an entity that already had one, receiving
+    /// the replaced component then the new one.  Mutating a component in place does not run it.
+    pub fn on_replace<T: Component>(
+        &mut self,
+        hook: impl FnMut(Entity, &T, &T) + Send + Sync + 'static,
+    ) {
+        self.hooks_mut::<T>().on_replace.push(Box::new(hook));
+    }
+
+    /// Registers a hook run when a component of type `T` is removed from an entity, or when the entity is destroyed.
+    pub fn on_remove<T: Component>(
+        &mut self,
+        hook: impl FnMut(Entity, &T) + Send + Sync + 'static,
+    ) {
+        self.hooks_mut::<T>().on_remove.push(Box::new(hook));
+    }
+
+    pub(crate) fn set_sync_hooks<T: Component>(&mut self, sync: SyncHooks<T>) {
+        self.hooks_mut::<T>().sync = Some(sync);
+    }
+
+    pub(crate) fn sync_hooks<T: Component>(&self) -> Option<SyncHooks<T>> {
+        self.hook_entries()
+            .get(T::id())?
+            .as_ref()?
+            .hooks
+            .downcast_ref::<ComponentHooks<T>>()?
+            .sync
+    }
+
+    fn hooks_mut<T: Component>(&mut self) -> &mut ComponentHooks<T> {
+        let id = T::id();
+        let hooks = self.hook_entries_mut();
+        if hooks.len() <= id {
+            hooks.resize_with(id + 1, || None);
+        }
+
+        hooks[id]
+            .get_or_insert_with(|| HookEntry {
+                hooks: Box::<ComponentHooks<T>>::default(),
+                remove: remove_component::<T>,
+            })
+            .hooks
+            .downcast_mut()
+            .expect("Hooks are registered under the identifier of another component.")
+    }
+
+    /// Runs the hooks of `T`, which are taken out of the registry meanwhile so that they can look at its components.
+    pub(crate) fn with_hooks<T: Component>(
+        &mut self,
+        f: impl FnOnce(&Registry, &mut ComponentHooks<T>),
+    ) {
+        let id = T::id();
+        let mut entry = match self.hook_entries_mut().get_mut(id).and_then(Option::take) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        if let Some(hooks) = entry.hooks.downcast_mut() {
+            f(self, hooks);
+        }
+        self.hook_entries_mut()[id] = Some(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::registry::StorageKind;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Mesh(u32);
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Other;
+
+    fn lifecycle(kind: StorageKind) {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut reg = Registry::new(kind);
+
+        let added = log.clone();
+        reg.on_add::<Mesh>(move |ent, mesh| {
+            added
+                .lock()
+                .unwrap()
+                .push(format!("add {} {}", ent.index(), mesh.0))
+        });
+        let replaced = log.clone();
+        reg.on_replace::<Mesh>(move |ent, old, new| {
+            replaced
+                .lock()
+                .unwrap()
+                .push(format!("replace {} {} {}", ent.index(), old.0, new.0))
+        });
+        let removed = log.clone();
+        reg.on_remove::<Mesh>(move |ent, mesh| {
+            removed
+                .lock()
+                .unwrap()
+                .push(format!("remove {} {}", ent.index(), mesh.0))
+        });
+
+        let [parent, child, bystander] = [(); 3].map(|_| reg.create_entity());
+        reg.set_parent(child, parent);
+        reg.assign_component(parent, Mesh(1));
+        reg.assign_component(parent, Other);
+        reg.assign_component(parent, Mesh(2));
+        assert_eq!(reg.get_component::<Mesh>(parent), Some(Mesh(2)));
+
+        // in-place mutation does not count as a replacement
+        reg.get_component_mut::<Mesh>(parent).unwrap().0 = 3;
+        reg.assign_component(child, Mesh(4));
+        reg.assign_component(bystander, Mesh(5));
+        assert_eq!(reg.remove_component::<Mesh>(bystander), Some(Mesh(5)));
+        assert_eq!(reg.remove_component::<Mesh>(bystander), None);
+
+        // destroying an entity removes the components of its descendants, then its own
+        reg.destroy_entity(&parent);
+        assert!(!reg.assign_component(parent, Mesh(6)));
+        assert!(reg.is_alive(bystander));
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "add 0 1",
+                "replace 0 1 2",
+                "add 1 4",
+                "add 2 5",
+                "remove 2 5",
+                "remove 1 4",
+                "remove 0 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lifecycle() {
+        lifecycle(StorageKind::Sparse);
+        lifecycle(StorageKind::Archetype);
+    }
+}
@@ -1,12 +1,29 @@
+pub mod access;
+pub(crate) mod archetype;
+pub mod bundle;
+pub mod commands;
 pub mod component;
 pub mod component_pool;
+pub mod entities;
+pub mod event;
 pub mod graph;
+pub(crate) mod group;
+pub mod hierarchy;
+pub mod hooks;
+pub mod reflect;
 pub mod registry;
+pub mod relation;
+pub mod resource;
+pub mod save;
+pub mod scene;
+pub mod schedule;
 pub mod slot_map;
+pub mod snapshot;
 pub mod sparse_index;
 pub mod sparse_map;
 pub mod sparse_set;
+pub mod tick;
 pub mod transformation;
 pub mod world;
 
-extern crate self as tempest_ecs;
\ No newline at end of file
+extern crate self as tempest_ecs;
@@ -0,0 +1,376 @@
+use std::{
+    any::{Any, TypeId},
+    fmt::Display,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use super::{
+    component::{component_id, Component},
+    registry::{Entity, Registry},
+};
+
+/// Field of a reflected type.  Tuple struct fields are named after their index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldInfo {
+    pub name: &'static str,
+    pub type_name: &'static str,
+    pub type_id: TypeId,
+    /// Offset of the field from the start of the value, in bytes.
+    pub offset: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeInfo {
+    /// Full name of the type, as given by [std::any::type_name], under which [reflected_component_id] finds it.
+    pub name: &'static str,
+    /// Fields in declaration order.  Enums have no reflected fields.
+    pub fields: Vec<FieldInfo>,
+}
+
+impl TypeInfo {
+    pub fn field(&self, name: &str) -> Option<&FieldInfo> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReflectError {
+    UnknownField(String),
+    TypeMismatch {
+        field: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+}
+
+impl Display for ReflectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownField(field) => write!(f, "Field {} does not exist.", field),
+            Self::TypeMismatch {
+                field,
+                expected,
+                found,
+            } => write!(f, "Field {} is of type {}, not {}.", field, expected, found),
+        }
+    }
+}
+
+impl std::error::Error for ReflectError {}
+
+/// Field-level access to a value whose type is not known statically.  Implemented by `#[derive(Component)]`.
+pub trait Reflect: Any + Send + Sync {
+    fn type_info(&self) -> TypeInfo;
+    fn field(&self, name: &str) -> Option<&dyn Any>;
+    fn field_mut(&mut self, name: &str) -> Option<&mut dyn Any>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl dyn Reflect {
+    pub fn get<T: 'static>(&self, name: &str) -> Option<&T> {
+        self.field(name)?.downcast_ref()
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, name: &str) -> Option<&mut T> {
+        self.field_mut(name)?.downcast_mut()
+    }
+
+    /// Assigns the field, which must be of the type of the value.
+    pub fn set<T: 'static>(&mut self, name: &str, value: T) -> Result<(), ReflectError> {
+        let info = self.type_info();
+        let field = self
+            .field_mut(name)
+            .ok_or_else(|| ReflectError::UnknownField(name.to_string()))?;
+
+        match field.downcast_mut::<T>() {
+            Some(field) => {
+                *field = value;
+                Ok(())
+            }
+            None => Err(ReflectError::TypeMismatch {
+                field: name.to_string(),
+                expected: info.field(name).map_or("?", |field| field.type_name),
+                found: std::any::type_name::<T>(),
+            }),
+        }
+    }
+
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+
+    pub fn downcast_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.as_any_mut().downcast_mut()
+    }
+}
+
+/// Type-erased access to the components of a reflected type.
+#[derive(Clone, Copy)]
+struct Reflector {
+    name: &'static str,
+    get: fn(&Registry, Entity) -> Option<&dyn Reflect>,
+    get_mut: fn(&mut Registry, Entity) -> Option<&mut dyn Reflect>,
+}
+
+/// Reflectors indexed by component identifier.
+static REFLECTORS: Mutex<Vec<Option<Reflector>>> = Mutex::new(Vec::new());
+
+fn reflector(component_id: usize) -> Option<Reflector> {
+    REFLECTORS
+        .lock()
+        .unwrap()
+        .get(component_id)
+        .copied()
+        .flatten()
+}
+
+fn get_reflected<T: Component + Reflect>(reg: &Registry, ent: Entity) -> Option<&dyn Reflect> {
+    reg.get_component_ref::<T>(ent)
+        .map(|component| component as &dyn Reflect)
+}
+
+fn get_reflected_mut<T: Component + Reflect>(
+    reg: &mut Registry,
+    ent: Entity,
+) -> Option<&mut dyn Reflect> {
+    reg.get_component_mut::<T>(ent)
+        .map(|component| component as &mut dyn Reflect)
+}
+
+/// Same as [component_id], but memoizes the result in `cache` to avoid taking the global lock on every call, and
+/// registers the reflection of the component type when its identifier is assigned, so that the [Registry] can reflect
+/// it.
+#[doc(hidden)]
+pub fn cached_reflected_component_id<T: Component + Reflect>(cache: &AtomicUsize) -> usize {
+    match cache.load(Ordering::Relaxed) {
+        usize::MAX => {
+            let id = component_id::<T>();
+            let mut reflectors = REFLECTORS.lock().unwrap();
+            if reflectors.len() <= id {
+                reflectors.resize(id + 1, None);
+            }
+            reflectors[id] = Some(Reflector {
+                name: std::any::type_name::<T>(),
+                get: get_reflected::<T>,
+                get_mut: get_reflected_mut::<T>,
+            });
+
+            cache.store(id, Ordering::Relaxed);
+            id
+        }
+        id => id,
+    }
+}
+
+/// Identifier of the reflected component type with the provided name, as given by [std::any::type_name], so that
+/// types of the same name declared in different modules are told apart.  Component types are only known once their
+/// identifier was used, which assigning a component does.
+pub fn reflected_component_id(name: &str) -> Option<usize> {
+    REFLECTORS
+        .lock()
+        .unwrap()
+        .iter()
+        .position(|reflector| reflector.map_or(false, |reflector| reflector.name == name))
+}
+
+impl Registry {
+    /// Reflects the component of the entity with the provided identifier, if its type derives [Component].
+    pub fn reflect(&self, ent: Entity, component_id: usize) -> Option<&dyn Reflect> {
+        (reflector(component_id)?.get)(self, ent)
+    }
+
+    /// Reflects the component of the entity with the provided identifier mutably, marking it as changed.
+    pub fn reflect_mut(&mut self, ent: Entity, component_id: usize) -> Option<&mut dyn Reflect> {
+        (reflector(component_id)?.get_mut)(self, ent)
+    }
+
+    /// Reflects every component of the entity whose type derives [Component], along with their identifiers.
+    pub fn reflect_all(&self, ent: Entity) -> Vec<(usize, &dyn Reflect)> {
+        let reflectors: Vec<(usize, Reflector)> = REFLECTORS
+            .lock()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .filter_map(|(id, reflector)| Some((id, (*reflector)?)))
+            .collect();
+
+        reflectors
+            .into_iter()
+            .filter_map(|(id, reflector)| Some((id, (reflector.get)(self, ent)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::StorageKind;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Stats {
+        health: u32,
+        speed: f32,
+        name: String,
+    }
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Pair(u8, u64);
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    enum Mood {
+        Happy,
+    }
+
+    mod other {
+        use crate::component::Component;
+
+        #[derive(Component, Clone, Debug, PartialEq)]
+        pub struct Stats(pub u8);
+    }
+
+    fn offset_of<T, F>(value: &T, field: &F) -> usize {
+        field as *const F as usize - value as *const T as usize
+    }
+
+    #[test]
+    fn test_type_info() {
+        let stats = Stats {
+            health: 1,
+            speed: 2.0,
+            name: "a".to_string(),
+        };
+        let info = stats.type_info();
+        assert_eq!(info.name, std::any::type_name::<Stats>());
+        assert_eq!(
+            info.fields
+                .iter()
+                .map(|field| field.name)
+                .collect::<Vec<_>>(),
+            ["health", "speed", "name"]
+        );
+
+        let speed = info.field("speed").unwrap();
+        assert_eq!(speed.type_name, "f32");
+        assert_eq!(speed.type_id, TypeId::of::<f32>());
+        assert_eq!(speed.offset, offset_of(&stats, &stats.speed));
+        assert_eq!(
+            info.field("name").unwrap().offset,
+            offset_of(&stats, &stats.name)
+        );
+
+        let pair = Pair(1, 2);
+        let info = pair.type_info();
+        assert_eq!(info.field("1").unwrap().type_name, "u64");
+        assert_eq!(info.field("1").unwrap().offset, offset_of(&pair, &pair.1));
+        assert!(Mood::Happy.type_info().fields.is_empty());
+    }
+
+    #[test]
+    fn test_get_and_set() {
+        let mut pair = Pair(1, 2);
+        let reflected: &mut dyn Reflect = &mut pair;
+        assert_eq!(reflected.get::<u8>("0"), Some(&1));
+        assert_eq!(reflected.get::<u32>("0"), None);
+        assert_eq!(reflected.get::<u8>("2"), None);
+
+        *reflected.get_mut::<u64>("1").unwrap() += 1;
+        reflected.set("0", 5u8).unwrap();
+        assert_eq!(
+            reflected.set("1", 5u32),
+            Err(ReflectError::TypeMismatch {
+                field: "1".to_string(),
+                expected: "u64",
+                found: "u32"
+            })
+        );
+        assert_eq!(
+            reflected.set("x", 5u8),
+            Err(ReflectError::UnknownField("x".to_string()))
+        );
+        assert_eq!(reflected.downcast_ref::<Pair>(), Some(&Pair(5, 3)));
+    }
+
+    fn registry_reflection(kind: StorageKind) {
+        let mut reg = Registry::new(kind);
+        let ent = reg.create_entity();
+        reg.assign_component(
+            ent,
+            Stats {
+                health: 10,
+                speed: 1.0,
+                name: "bob".to_string(),
+            },
+        );
+        reg.assign_component(ent, Pair(1, 2));
+
+        let stats_id = reflected_component_id(std::any::type_name::<Stats>()).unwrap();
+        assert_eq!(stats_id, Stats::id());
+        assert_eq!(
+            reg.reflect(ent, stats_id).unwrap().get::<String>("name"),
+            Some(&"bob".to_string())
+        );
+
+        let before = reg.tick();
+        reg.increment_tick();
+        reg.reflect_mut(ent, stats_id)
+            .unwrap()
+            .set("health", 5u32)
+            .unwrap();
+        assert_eq!(reg.get_component_ref::<Stats>(ent).unwrap().health, 5);
+        assert!(reg
+            .get_component_ticks::<Stats>(ent)
+            .unwrap()
+            .is_changed(before));
+
+        let mut names: Vec<&str> = reg
+            .reflect_all(ent)
+            .iter()
+            .map(|(_, component)| component.type_info().name)
+            .collect();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            [
+                std::any::type_name::<Pair>(),
+                std::any::type_name::<Stats>()
+            ]
+        );
+        assert_eq!(reflected_component_id(names[0]), Some(Pair::id()));
+
+        assert!(reg.reflect(ent, Mood::id()).is_none());
+    }
+
+    #[test]
+    fn test_registry_reflection() {
+        registry_reflection(StorageKind::Sparse);
+        registry_reflection(StorageKind::Archetype);
+    }
+
+    #[test]
+    fn test_same_name_components() {
+        let ids = [Stats::id(), other::Stats::id()];
+        assert_ne!(ids[0], ids[1]);
+        assert_eq!(
+            [
+                reflected_component_id(std::any::type_name::<Stats>()),
+                reflected_component_id(std::any::type_name::<other::Stats>())
+            ],
+            ids.map(Some)
+        );
+        assert_eq!(reflected_component_id("Stats"), None);
+        assert_ne!(
+            other::Stats(1).type_info().name,
+            Stats {
+                health: 1,
+                speed: 2.0,
+                name: "a".to_string(),
+            }
+            .type_info()
+            .name
+        );
+    }
+}
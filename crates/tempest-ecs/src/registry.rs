@@ -1,15 +1,33 @@
-use std::marker::PhantomData;
+use std::{
+    any::Any,
+    cmp::Ordering as CmpOrdering,
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use rayon::prelude::*;
 
+pub use super::entities::Entity;
 pub use tempest_ecs_macros::RegistryQuery;
 
 use super::{
+    access::{Access, BorrowError, BorrowFlags},
+    archetype::{ArchetypeStorage, EntityLocation},
+    bundle::{Bundle, BundleType, BundleTypes, BundleWriter},
     component::Component,
     component_pool::ComponentPool,
-    slot_map::{SlotMap, SlotMapKey},
+    entities::EntityAllocator,
+    group::{OwningGroups, Pools},
+    hooks::HookEntry,
+    relation::RelationCleanup,
+    snapshot::SnapshotError,
     sparse_index::SparseTableIndex,
     sparse_map::SparseMap,
+    tick::{ComponentTicks, Tracked},
 };
 
+/// Key of an entity in component storage, which is the index of the entity.  Components are erased when their entity
+/// is destroyed, so the key is never shared by two alive entities.
 #[derive(Clone, Copy, Eq, PartialEq)]
 pub(crate) struct EntityKey {
     pub(crate) id: usize,
@@ -29,108 +47,406 @@ impl SparseTableIndex for EntityKey {
     }
 }
 
-#[derive(Default)]
+/// Strategy used by a [Registry] to lay out component data in memory.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum StorageKind {
+    /// Each component type is stored in its own sparse map.  Adding and removing components is cheap, but queries
+    /// over several components perform a sparse lookup per component per entity.
+    #[default]
+    Sparse,
+
+    /// Entities with the same set of components share a table with one contiguous column per component.  Queries
+    /// only visit the tables matching every requested component, at the cost of moving an entity between tables
+    /// whenever a component is added or removed.
+    Archetype,
+}
+
+type Pool<T> = SparseMap<EntityKey, Tracked<T>>;
+
+enum ComponentStorage {
+    Sparse(Vec<Option<Box<dyn ComponentPool<EntityKey>>>>),
+    Archetype(ArchetypeStorage),
+}
+
+impl ComponentStorage {
+    fn new(kind: StorageKind) -> Self {
+        match kind {
+            StorageKind::Sparse => Self::Sparse(Vec::default()),
+            StorageKind::Archetype => Self::Archetype(ArchetypeStorage::default()),
+        }
+    }
+
+    fn snapshot(&self, cloners: &[Option<Cloner>]) -> Result<Self, SnapshotError> {
+        let cloner = |id: usize| cloners.get(id).and_then(|cloner| cloner.as_deref());
+        Ok(match self {
+            Self::Sparse(pools) => Self::Sparse(
+                pools
+                    .iter()
+                    .enumerate()
+                    .map(|(id, pool)| {
+                        pool.as_ref()
+                            .map(|pool| pool.snapshot(cloner(id)))
+                            .transpose()
+                    })
+                    .collect::<Result<_, SnapshotError>>()?,
+            ),
+            Self::Archetype(archetypes) => Self::Archetype(archetypes.snapshot(cloner)?),
+        })
+    }
+}
+
+/// Type-erased `fn(&T) -> T` cloning components of a type `T` that does not implement [Clone] itself into snapshots.
+type Cloner = Box<dyn Any + Send + Sync>;
+
 pub struct Registry {
-    pools: Vec<Option<Box<dyn ComponentPool<EntityKey>>>>,
-    entities: SlotMap<EntityKey>,
+    storage: ComponentStorage,
+    entities: EntityAllocator,
+    hooks: Vec<Option<HookEntry>>,
+    /// Cloners registered for component types that do not implement [Clone], by component identifier.
+    cloners: Vec<Option<Cloner>>,
+    /// Component identifiers of the relation kinds in use, with the removal of the pairs of an entity being destroyed.
+    relation_cleanups: Vec<(usize, RelationCleanup)>,
+    groups: OwningGroups,
+    tick: AtomicU64,
+    last_tick: u64,
 }
 
-#[derive(Clone, Copy)]
-pub struct Entity {
-    pub id: SlotMapKey,
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new(StorageKind::default())
+    }
 }
 
 impl Registry {
+    pub fn new(kind: StorageKind) -> Self {
+        let mut reg = Self {
+            storage: ComponentStorage::new(kind),
+            entities: EntityAllocator::default(),
+            hooks: Vec::new(),
+            cloners: Vec::new(),
+            relation_cleanups: Vec::new(),
+            groups: OwningGroups::default(),
+            tick: AtomicU64::new(1),
+            last_tick: 0,
+        };
+        reg.init_hierarchy();
+        reg
+    }
+
+    pub fn storage_kind(&self) -> StorageKind {
+        match self.storage {
+            ComponentStorage::Sparse(_) => StorageKind::Sparse,
+            ComponentStorage::Archetype(_) => StorageKind::Archetype,
+        }
+    }
+
+    /// Current tick of the registry, stamped on components when they are added or mutably accessed.
+    pub fn tick(&self) -> u64 {
+        self.tick.load(Ordering::Relaxed)
+    }
+
+    /// Tick preceding the current one.  Queries report the components added or changed since this tick by default.
+    pub fn last_tick(&self) -> u64 {
+        self.last_tick
+    }
+
+    /// Advances the tick of the registry without moving [Registry::last_tick], so that changes made from now on are
+    /// told apart from earlier ones.
+    pub(crate) fn advance_tick(&mut self) -> u64 {
+        let tick = self.tick.get_mut();
+        *tick += 1;
+        *tick
+    }
+
+    /// Advances the tick of the registry, returning the new tick.
+    pub fn increment_tick(&mut self) -> u64 {
+        let tick = self.tick.get_mut();
+        self.last_tick = *tick;
+        *tick += 1;
+        *tick
+    }
+
     pub fn create_entity(&mut self) -> Entity {
-        let entity_id = self.entities.len();
-        let ent_key = EntityKey { id: entity_id };
-        let key = self.entities.insert(ent_key);
+        let ent = self.entities.allocate();
 
-        Entity { id: key }
+        if let ComponentStorage::Archetype(archetypes) = &mut self.storage {
+            archetypes.spawn(Self::entity_key(ent));
+        }
+
+        ent
     }
 
-    pub fn destroy_entity(&mut self, ent: &Entity) -> bool {
-        let key = ent.id;
-        let ent_key = self.entities.get(key);
+    /// Creates an entity with the components of the bundle.
+    pub fn spawn<B: Bundle>(&mut self, bundle: B) -> Entity {
+        let types = BundleTypes::of::<B>();
+        let archetype = self.bundle_archetype(&types);
+        let tick = self.tick();
+        self.spawn_bundle(bundle, &types, archetype, tick)
+    }
 
-        if let Some(k) = ent_key {
-            self.pools.iter_mut().for_each(|pool| {
-                if let Some(p) = pool {
-                    p.erase(*k);
+    /// Creates an entity for each bundle, returning them in order.  Storage is reserved once for the number of bundles
+    /// the iterator reports, and each component is written straight to its final storage.
+    pub fn spawn_batch<B: Bundle, I: IntoIterator<Item = B>>(&mut self, bundles: I) -> Vec<Entity> {
+        let bundles = bundles.into_iter();
+        let additional = bundles.size_hint().0;
+        let types = BundleTypes::of::<B>();
+
+        self.entities.reserve(additional);
+        let archetype = self.bundle_archetype(&types);
+        let key_bound = self.entities.capacity() + additional;
+        match (&mut self.storage, archetype) {
+            (ComponentStorage::Archetype(archetypes), Some(archetype)) => {
+                archetypes.reserve(archetype, additional, key_bound);
+            }
+            _ => {
+                for ty in types.iter() {
+                    (ty.reserve)(self, additional);
                 }
-            });
+            }
+        }
+
+        let tick = self.tick();
+        let mut spawned = Vec::with_capacity(additional);
+        for bundle in bundles {
+            spawned.push(self.spawn_bundle(bundle, &types, archetype, tick));
+        }
 
-            self.entities.remove(key);
+        spawned
+    }
 
-            return true;
+    /// Archetype storing exactly the components of a bundle, with archetype storage.
+    fn bundle_archetype(&mut self, types: &[BundleType]) -> Option<usize> {
+        match &mut self.storage {
+            ComponentStorage::Sparse(_) => None,
+            ComponentStorage::Archetype(archetypes) => {
+                Some(archetypes.find_or_create(types.iter().map(|ty| (ty.id, ty.column)).collect()))
+            }
+        }
+    }
+
+    fn spawn_bundle<B: Bundle>(
+        &mut self,
+        bundle: B,
+        types: &[BundleType],
+        archetype: Option<usize>,
+        tick: u64,
+    ) -> Entity {
+        let ent = self.entities.allocate();
+        let key = Self::entity_key(ent);
+        if let (ComponentStorage::Archetype(archetypes), Some(archetype)) =
+            (&mut self.storage, archetype)
+        {
+            archetypes.spawn_in(key, archetype);
         }
 
-        false
+        let mut writer = BundleWriter {
+            reg: self,
+            key,
+            archetype,
+            tick,
+            types,
+            written: 0,
+        };
+        bundle.write(&mut writer);
+        assert!(
+            writer.written == types.len(),
+            "Bundle {} wrote {} of its {} components.",
+            std::any::type_name::<B>(),
+            writer.written,
+            types.len()
+        );
+
+        for ty in types.iter() {
+            (ty.added)(self, ent);
+        }
+        ent
     }
 
-    pub fn assign_component<T: Component>(&mut self, ent: Entity, component: T) -> bool {
-        let id = self.entities.get(ent.id).map(|k| *k);
-        let pool = self.fetch_or_create_pool::<T>();
+    /// Destroys the entity along with every descendant attached to it, detaching it from its parent.
+    pub fn destroy_entity(&mut self, ent: &Entity) -> bool {
+        if !self.is_alive(*ent) {
+            return false;
+        }
 
-        match id {
-            Some(id) => {
-                pool.insert(id.clone(), component);
-                true
-            }
-            None => false,
+        self.clear_parent(*ent);
+        let descendants: Vec<Entity> = self.descendants_depth_first(*ent).collect();
+        for descendant in descendants {
+            self.erase_entity(descendant);
+        }
+
+        self.erase_entity(*ent)
+    }
+
+    pub fn is_alive(&self, ent: Entity) -> bool {
+        self.entities.is_alive(ent)
+    }
+
+    fn entity_key(ent: Entity) -> EntityKey {
+        EntityKey {
+            id: ent.index() as usize,
         }
     }
 
-    pub fn get_component<T: Component>(&self, ent: Entity) -> Option<T> {
-        let id = self.entities.get(ent.id);
-        let pool = self.fetch_pool::<T>();
+    /// Key of the entity in component storage, or `None` if the entity is not alive.
+    fn key(&self, ent: Entity) -> Option<EntityKey> {
+        self.is_alive(ent).then(|| Self::entity_key(ent))
+    }
 
-        if let Some(id) = id {
-            if let Some(pool) = pool {
-                return pool.get(id.clone()).map(|v| *v);
+    /// Erases the components of the entity and frees it, leaving the entities it is related to untouched.
+    fn erase_entity(&mut self, ent: Entity) -> bool {
+        if !self.is_alive(ent) {
+            return false;
+        }
+
+        let cleanups: Vec<RelationCleanup> = self
+            .relation_cleanups
+            .iter()
+            .map(|(_, cleanup)| *cleanup)
+            .collect();
+        for cleanup in cleanups {
+            cleanup(self, ent);
+        }
+
+        // Components with hooks are removed one by one, so that their removal hooks see them
+        let removals: Vec<fn(&mut Registry, Entity)> = self
+            .hooks
+            .iter()
+            .flatten()
+            .map(|entry| entry.remove)
+            .collect();
+        for remove in removals {
+            remove(self, ent);
+        }
+
+        let key = Self::entity_key(ent);
+        match &mut self.storage {
+            ComponentStorage::Sparse(pools) => {
+                self.groups.erasing(pools, key);
+                pools.iter_mut().for_each(|pool| {
+                    if let Some(p) = pool {
+                        p.erase(key);
+                    }
+                })
             }
+            ComponentStorage::Archetype(archetypes) => archetypes.despawn(key),
         }
 
-        None
+        self.entities.free(ent)
     }
 
-    pub fn has_component<T: Component>(&self, ent: Entity) -> bool {
-        let id = self.entities.get(ent.id);
-        let pool = self.fetch_pool::<T>();
+    /// Assigns the component to the entity, replacing the component of the same type it may already have.
+    pub fn assign_component<T: Component>(&mut self, ent: Entity, component: T) -> bool {
+        let id = match self.key(ent) {
+            Some(id) => id,
+            None => return false,
+        };
 
-        if let Some(id) = id {
-            if let Some(pool) = pool {
-                return pool.contains(*id);
+        let sync = self.sync_hooks::<T>();
+        if let Some(sync) = sync {
+            (sync.check)(self, ent, &component);
+        }
+
+        let tick = self.tick();
+        if let Some(tracked) = self.get_tracked_mut::<T>(ent) {
+            let old = std::mem::replace(tracked.value_mut(tick), component);
+            self.with_hooks::<T>(|reg, hooks| {
+                hooks.replaced(ent, &old, reg.get_component_ref(ent).unwrap())
+            });
+            if let Some(sync) = sync {
+                (sync.replaced)(self, ent, &old);
             }
+            return true;
         }
 
-        false
+        let component = Tracked::new(component, tick);
+        match &mut self.storage {
+            ComponentStorage::Sparse(_) => self.insert_pooled(id, component),
+            ComponentStorage::Archetype(archetypes) => archetypes.insert(id, component),
+        }
+        self.with_hooks::<T>(|reg, hooks| hooks.added(ent, reg.get_component_ref(ent).unwrap()));
+        if let Some(sync) = sync {
+            (sync.added)(self, ent);
+        }
+
+        true
+    }
+
+    pub fn get_component<T: Component + Clone>(&self, ent: Entity) -> Option<T> {
+        self.get_component_ref(ent).cloned()
+    }
+
+    pub fn get_component_ref<T: Component>(&self, ent: Entity) -> Option<&T> {
+        self.get_tracked(ent).map(|tracked| &tracked.value)
+    }
+
+    /// Fetches a mutable reference to the component, marking it as changed.
+    pub fn get_component_mut<T: Component>(&mut self, ent: Entity) -> Option<&mut T> {
+        let tick = self.tick();
+        self.get_tracked_mut(ent)
+            .map(|tracked| tracked.value_mut(tick))
+    }
+
+    pub fn get_component_ticks<T: Component>(&self, ent: Entity) -> Option<ComponentTicks> {
+        self.get_tracked::<T>(ent).map(|tracked| tracked.ticks)
+    }
+
+    pub fn has_component<T: Component>(&self, ent: Entity) -> bool {
+        self.has_component_id(ent, T::id())
     }
 
     pub fn has_component_id(&self, ent: Entity, component_id: usize) -> bool {
-        let id = self.entities.get(ent.id);
-        if let Some(id) = id {
-            let pool = self.fetch_pool_base(component_id);
-            if let Some(pool) = pool {
-                return pool.contains(*id);
+        self.key(ent)
+            .map(|id| self.contains_component_id(id, component_id))
+            .unwrap_or(false)
+    }
+
+    pub fn remove_component<T: Component>(&mut self, ent: Entity) -> Option<T> {
+        let id = self.key(ent)?;
+
+        let component = match &mut self.storage {
+            ComponentStorage::Sparse(pools) => {
+                self.groups.removing(pools, id, T::id());
+                self.fetch_pool_mut::<T>()?.remove(id).map(|t| t.value)
             }
+            ComponentStorage::Archetype(archetypes) => archetypes.remove(id),
+        }?;
+        self.with_hooks::<T>(|_, hooks| hooks.removed(ent, &component));
+        if let Some(sync) = self.sync_hooks::<T>() {
+            (sync.removed)(self, ent, &component);
         }
 
-        false
+        Some(component)
     }
 
-    pub fn remove_component<T: Component>(&mut self, ent: Entity) -> Option<T> {
-        let id = self.entities.get(ent.id).map(|k| *k);
-        let pool = self.fetch_pool_mut::<T>();
-        match id {
-            Some(id) => match pool {
-                Some(p) => p.remove(id),
-                None => None,
-            },
-            None => None,
+    /// Sorts the components of type `T` with the comparator, so that queries driven by them visit entities in order,
+    /// such as sprites by layer.  The sort is stable.  With archetype storage, the rows of each archetype storing `T`
+    /// are sorted, and archetypes are still visited one after the other.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is owned by a group, whose pools must stay in the same order.
+    pub fn sort<T: Component>(&mut self, mut compare: impl FnMut(&T, &T) -> CmpOrdering) {
+        match &mut self.storage {
+            ComponentStorage::Sparse(_) => {
+                if self.groups.owns(T::id()) {
+                    panic!(
+                        "Component {} is owned by a group, and cannot be sorted on its own.",
+                        std::any::type_name::<T>()
+                    );
+                }
+                if let Some(pool) = self.fetch_pool_mut::<T>() {
+                    pool.sort_by(|a, b| compare(&a.value, &b.value));
+                }
+            }
+            ComponentStorage::Archetype(archetypes) => archetypes.sort(compare),
         }
     }
 
+    /// Iterates over the entities alive in the registry, in no particular order.
+    pub fn iter_entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.iter()
+    }
+
     pub fn num_entities(&self) -> usize {
         self.entities.len()
     }
@@ -139,223 +455,786 @@ impl Registry {
         self.entities.capacity()
     }
 
-    fn fetch_or_create_pool<T: Component>(&mut self) -> &mut SparseMap<EntityKey, T, 1024> {
-        let id = T::id();
+    /// Releases the memory held beyond the entities and components currently alive, such as after unloading a level.
+    /// Sparse pages left without any component are freed, pools left without any component are dropped, and dense
+    /// storage is trimmed to its length.
+    pub fn shrink_to_fit(&mut self) {
+        self.entities.shrink_to_fit();
+
+        match &mut self.storage {
+            ComponentStorage::Sparse(pools) => {
+                for slot in pools.iter_mut() {
+                    match slot {
+                        Some(pool) if pool.is_empty() => *slot = None,
+                        Some(pool) => pool.shrink_to_fit(),
+                        None => {}
+                    }
+                }
 
-        if id < self.pools.len() {
-            let pool = &self.pools[id];
-            match pool {
-                Some(_) => {}
-                None => return self.register_pool::<T>(),
+                while let Some(None) = pools.last() {
+                    pools.pop();
+                }
+                pools.shrink_to_fit();
             }
-        } else {
-            return self.register_pool::<T>();
-        };
+            ComponentStorage::Archetype(archetypes) => archetypes.shrink_to_fit(),
+        }
+    }
 
-        unsafe {
-            self.pools[id]
-                .as_mut()
-                .unwrap_unchecked()
-                .as_any_mut()
-                .downcast_mut::<SparseMap<EntityKey, T, 1024>>()
-                .unwrap_unchecked()
+    fn get_tracked<T: Component>(&self, ent: Entity) -> Option<&Tracked<T>> {
+        let id = self.key(ent)?;
+
+        match &self.storage {
+            ComponentStorage::Sparse(_) => self.fetch_pool::<T>()?.get(id),
+            ComponentStorage::Archetype(archetypes) => archetypes.get(archetypes.location(id)?),
         }
     }
 
-    pub(crate) fn fetch_pool<T: Component>(&self) -> Option<&SparseMap<EntityKey, T, 1024>> {
+    /// Copies the entities and components of the registry, without its hooks.
+    pub(crate) fn snapshot_state(&self) -> Result<Registry, SnapshotError> {
+        Ok(Registry {
+            storage: self.storage.snapshot(&self.cloners)?,
+            entities: self.entities.clone(),
+            hooks: Vec::new(),
+            cloners: Vec::new(),
+            relation_cleanups: self.relation_cleanups.clone(),
+            groups: self.groups.clone(),
+            tick: AtomicU64::new(self.tick()),
+            last_tick: self.last_tick,
+        })
+    }
+
+    /// Replaces the entities and components of the registry with those of the snapshot, keeping its hooks and
+    /// cloners.
+    pub(crate) fn restore_state(&mut self, snapshot: Registry) {
+        self.storage = snapshot.storage;
+        self.entities = snapshot.entities;
+        self.groups = snapshot.groups;
+        for (id, cleanup) in snapshot.relation_cleanups {
+            if !self.relation_cleanups.iter().any(|(other, _)| *other == id) {
+                self.relation_cleanups.push((id, cleanup));
+            }
+        }
+        *self.tick.get_mut() = snapshot.tick.into_inner();
+        self.last_tick = snapshot.last_tick;
+    }
+
+    /// Sets the cloner of the components of type `T` into snapshots, for types that do not implement [Clone].
+    pub(crate) fn set_cloner<T: Component>(&mut self, cloner: fn(&T) -> T) {
         let id = T::id();
+        if self.cloners.len() <= id {
+            self.cloners.resize_with(id + 1, || None);
+        }
+        self.cloners[id] = Some(Box::new(cloner));
+    }
 
-        if id < self.pools.len() {
-            let pool = &self.pools[id];
-            match pool {
-                Some(_) => {}
-                None => return None,
+    /// Reserves the pool of `T` so that `additional` more components can be inserted without reallocating.
+    pub(crate) fn reserve_pool<T: Component>(&mut self, additional: usize) {
+        if let ComponentStorage::Sparse(_) = self.storage {
+            self.fetch_or_create_pool::<T>().reserve(additional);
+        }
+    }
+
+    /// Writes a component of an entity being spawned, see [Registry::spawn_batch].
+    pub(crate) fn write_spawned<T: Component>(
+        &mut self,
+        key: EntityKey,
+        archetype: Option<usize>,
+        component: Tracked<T>,
+    ) {
+        match (&mut self.storage, archetype) {
+            (ComponentStorage::Archetype(archetypes), Some(archetype)) => {
+                archetypes.push(archetype, component)
             }
-        } else {
-            return None;
-        };
+            _ => self.insert_pooled(key, component),
+        }
+    }
 
-        unsafe {
-            self.pools[id]
-                .as_ref()
-                .unwrap_unchecked()
-                .as_any()
-                .downcast_ref::<SparseMap<EntityKey, T, 1024>>()
+    /// Inserts the component into its pool, and the entity into the group owning it once complete.
+    fn insert_pooled<T: Component>(&mut self, key: EntityKey, component: Tracked<T>) {
+        self.fetch_or_create_pool::<T>().insert(key, component);
+        if let ComponentStorage::Sparse(pools) = &mut self.storage {
+            self.groups.inserted(pools, key, T::id());
         }
     }
 
-    fn fetch_pool_base(&self, id: usize) -> Option<&Box<dyn ComponentPool<EntityKey>>> {
-        if id < self.pools.len() {
-            let pool = &self.pools[id];
-            match pool {
-                Some(_) => {}
-                None => return None,
+    fn get_tracked_mut<T: Component>(&mut self, ent: Entity) -> Option<&mut Tracked<T>> {
+        let id = self.key(ent)?;
+
+        match self.storage {
+            ComponentStorage::Sparse(_) => self.fetch_pool_mut::<T>()?.get_mut(id),
+            ComponentStorage::Archetype(ref mut archetypes) => {
+                archetypes.get_mut(archetypes.location(id)?)
             }
-        } else {
-            return None;
-        };
+        }
+    }
 
-        unsafe { Some(self.pools[id].as_ref().unwrap_unchecked()) }
+    pub(crate) fn relation_cleanups_mut(&mut self) -> &mut Vec<(usize, RelationCleanup)> {
+        &mut self.relation_cleanups
     }
 
-    fn fetch_pool_mut<T: Component>(&mut self) -> Option<&mut SparseMap<EntityKey, T, 1024>> {
+    pub(crate) fn hook_entries(&self) -> &[Option<HookEntry>] {
+        &self.hooks
+    }
+
+    pub(crate) fn hook_entries_mut(&mut self) -> &mut Vec<Option<HookEntry>> {
+        &mut self.hooks
+    }
+
+    pub(crate) fn groups(&self) -> &OwningGroups {
+        &self.groups
+    }
+
+    /// Owning groups along with the pools they reorder.
+    ///
+    /// # Panics
+    ///
+    /// Panics with archetype storage.
+    pub(crate) fn groups_and_pools_mut(&mut self) -> (&mut OwningGroups, &mut Pools) {
+        match &mut self.storage {
+            ComponentStorage::Sparse(pools) => (&mut self.groups, pools),
+            ComponentStorage::Archetype(_) => {
+                panic!("Groups are not available with archetype storage.")
+            }
+        }
+    }
+
+    /// Upper bound of the identifiers of the components stored in the registry.
+    fn component_id_bound(&self) -> usize {
+        match &self.storage {
+            ComponentStorage::Sparse(pools) => pools.len(),
+            ComponentStorage::Archetype(archetypes) => archetypes.component_id_bound(),
+        }
+    }
+
+    fn contains_component_id(&self, entity: EntityKey, component_id: usize) -> bool {
+        match &self.storage {
+            ComponentStorage::Sparse(_) => self
+                .fetch_pool_base(component_id)
+                .map(|pool| pool.contains(entity))
+                .unwrap_or(false),
+            ComponentStorage::Archetype(archetypes) => archetypes.contains(entity, component_id),
+        }
+    }
+
+    fn pools(&self) -> &[Option<Box<dyn ComponentPool<EntityKey>>>] {
+        match &self.storage {
+            ComponentStorage::Sparse(pools) => pools,
+            ComponentStorage::Archetype(_) => &[],
+        }
+    }
+
+    fn pools_mut(&mut self) -> &mut Vec<Option<Box<dyn ComponentPool<EntityKey>>>> {
+        match &mut self.storage {
+            ComponentStorage::Sparse(pools) => pools,
+            ComponentStorage::Archetype(_) => {
+                panic!("Component pools are not available with archetype storage.")
+            }
+        }
+    }
+
+    fn fetch_or_create_pool<T: Component>(&mut self) -> &mut Pool<T> {
         let id = T::id();
 
-        if id < self.pools.len() {
-            let pool = &self.pools[id];
+        if id < self.pools().len() {
+            let pool = &self.pools()[id];
             match pool {
                 Some(_) => {}
-                None => return None,
+                None => return self.register_pool::<T>(),
             }
         } else {
-            return None;
+            return self.register_pool::<T>();
         };
 
         unsafe {
-            self.pools[id]
+            self.pools_mut()[id]
                 .as_mut()
                 .unwrap_unchecked()
                 .as_any_mut()
-                .downcast_mut::<SparseMap<EntityKey, T, 1024>>()
+                .downcast_mut::<Pool<T>>()
+                .unwrap_unchecked()
         }
     }
 
-    fn register_pool<T: Component>(&mut self) -> &mut SparseMap<EntityKey, T, 1024> {
+    pub(crate) fn fetch_pool<T: Component>(&self) -> Option<&Pool<T>> {
+        self.fetch_pool_base(T::id())?
+            .as_any()
+            .downcast_ref::<Pool<T>>()
+    }
+
+    fn fetch_pool_base(&self, id: usize) -> Option<&dyn ComponentPool<EntityKey>> {
+        self.pools().get(id)?.as_deref()
+    }
+
+    fn fetch_pool_mut<T: Component>(&mut self) -> Option<&mut Pool<T>> {
+        self.pools_mut()
+            .get_mut(T::id())?
+            .as_mut()?
+            .as_any_mut()
+            .downcast_mut::<Pool<T>>()
+    }
+
+    fn register_pool<T: Component>(&mut self) -> &mut Pool<T> {
         let id: usize = T::id();
-        assert!(id >= self.pools.len());
+        let pools = self.pools_mut();
 
-        self.pools.resize_with(id + 1, || None);
+        if id >= pools.len() {
+            pools.resize_with(id + 1, || None);
+        }
 
-        let pool = SparseMap::<EntityKey, T, 1024>::default();
-        self.pools[id] = Some(Box::new(pool));
+        let pool = Pool::<T>::with_page_size(T::PAGE_SIZE);
+        pools[id] = Some(Box::new(pool));
 
         unsafe {
-            self.pools[id]
+            pools[id]
                 .as_mut()
                 .unwrap_unchecked()
                 .as_any_mut()
-                .downcast_mut::<SparseMap<EntityKey, T, 1024>>()
+                .downcast_mut::<Pool<T>>()
                 .unwrap_unchecked()
         }
     }
 }
 
+/// Cursor of a query over a [Registry].
+///
+/// With sparse storage, `id` is the key of the entity in the component pools.  With archetype storage, `archetype`
+/// names the table being visited and `id` is the row within that table.  Components added or changed after the
+/// `since` tick are matched by the added and changed filters of the query, and components reached mutably are marked
+/// as changed at `tick`.
 #[derive(Clone, Copy)]
 pub struct QueryIterator {
     pub id: usize,
+    pub archetype: Option<usize>,
+    pub since: u64,
+    pub tick: u64,
+    /// With sparse storage, index of the entity in the packed arrays of the pools owned by the group driving the
+    /// query, if any.
+    pub position: Option<usize>,
 }
 
 impl Registry {
+    fn location_from_iter(&self, it: QueryIterator) -> Option<EntityLocation> {
+        it.archetype.map(|archetype| EntityLocation {
+            archetype,
+            row: it.id,
+        })
+    }
+
     pub fn contains_component_from_iter<T: Component>(&self, it: QueryIterator) -> bool {
-        let entity = self.entities.at_index(it.id);
-        if let Some(entity) = entity {
-            let pool = self.fetch_pool::<T>();
-            if let Some(pool) = pool {
-                pool.contains(entity)
-            } else {
-                false
+        match &self.storage {
+            ComponentStorage::Sparse(_) => {
+                self.contains_component_id(EntityKey { id: it.id }, T::id())
             }
-        } else {
-            false
+            ComponentStorage::Archetype(archetypes) => it
+                .archetype
+                .and_then(|idx| archetypes.archetypes().get(idx))
+                .map(|archetype| archetype.contains(T::id()))
+                .unwrap_or(false),
         }
     }
 
-    pub fn get_component_from_iter<T: Component>(&self, it: QueryIterator) -> Option<T> {
-        let entity = self.entities.at_index(it.id);
-        if let Some(entity) = entity {
-            let pool = self.fetch_pool::<T>();
-            if let Some(pool) = pool {
-                pool.get(entity).map(|v| *v)
-            } else {
-                None
+    /// Entity pointed to by the iterator.
+    pub fn entity_from_iter(&self, it: QueryIterator) -> Option<Entity> {
+        let key = match &self.storage {
+            ComponentStorage::Sparse(_) => EntityKey { id: it.id },
+            ComponentStorage::Archetype(archetypes) => {
+                archetypes.archetypes().get(it.archetype?)?.entity(it.id)?
             }
-        } else {
-            None
-        }
+        };
+        self.entities.alive_at_index(key.id)
+    }
+
+    pub fn get_component_from_iter<T: Component + Clone>(&self, it: QueryIterator) -> Option<T> {
+        self.get_component_ref_from_iter(it).cloned()
     }
 
     pub fn get_component_ref_from_iter<T: Component>(&self, it: QueryIterator) -> Option<&T> {
-        let entity = self.entities.at_index(it.id);
-        if let Some(entity) = entity {
-            let pool = self.fetch_pool::<T>();
-            if let Some(pool) = pool {
-                pool.get(entity)
-            } else {
-                None
+        self.get_tracked_from_iter(it).map(|tracked| &tracked.value)
+    }
+
+    pub fn is_component_added_from_iter<T: Component>(&self, it: QueryIterator) -> bool {
+        self.get_tracked_from_iter::<T>(it)
+            .map(|tracked| tracked.ticks.is_added(it.since))
+            .unwrap_or(false)
+    }
+
+    pub fn is_component_changed_from_iter<T: Component>(&self, it: QueryIterator) -> bool {
+        self.get_tracked_from_iter::<T>(it)
+            .map(|tracked| tracked.ticks.is_changed(it.since))
+            .unwrap_or(false)
+    }
+
+    /// Index of the entity of the iterator in the pool, taken from the iterator when the pool is owned by the group
+    /// driving the query, and looked up otherwise.
+    fn position_from_iter<T: Component>(pool: &Pool<T>, it: QueryIterator) -> Option<usize> {
+        let key = EntityKey { id: it.id };
+        match it.position {
+            Some(position) if pool.as_keys_slice().get(position) == Some(&key) => Some(position),
+            _ => pool.position(key),
+        }
+    }
+
+    fn get_tracked_from_iter<T: Component>(&self, it: QueryIterator) -> Option<&Tracked<T>> {
+        match &self.storage {
+            ComponentStorage::Sparse(_) => {
+                let pool = self.fetch_pool::<T>()?;
+                pool.at_index_ref(Self::position_from_iter(pool, it)?)
+            }
+            ComponentStorage::Archetype(archetypes) => archetypes.get(self.location_from_iter(it)?),
+        }
+    }
+
+    /// Fetches a mutable reference to a component through a shared reference to the registry, marking it as changed.
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that no other reference to the component is alive for the lifetime of the returned
+    /// one, which is what the borrow checks of [Registry::query_registry_mut] and [RegistryCell] ensure for queries.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get_component_mut_from_iter<T: Component>(
+        &self,
+        it: QueryIterator,
+    ) -> Option<&mut T> {
+        let tracked = match &self.storage {
+            ComponentStorage::Sparse(_) => {
+                let pool = self.fetch_pool::<T>()?;
+                pool.at_index_unchecked_mut(Self::position_from_iter(pool, it)?)
+            }
+            ComponentStorage::Archetype(archetypes) => {
+                archetypes.get_unchecked_mut(self.location_from_iter(it)?)
+            }
+        };
+
+        tracked.map(|tracked| tracked.value_mut(it.tick))
+    }
+}
+
+/// Packed keys of a single component pool, used to drive the iteration of a query.
+#[derive(Clone, Copy)]
+pub struct PoolKeys<'a> {
+    keys: &'a [EntityKey],
+    /// Whether the keys are the front of the pools of an owning group, so that the index of a key is also the index of
+    /// the entity in every owned pool.
+    grouped: bool,
+}
+
+impl<'a> PoolKeys<'a> {
+    pub(crate) fn grouped(keys: &'a [EntityKey]) -> Self {
+        Self {
+            keys,
+            grouped: true,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Picks the smallest of the provided pools.  A missing pool yields an empty set, as no entity can match the
+    /// query, and providing no pools at all yields `None`, meaning every entity has to be visited.
+    pub fn smallest<I: IntoIterator<Item = Option<PoolKeys<'a>>>>(pools: I) -> Option<Self> {
+        let mut smallest: Option<Self> = None;
+
+        for pool in pools {
+            let pool = pool.unwrap_or(PoolKeys {
+                keys: &[],
+                grouped: false,
+            });
+            if smallest.map(|s| pool.len() < s.len()).unwrap_or(true) {
+                smallest = Some(pool);
+            }
+        }
+
+        smallest
+    }
+}
+
+pub trait RegistryQuery<'r> {
+    type Result;
+    fn contains(it: QueryIterator, reg: &'r Registry) -> bool;
+
+    /// Fetches the components of the query for the entity pointed to by the iterator.
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that none of the components written by the query, as reported by
+    /// [RegistryQuery::access], are otherwise borrowed for `'r`, and that none of the components it reads are mutably
+    /// borrowed for `'r`.
+    unsafe fn fetch(it: QueryIterator, reg: &'r Registry) -> Option<Self::Result>;
+
+    /// Registers the components read and written by the query.
+    fn access(access: &mut Access);
+
+    /// Keys driving the iteration of the query over sparse storage.  Returning `None` visits every entity.
+    fn driver(_reg: &'r Registry) -> Option<PoolKeys<'r>> {
+        None
+    }
+}
+
+/// Marker of queries that never hand out mutable references, and can thus run on a shared [Registry].
+///
+/// # Safety
+///
+/// [RegistryQuery::access] of the query must not report any written component.
+pub unsafe trait ReadOnlyRegistryQuery<'r>: RegistryQuery<'r> {}
+
+pub struct RegistryRefQuery<'a, T: RegistryQuery<'a>> {
+    reg: &'a Registry,
+    index: QueryIterator,
+    driver: Option<PoolKeys<'a>>,
+    position: usize,
+    /// Position, or row of the current archetype, at which a batch of a parallel query stops.  Queries without a
+    /// bound visit every archetype.
+    end: Option<usize>,
+    type_phantom: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: RegistryQuery<'a>> RegistryRefQuery<'a, T> {
+    /// Overrides the tick the added and changed filters of the query compare against, which defaults to
+    /// [Registry::last_tick].
+    pub fn since(mut self, tick: u64) -> Self {
+        self.index.since = tick;
+        self
+    }
+
+    /// Splits the entities left to visit into batches of at most `batch_size` entities, iterated in parallel on the
+    /// current rayon thread pool.  Run the query within [rayon::ThreadPool::install] to use another pool.
+    ///
+    /// Batches never mix entities of different archetypes, and entities are visited once, so the components handed
+    /// out are as disjoint as those of the sequential iteration, which the borrow checks of the query guarantee.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the batch size is zero.
+    pub fn par_iter(self, batch_size: usize) -> impl ParallelIterator<Item = T::Result> + 'a
+    where
+        T: 'a,
+        T::Result: Send,
+    {
+        assert!(batch_size > 0, "Batch size must not be zero.");
+
+        self.batches(batch_size)
+            .into_par_iter()
+            .flat_map_iter(|batch| batch)
+    }
+
+    /// Runs the function on each entity matching the query, in parallel batches of at most `batch_size` entities.
+    /// See [RegistryRefQuery::par_iter].
+    pub fn par_for_each(self, batch_size: usize, f: impl Fn(T::Result) + Send + Sync)
+    where
+        T: 'a,
+        T::Result: Send,
+    {
+        self.par_iter(batch_size).for_each(f);
+    }
+
+    fn batches(&self, batch_size: usize) -> Vec<Self> {
+        let batch = |index: QueryIterator, position: usize, end: usize| Self {
+            reg: self.reg,
+            index,
+            driver: self.driver,
+            position,
+            end: Some(end),
+            type_phantom: PhantomData,
+        };
+
+        match (&self.reg.storage, self.index.archetype) {
+            (ComponentStorage::Archetype(archetypes), Some(current)) => {
+                let mut batches = Vec::new();
+                for (archetype, table) in archetypes.archetypes().iter().enumerate().skip(current) {
+                    let start = if archetype == current {
+                        self.index.id
+                    } else {
+                        0
+                    };
+                    let index = QueryIterator {
+                        id: 0,
+                        archetype: Some(archetype),
+                        position: None,
+                        ..self.index
+                    };
+                    if start >= table.len() || !T::contains(index, self.reg) {
+                        continue;
+                    }
+
+                    batches.extend((start..table.len()).step_by(batch_size).map(|row| {
+                        let index = QueryIterator { id: row, ..index };
+                        batch(index, 0, (row + batch_size).min(table.len()))
+                    }));
+                }
+                batches
+            }
+            _ => {
+                let len = match self.driver {
+                    Some(driver) => driver.len(),
+                    None => self.reg.entities.len(),
+                };
+                (self.position..len)
+                    .step_by(batch_size)
+                    .map(|position| batch(self.index, position, (position + batch_size).min(len)))
+                    .collect()
             }
-        } else {
-            None
         }
     }
 
-    pub fn get_component_mut_from_iter<T: Component>(&self, it: QueryIterator) -> Option<&mut T> {
-        let entity = self.entities.at_index(it.id);
-        if let Some(entity) = entity {
-            let pool = self.fetch_pool::<T>();
-            if let Some(pool) = pool {
-                pool.get_mut(entity)
+    fn next_sparse(&mut self) -> Option<T::Result> {
+        loop {
+            if self.end.map_or(false, |end| self.position >= end) {
+                return None;
+            }
+
+            let (key, position) = match self.driver {
+                Some(driver) => (
+                    *driver.keys.get(self.position)?,
+                    driver.grouped.then_some(self.position),
+                ),
+                None => (
+                    Registry::entity_key(self.reg.entities.at(self.position)?),
+                    None,
+                ),
+            };
+            self.position += 1;
+
+            self.index.id = key.id;
+            self.index.position = position;
+            // The borrows of the query were checked when the iterator was created
+            let result = unsafe { T::fetch(self.index, self.reg) };
+
+            if result.is_some() {
+                return result;
+            }
+        }
+    }
+
+    fn next_archetype(&mut self, archetypes: &ArchetypeStorage) -> Option<T::Result> {
+        let tables = archetypes.archetypes();
+
+        while let Some(archetype) = self.index.archetype {
+            let table = tables.get(archetype)?;
+
+            // Archetypes either match a query for all of their rows or for none of them, so only the first row of a
+            // table needs to be checked.
+            if self.index.id == 0 && !T::contains(self.index, self.reg) {
+                self.index.id = table.len();
+            }
+
+            if self.index.id < self.end.unwrap_or(usize::MAX).min(table.len()) {
+                let result = unsafe { T::fetch(self.index, self.reg) };
+                self.index.id += 1;
+
+                if result.is_some() {
+                    return result;
+                }
+            } else if self.end.is_some() {
+                return None;
             } else {
-                None
+                self.index.id = 0;
+                self.index.archetype = Some(archetype + 1);
             }
-        } else {
-            None
         }
+
+        None
+    }
+}
+
+impl<'a, T: RegistryQuery<'a>> Iterator for RegistryRefQuery<'a, T> {
+    type Item = T::Result;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reg = self.reg;
+        match &reg.storage {
+            ComponentStorage::Sparse(_) => self.next_sparse(),
+            ComponentStorage::Archetype(archetypes) => self.next_archetype(archetypes),
+        }
+    }
+}
+
+impl Registry {
+    pub fn query_registry<'a, T: ReadOnlyRegistryQuery<'a>>(&'a self) -> RegistryRefQuery<'a, T> {
+        self.query_registry_unchecked()
+    }
+
+    /// Queries components of the registry, including mutable ones.  Exclusive access to the registry guarantees that
+    /// no other query is alive, but the query itself may not request the same component mutably twice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a component is both read and written, or written twice, by the query.
+    ///
+    /// ```compile_fail
+    /// # use tempest_ecs::{component::Component, registry::{Registry, RegistryQuery}};
+    /// # #[derive(Component)]
+    /// # struct Position(f32);
+    /// #[derive(RegistryQuery)]
+    /// #[read_write(Position)]
+    /// struct Movement;
+    ///
+    /// let mut reg = Registry::default();
+    /// let mut first = reg.query_registry_mut::<Movement>();
+    /// let mut second = reg.query_registry_mut::<Movement>();
+    /// first.next();
+    /// second.next();
+    /// ```
+    pub fn query_registry_mut<'a, T: RegistryQuery<'a>>(&'a mut self) -> RegistryRefQuery<'a, T> {
+        let mut access = Access::default();
+        T::access(&mut access);
+
+        if let Some(id) = access.find_alias() {
+            panic!("{}", BorrowError::Aliased(id));
+        }
+
+        self.query_registry_unchecked()
+    }
+
+    /// Grants shared access to the registry, where several queries may be alive at once as long as their borrows do
+    /// not conflict.
+    pub fn cell(&mut self) -> RegistryCell<'_> {
+        RegistryCell {
+            borrows: BorrowFlags::new(self.component_id_bound()),
+            reg: self,
+        }
+    }
+
+    fn query_registry_unchecked<'a, T: RegistryQuery<'a>>(&'a self) -> RegistryRefQuery<'a, T> {
+        let (archetype, driver) = match self.storage {
+            ComponentStorage::Sparse(_) => (None, T::driver(self)),
+            ComponentStorage::Archetype(_) => (Some(0), None),
+        };
+
+        RegistryRefQuery {
+            reg: self,
+            index: QueryIterator {
+                id: 0,
+                archetype,
+                since: self.last_tick,
+                tick: self.tick(),
+                position: None,
+            },
+            driver,
+            position: 0,
+            end: None,
+            type_phantom: PhantomData,
+        }
+    }
+
+    /// Packed keys of the pool storing `T`, or `None` if no such pool exists.
+    pub fn pool_keys<T: Component>(&self) -> Option<PoolKeys<'_>> {
+        self.fetch_pool::<T>().map(|pool| PoolKeys {
+            keys: pool.as_keys_slice(),
+            grouped: false,
+        })
+    }
+
+    /// Keys of the owning group driving a query over entities that have at least the components, or `None` if no
+    /// group owns a subset of them.  See [Registry::group].
+    pub fn group_keys(&self, component_ids: &[usize]) -> Option<PoolKeys<'_>> {
+        self.groups.keys(self.pools(), component_ids)
+    }
+}
+
+/// Registry shared between several queries.  Components are borrowed at runtime for as long as the [QueryBorrow]
+/// returned by a query is alive, and conflicting borrows are rejected.
+pub struct RegistryCell<'w> {
+    reg: &'w Registry,
+    borrows: BorrowFlags,
+}
+
+impl<'w> RegistryCell<'w> {
+    /// Borrows the components of the query, failing if any of them conflicts with a borrow that is still alive.
+    pub fn try_query<T: for<'r> RegistryQuery<'r>>(
+        &self,
+    ) -> Result<QueryBorrow<'_, T>, BorrowError> {
+        let mut access = Access::default();
+        T::access(&mut access);
+        self.borrows.acquire(&access)?;
+
+        Ok(QueryBorrow {
+            reg: self.reg,
+            borrows: &self.borrows,
+            access,
+            since: self.reg.last_tick,
+            tick: self.reg.tick(),
+            type_phantom: PhantomData,
+        })
+    }
+
+    /// Borrows the components of the query.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the components conflicts with a borrow that is still alive.
+    pub fn query<T: for<'r> RegistryQuery<'r>>(&self) -> QueryBorrow<'_, T> {
+        match self.try_query() {
+            Ok(query) => query,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.reg.tick()
     }
-}
 
-pub trait RegistryQuery<'r> {
-    type Result;
-    fn contains(it: QueryIterator, reg: &'r Registry) -> bool;
-    fn fetch(it: QueryIterator, reg: &'r Registry) -> Option<Self::Result>;
+    /// Advances the tick of the registry, so that changes made from now on are told apart from earlier ones.
+    pub(crate) fn advance_tick(&self) -> u64 {
+        self.reg.tick.fetch_add(1, Ordering::Relaxed) + 1
+    }
 }
 
-pub struct RegistryRefQuery<'a, T: RegistryQuery<'a>> {
-    reg: &'a Registry,
-    index: QueryIterator,
+/// Components borrowed by a query of a [RegistryCell], released when dropped.
+pub struct QueryBorrow<'c, T> {
+    reg: &'c Registry,
+    borrows: &'c BorrowFlags,
+    access: Access,
+    since: u64,
+    tick: u64,
     type_phantom: PhantomData<T>,
 }
 
-impl<'a, T: RegistryQuery<'a>> Iterator for RegistryRefQuery<'a, T> {
-    type Item = T::Result;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        while self.index.id < self.reg.num_entities() {
-            let result = match T::fetch(self.index, self.reg) {
-                Some(res) => Some(res),
-                None => None,
-            };
-
-            self.index = QueryIterator {
-                id: self.index.id + 1,
-            };
+impl<'c, T> QueryBorrow<'c, T> {
+    /// Overrides the tick the added and changed filters of the query compare against.
+    pub fn since(mut self, tick: u64) -> Self {
+        self.since = tick;
+        self
+    }
 
-            if result.is_some() {
-                return result;
-            }
-        }
+    /// Overrides the tick components reached mutably by the query are marked as changed at, which defaults to the
+    /// tick of the registry when the query was borrowed.
+    pub(crate) fn change_tick(mut self, tick: u64) -> Self {
+        self.tick = tick;
+        self
+    }
 
-        None
+    pub fn iter<'b>(&'b mut self) -> RegistryRefQuery<'b, T>
+    where
+        T: RegistryQuery<'b>,
+    {
+        let mut query = self.reg.query_registry_unchecked().since(self.since);
+        query.index.tick = self.tick;
+        query
     }
 }
 
-impl Registry {
-    pub fn query_registry<'a, T: RegistryQuery<'a>>(&'a self) -> RegistryRefQuery<'a, T> {
-        let init = QueryIterator { id: 0 };
-        return RegistryRefQuery {
-            reg: &self,
-            index: init,
-            type_phantom: PhantomData::default(),
-        };
+impl<'c, T> Drop for QueryBorrow<'c, T> {
+    fn drop(&mut self) {
+        self.borrows.release(&self.access);
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
     use tempest_ecs_macros::{Component, RegistryQuery};
 
     use super::*;
+    use crate::entities::tests::Rng;
 
-    #[derive(Component, Default)]
+    #[derive(Component, Clone, Default)]
     struct TestSuiteComponent(u32);
 
     impl TestSuiteComponent {
@@ -366,7 +1245,7 @@ mod tests {
         }
     }
 
-    #[derive(Component, Default)]
+    #[derive(Component, Clone, Default)]
     struct TestSuiteComponent2(u32);
 
     #[test]
@@ -407,9 +1286,9 @@ mod tests {
         assert_eq!(reg.num_entities(), 1);
         assert!(reg.entity_capacity() >= reg.num_entities());
 
-        assert_ne!(ent.id, ent2.id);
-        assert_eq!(ent.id.index, ent2.id.index);
-        assert_ne!(ent.id.generation, ent2.id.generation);
+        assert_ne!(ent, ent2);
+        assert_eq!(ent.index(), ent2.index());
+        assert_ne!(ent.generation(), ent2.generation());
     }
 
     #[test]
@@ -488,7 +1367,7 @@ mod tests {
     fn test_multi_rw_component_query() {
         let mut reg = Registry::default();
         let mut count = 0;
-        for _ in reg.query_registry::<MutMyTestQuery>() {
+        for _ in reg.query_registry_mut::<MutMyTestQuery>() {
             count += 1;
         }
 
@@ -497,7 +1376,7 @@ mod tests {
         let entity = reg.create_entity();
         reg.assign_component(entity, TestSuiteComponent::new(1));
 
-        for _ in reg.query_registry::<MutMyTestQuery>() {
+        for _ in reg.query_registry_mut::<MutMyTestQuery>() {
             count += 1;
         }
 
@@ -505,7 +1384,7 @@ mod tests {
 
         reg.assign_component(entity, TestSuiteComponent2::default());
 
-        for (comp0, comp1) in reg.query_registry::<MutMyTestQuery>() {
+        for (comp0, comp1) in reg.query_registry_mut::<MutMyTestQuery>() {
             assert_eq!(comp0.0, 1);
             assert_eq!(comp1.0, 0);
             count += 1;
@@ -516,7 +1395,7 @@ mod tests {
 
         reg.destroy_entity(&entity);
 
-        for _ in reg.query_registry::<MutMyTestQuery>() {
+        for _ in reg.query_registry_mut::<MutMyTestQuery>() {
             count += 1;
         }
 
@@ -532,7 +1411,7 @@ mod tests {
     fn test_multi_mixed_rw_component_query() {
         let mut reg = Registry::default();
         let mut count = 0;
-        for _ in reg.query_registry::<MixedMutMyTestQuery>() {
+        for _ in reg.query_registry_mut::<MixedMutMyTestQuery>() {
             count += 1;
         }
 
@@ -541,7 +1420,7 @@ mod tests {
         let entity = reg.create_entity();
         reg.assign_component(entity, TestSuiteComponent::new(1));
 
-        for _ in reg.query_registry::<MixedMutMyTestQuery>() {
+        for _ in reg.query_registry_mut::<MixedMutMyTestQuery>() {
             count += 1;
         }
 
@@ -549,7 +1428,7 @@ mod tests {
 
         reg.assign_component(entity, TestSuiteComponent2::default());
 
-        for (comp0, comp1) in reg.query_registry::<MixedMutMyTestQuery>() {
+        for (comp0, comp1) in reg.query_registry_mut::<MixedMutMyTestQuery>() {
             assert_eq!(comp0.0, 1);
             assert_eq!(comp1.0, 0);
             count += 1;
@@ -560,10 +1439,801 @@ mod tests {
 
         reg.destroy_entity(&entity);
 
-        for _ in reg.query_registry::<MixedMutMyTestQuery>() {
+        for _ in reg.query_registry_mut::<MixedMutMyTestQuery>() {
             count += 1;
         }
 
         assert_eq!(count, 0);
     }
+
+    #[test]
+    fn test_query_driven_by_smallest_pool() {
+        let mut reg = Registry::default();
+        assert_eq!(MyTestQuery::driver(&reg).map(|keys| keys.len()), Some(0));
+
+        let mut rare = Vec::new();
+        for i in 0..100 {
+            let ent = reg.create_entity();
+            reg.assign_component(ent, TestSuiteComponent::new(i));
+            if i % 25 == 0 {
+                reg.assign_component(ent, TestSuiteComponent2(i));
+                rare.push(i);
+            }
+        }
+
+        let driver = MyTestQuery::driver(&reg).unwrap();
+        assert_eq!(driver.len(), rare.len());
+
+        let mut seen: Vec<u32> = reg
+            .query_registry::<MyTestQuery>()
+            .map(|(comp0, comp1)| {
+                assert_eq!(comp0.0, comp1.0);
+                comp0.0
+            })
+            .collect();
+        seen.sort();
+        assert_eq!(seen, rare);
+    }
+
+    #[derive(Component)]
+    struct Static;
+
+    #[derive(Component)]
+    struct Dynamic;
+
+    #[derive(RegistryQuery)]
+    #[read_only(TestSuiteComponent)]
+    #[without(Static)]
+    struct NonStaticQuery;
+
+    #[derive(RegistryQuery)]
+    #[read_only(TestSuiteComponent)]
+    #[with(Static)]
+    struct StaticQuery;
+
+    #[derive(RegistryQuery)]
+    #[read_write(TestSuiteComponent)]
+    #[optional(TestSuiteComponent2)]
+    struct OptionalQuery;
+
+    #[derive(RegistryQuery)]
+    #[read_only(TestSuiteComponent)]
+    #[any_of(Static, Dynamic)]
+    struct AnyOfQuery;
+
+    #[derive(RegistryQuery)]
+    #[optional(TestSuiteComponent2)]
+    #[without(Dynamic)]
+    struct OnlyFiltersQuery;
+
+    fn query_filters(kind: StorageKind) {
+        let mut reg = Registry::new(kind);
+
+        let plain = reg.create_entity();
+        reg.assign_component(plain, TestSuiteComponent::new(1));
+
+        let fixed = reg.create_entity();
+        reg.assign_component(fixed, TestSuiteComponent::new(2));
+        reg.assign_component(fixed, Static);
+
+        let moving = reg.create_entity();
+        reg.assign_component(moving, TestSuiteComponent::new(3));
+        reg.assign_component(moving, TestSuiteComponent2(30));
+        reg.assign_component(moving, Dynamic);
+
+        let sorted = |mut values: Vec<u32>| {
+            values.sort();
+            values
+        };
+
+        let non_static = reg.query_registry::<NonStaticQuery>().map(|c| c.0);
+        assert_eq!(sorted(non_static.collect()), vec![1, 3]);
+
+        let statics = reg.query_registry::<StaticQuery>().map(|c| c.0);
+        assert_eq!(sorted(statics.collect()), vec![2]);
+
+        let any_of = reg.query_registry::<AnyOfQuery>().map(|c| c.0);
+        assert_eq!(sorted(any_of.collect()), vec![2, 3]);
+
+        let mut optional = 0;
+        for (comp, comp2) in reg.query_registry_mut::<OptionalQuery>() {
+            comp.0 += 10;
+            match comp2 {
+                Some(comp2) => assert_eq!(comp2.0, 30),
+                None => assert_ne!(comp.0, 13),
+            }
+            optional += 1;
+        }
+        assert_eq!(optional, 3);
+        assert_eq!(
+            reg.get_component::<TestSuiteComponent>(moving).map(|c| c.0),
+            Some(13)
+        );
+
+        // a query without any required component visits every entity that passes its filters
+        assert_eq!(reg.query_registry::<OnlyFiltersQuery>().count(), 2);
+        assert!(reg
+            .query_registry::<OnlyFiltersQuery>()
+            .all(|comp2| comp2.is_none()));
+    }
+
+    #[test]
+    fn test_query_filters() {
+        query_filters(StorageKind::Sparse);
+        query_filters(StorageKind::Archetype);
+    }
+
+    fn parallel_queries(kind: StorageKind) {
+        let mut reg = Registry::new(kind);
+        for i in 0..50_000 {
+            let ent = reg.create_entity();
+            reg.assign_component(ent, TestSuiteComponent::new(i));
+            match i % 3 {
+                0 => {
+                    reg.assign_component(ent, TestSuiteComponent2(1));
+                }
+                1 => {
+                    reg.assign_component(ent, TestSuiteComponent2(2));
+                    reg.assign_component(ent, Static);
+                }
+                _ => {}
+            }
+        }
+        reg.increment_tick();
+
+        let expected: u64 = reg
+            .query_registry::<MyTestQuery>()
+            .map(|(comp, comp2)| (comp.0 + comp2.0) as u64)
+            .sum();
+        for batch_size in [1, 7, 4096, 100_000] {
+            let sum: u64 = reg
+                .query_registry::<MyTestQuery>()
+                .par_iter(batch_size)
+                .map(|(comp, comp2)| (comp.0 + comp2.0) as u64)
+                .sum();
+            assert_eq!(sum, expected);
+        }
+
+        reg.query_registry_mut::<MixedMutMyTestQuery>()
+            .par_for_each(512, |(comp, comp2)| comp2.0 += comp.0);
+        assert_eq!(
+            reg.query_registry::<ChangedQuery>().count(),
+            reg.query_registry::<MyTestQuery>().count()
+        );
+        for (comp, comp2) in reg.query_registry::<MyTestQuery>() {
+            assert_eq!(comp2.0, comp.0 + 1 + (comp.0 % 3));
+        }
+
+        // batches resume from where the sequential iteration stopped
+        let mut query = reg.query_registry::<MyTestQuery>();
+        let first: Vec<u32> = query.by_ref().take(10).map(|(comp, _)| comp.0).collect();
+        let rest: Vec<u32> = query.par_iter(3).map(|(comp, _)| comp.0).collect();
+        assert_eq!(first.len() + rest.len(), 33_334);
+        assert!(first.iter().all(|value| !rest.contains(value)));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_parallel_queries() {
+        parallel_queries(StorageKind::Sparse);
+        parallel_queries(StorageKind::Archetype);
+    }
+
+    #[test]
+    fn test_archetype_assign_component() {
+        let mut reg = Registry::new(StorageKind::Archetype);
+        assert_eq!(reg.storage_kind(), StorageKind::Archetype);
+
+        let ent = reg.create_entity();
+        assert!(reg.assign_component(ent, TestSuiteComponent::new(7)));
+        assert!(reg.assign_component(ent, TestSuiteComponent2(3)));
+        assert!(reg.has_component::<TestSuiteComponent>(ent));
+        assert!(reg.has_component::<TestSuiteComponent2>(ent));
+
+        assert_eq!(
+            reg.remove_component::<TestSuiteComponent>(ent).map(|c| c.0),
+            Some(7)
+        );
+        assert!(!reg.has_component::<TestSuiteComponent>(ent));
+        assert_eq!(
+            reg.get_component::<TestSuiteComponent2>(ent).map(|c| c.0),
+            Some(3)
+        );
+
+        assert!(reg.destroy_entity(&ent));
+        assert!(!reg.assign_component(ent, TestSuiteComponent::default()));
+    }
+
+    #[test]
+    fn test_archetype_rows_survive_swap_remove() {
+        let mut reg = Registry::new(StorageKind::Archetype);
+        let entities: Vec<Entity> = (0..4).map(|_| reg.create_entity()).collect();
+
+        for (i, ent) in entities.iter().enumerate() {
+            reg.assign_component(*ent, TestSuiteComponent::new(i as u32));
+        }
+
+        // moving the first entity to a new archetype swaps the last row into its place
+        reg.assign_component(entities[0], TestSuiteComponent2(10));
+
+        for (i, ent) in entities.iter().enumerate() {
+            assert_eq!(
+                reg.get_component::<TestSuiteComponent>(*ent).map(|c| c.0),
+                Some(i as u32)
+            );
+        }
+
+        reg.remove_component::<TestSuiteComponent>(entities[3]);
+        assert_eq!(
+            reg.get_component::<TestSuiteComponent>(entities[1])
+                .map(|c| c.0),
+            Some(1)
+        );
+        assert_eq!(
+            reg.get_component::<TestSuiteComponent>(entities[2])
+                .map(|c| c.0),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_archetype_multi_component_query() {
+        let mut reg = Registry::new(StorageKind::Archetype);
+        assert_eq!(reg.query_registry::<MyTestQuery>().count(), 0);
+
+        let only_first = reg.create_entity();
+        reg.assign_component(only_first, TestSuiteComponent::new(1));
+
+        let both = reg.create_entity();
+        reg.assign_component(both, TestSuiteComponent2(5));
+        reg.assign_component(both, TestSuiteComponent::new(2));
+
+        let mut count = 0;
+        for (comp0, comp1) in reg.query_registry::<MyTestQuery>() {
+            assert_eq!(comp0.0, 2);
+            assert_eq!(comp1.0, 5);
+            count += 1;
+        }
+        assert_eq!(count, 1);
+
+        for (_, comp1) in reg.query_registry_mut::<MixedMutMyTestQuery>() {
+            comp1.0 += 1;
+        }
+        assert_eq!(
+            reg.get_component::<TestSuiteComponent2>(both).map(|c| c.0),
+            Some(6)
+        );
+
+        reg.assign_component(only_first, TestSuiteComponent2::default());
+        assert_eq!(reg.query_registry::<MyTestQuery>().count(), 2);
+
+        reg.destroy_entity(&both);
+        assert_eq!(reg.query_registry::<MyTestQuery>().count(), 1);
+    }
+
+    fn populated_registry(kind: StorageKind) -> Registry {
+        let mut reg = Registry::new(kind);
+        for i in 0..4 {
+            let ent = reg.create_entity();
+            reg.assign_component(ent, TestSuiteComponent::new(i));
+            reg.assign_component(ent, TestSuiteComponent2(i * 10));
+        }
+        reg
+    }
+
+    #[test]
+    fn test_cell_shared_borrows() {
+        for kind in [StorageKind::Sparse, StorageKind::Archetype] {
+            let mut reg = populated_registry(kind);
+            let cell = reg.cell();
+
+            let mut first = cell.query::<MyTestQuery>();
+            let mut second = cell.query::<StaticQuery>();
+            let mut third = cell.query::<MyTestQuery>();
+
+            let pairs = first.iter().zip(third.iter());
+            for ((lhs, _), (rhs, _)) in pairs {
+                assert_eq!(lhs.0, rhs.0);
+            }
+            assert_eq!(second.iter().count(), 0);
+        }
+    }
+
+    #[test]
+    fn test_cell_conflicting_borrows() {
+        for kind in [StorageKind::Sparse, StorageKind::Archetype] {
+            let mut reg = populated_registry(kind);
+            let id = TestSuiteComponent2::id();
+            let cell = reg.cell();
+
+            let mut writer = cell.query::<MixedMutMyTestQuery>();
+            assert_eq!(
+                cell.try_query::<MyTestQuery>().err(),
+                Some(BorrowError::AlreadyMutablyBorrowed(id))
+            );
+            assert_eq!(
+                cell.try_query::<MutMyTestQuery>().err(),
+                Some(BorrowError::AlreadyBorrowed(TestSuiteComponent::id()))
+            );
+
+            // reading the component that is only read by the writer stays possible
+            let mut reader = cell.query::<NonStaticQuery>();
+
+            for ((comp0, comp1), comp2) in writer.iter().zip(reader.iter()) {
+                comp1.0 += comp0.0;
+                assert_eq!(comp0.0, comp2.0);
+            }
+
+            drop(writer);
+            assert_eq!(
+                cell.try_query::<MutMyTestQuery>().err(),
+                Some(BorrowError::AlreadyBorrowed(TestSuiteComponent::id()))
+            );
+
+            drop(reader);
+            let mut writer = cell.query::<MutMyTestQuery>();
+            for (comp0, comp1) in writer.iter() {
+                assert_eq!(comp1.0, comp0.0 * 11);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cell_conflict_panics() {
+        let mut reg = populated_registry(StorageKind::Sparse);
+        let cell = reg.cell();
+
+        let _writer = cell.query::<OptionalQuery>();
+        let _reader = cell.query::<MyTestQuery>();
+    }
+
+    /// Hand written query that hands out the same component twice, which the derive refuses to generate.
+    struct AliasedQuery;
+
+    impl<'r> RegistryQuery<'r> for AliasedQuery {
+        type Result = (&'r mut TestSuiteComponent, &'r mut TestSuiteComponent);
+
+        fn contains(it: QueryIterator, reg: &'r Registry) -> bool {
+            reg.contains_component_from_iter::<TestSuiteComponent>(it)
+        }
+
+        unsafe fn fetch(it: QueryIterator, reg: &'r Registry) -> Option<Self::Result> {
+            Some((
+                reg.get_component_mut_from_iter(it)?,
+                reg.get_component_mut_from_iter(it)?,
+            ))
+        }
+
+        fn access(access: &mut Access) {
+            access
+                .write(TestSuiteComponent::id())
+                .write(TestSuiteComponent::id());
+        }
+    }
+
+    #[test]
+    fn test_aliased_query_is_rejected() {
+        let mut reg = populated_registry(StorageKind::Sparse);
+        assert_eq!(
+            reg.cell().try_query::<AliasedQuery>().err(),
+            Some(BorrowError::Aliased(TestSuiteComponent::id()))
+        );
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            reg.query_registry_mut::<AliasedQuery>().count()
+        }));
+        assert!(result.is_err());
+    }
+
+    fn cell_overlapping_references(kind: StorageKind) {
+        let mut reg = populated_registry(kind);
+        let cell = reg.cell();
+
+        // every reference handed out by both queries is alive at once
+        let mut writer = cell.query::<MixedMutMyTestQuery>();
+        let mut reader = cell.query::<NonStaticQuery>();
+        let written: Vec<_> = writer.iter().collect();
+        let read: Vec<_> = reader.iter().collect();
+
+        for ((comp0, comp1), comp2) in written.into_iter().zip(read) {
+            comp1.0 += comp0.0 + comp2.0;
+        }
+
+        drop((writer, reader));
+        for (comp0, comp1) in cell.query::<MyTestQuery>().iter() {
+            assert_eq!(comp1.0, comp0.0 * 12);
+        }
+    }
+
+    #[test]
+    fn test_cell_overlapping_references() {
+        cell_overlapping_references(StorageKind::Sparse);
+        cell_overlapping_references(StorageKind::Archetype);
+    }
+
+    fn parallel_mutable_query(kind: StorageKind) {
+        let mut reg = populated_registry(kind);
+
+        // batches of a single entity hand out references to neighbouring rows from different threads
+        reg.query_registry_mut::<MixedMutMyTestQuery>()
+            .par_for_each(1, |(comp0, comp1)| comp1.0 += comp0.0);
+
+        let written: Vec<_> = reg
+            .query_registry_mut::<MutMyTestQuery>()
+            .par_iter(3)
+            .collect();
+        for (comp0, comp1) in written {
+            comp0.0 = comp1.0;
+        }
+
+        for (comp0, comp1) in reg.query_registry::<MyTestQuery>() {
+            assert_eq!(comp0.0, comp1.0);
+            assert_eq!(comp1.0 % 11, 0);
+        }
+    }
+
+    #[test]
+    fn test_parallel_mutable_query() {
+        parallel_mutable_query(StorageKind::Sparse);
+        parallel_mutable_query(StorageKind::Archetype);
+    }
+
+    #[derive(RegistryQuery)]
+    #[read_only(TestSuiteComponent)]
+    #[added(TestSuiteComponent)]
+    struct AddedQuery;
+
+    #[derive(RegistryQuery)]
+    #[read_only(TestSuiteComponent)]
+    #[changed(TestSuiteComponent2)]
+    struct ChangedQuery;
+
+    #[derive(RegistryQuery)]
+    #[read_write(TestSuiteComponent2)]
+    #[changed(TestSuiteComponent2)]
+    struct MutChangedQuery;
+
+    fn change_detection(kind: StorageKind) {
+        let mut reg = populated_registry(kind);
+        let first_tick = reg.tick();
+
+        let added = reg.query_registry::<AddedQuery>().map(|c| c.0);
+        assert_eq!(added.count(), 4);
+        assert_eq!(reg.query_registry::<ChangedQuery>().count(), 4);
+
+        reg.increment_tick();
+        assert_eq!(reg.query_registry::<AddedQuery>().count(), 0);
+        assert_eq!(reg.query_registry::<ChangedQuery>().count(), 0);
+
+        let late = reg.create_entity();
+        reg.assign_component(late, TestSuiteComponent::new(100));
+        reg.assign_component(late, TestSuiteComponent2(0));
+
+        let added: Vec<u32> = reg.query_registry::<AddedQuery>().map(|c| c.0).collect();
+        assert_eq!(added, vec![100]);
+
+        // reading a component does not mark it, reaching it mutably does
+        reg.increment_tick();
+        assert_eq!(reg.query_registry::<MyTestQuery>().count(), 5);
+        assert_eq!(reg.query_registry::<ChangedQuery>().count(), 0);
+
+        reg.get_component_mut::<TestSuiteComponent2>(late)
+            .unwrap()
+            .0 += 1;
+        let changed: Vec<u32> = reg.query_registry::<ChangedQuery>().map(|c| c.0).collect();
+        assert_eq!(changed, vec![100]);
+
+        let ticks = reg
+            .get_component_ticks::<TestSuiteComponent2>(late)
+            .unwrap();
+        assert_eq!(ticks.added(), first_tick + 1);
+        assert_eq!(ticks.changed(), reg.tick());
+
+        // the tick of a component survives structural changes of its entity
+        reg.assign_component(late, Static);
+        assert_eq!(
+            reg.get_component_ticks::<TestSuiteComponent2>(late),
+            Some(ticks)
+        );
+
+        reg.increment_tick();
+        for comp in reg.query_registry_mut::<MutMyTestQuery>() {
+            comp.1 .0 += 1;
+        }
+        assert_eq!(reg.query_registry::<ChangedQuery>().count(), 5);
+        assert_eq!(reg.query_registry_mut::<MutChangedQuery>().count(), 5);
+
+        // an older tick also reports the changes made since then
+        reg.increment_tick();
+        assert_eq!(reg.query_registry::<ChangedQuery>().count(), 0);
+        let since_first = reg.query_registry::<AddedQuery>().since(first_tick);
+        assert_eq!(since_first.map(|c| c.0).collect::<Vec<_>>(), vec![100]);
+    }
+
+    #[test]
+    fn test_change_detection() {
+        change_detection(StorageKind::Sparse);
+        change_detection(StorageKind::Archetype);
+    }
+
+    #[derive(Component, Clone)]
+    struct Name(String);
+
+    #[derive(Component)]
+    struct Waypoints(Vec<(f32, f32)>);
+
+    trait Behavior: Send + Sync {
+        fn speed(&self) -> f32;
+    }
+
+    struct Walk;
+
+    impl Behavior for Walk {
+        fn speed(&self) -> f32 {
+            1.5
+        }
+    }
+
+    #[derive(Component)]
+    struct Brain(Box<dyn Behavior>);
+
+    #[derive(Component)]
+    struct Tracked(#[allow(dead_code)] Arc<()>);
+
+    fn owned_components(kind: StorageKind) {
+        let mut reg = Registry::new(kind);
+        let ent = reg.create_entity();
+
+        reg.assign_component(ent, Name("player".to_string()));
+        reg.assign_component(ent, Waypoints(vec![(0.0, 0.0), (1.0, 2.0)]));
+        reg.assign_component(ent, Brain(Box::new(Walk)));
+
+        assert_eq!(reg.get_component::<Name>(ent).unwrap().0, "player");
+        assert_eq!(reg.get_component_ref::<Waypoints>(ent).unwrap().0.len(), 2);
+        assert_eq!(reg.get_component_ref::<Brain>(ent).unwrap().0.speed(), 1.5);
+
+        reg.get_component_mut::<Waypoints>(ent)
+            .unwrap()
+            .0
+            .push((3.0, 4.0));
+        assert_eq!(reg.get_component_ref::<Waypoints>(ent).unwrap().0.len(), 3);
+
+        let removed = reg.remove_component::<Name>(ent);
+        assert_eq!(removed.map(|n| n.0), Some("player".to_string()));
+        assert_eq!(reg.get_component_ref::<Brain>(ent).unwrap().0.speed(), 1.5);
+    }
+
+    fn owned_component_drops(kind: StorageKind) {
+        let tracker = Arc::new(());
+        let mut reg = Registry::new(kind);
+
+        let first = reg.create_entity();
+        let second = reg.create_entity();
+        reg.assign_component(first, Tracked(tracker.clone()));
+        reg.assign_component(second, Tracked(tracker.clone()));
+        reg.assign_component(second, Name("second".to_string()));
+        assert_eq!(Arc::strong_count(&tracker), 3);
+
+        drop(reg.remove_component::<Tracked>(first));
+        assert_eq!(Arc::strong_count(&tracker), 2);
+
+        reg.destroy_entity(&second);
+        assert_eq!(Arc::strong_count(&tracker), 1);
+
+        let third = reg.create_entity();
+        reg.assign_component(third, Tracked(tracker.clone()));
+        assert_eq!(Arc::strong_count(&tracker), 2);
+
+        drop(reg);
+        assert_eq!(Arc::strong_count(&tracker), 1);
+    }
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    #[component(page_size = 64)]
+    struct Debris(u32);
+
+    fn shrink_to_fit(kind: StorageKind) {
+        let mut reg = Registry::new(kind);
+        let level: Vec<Entity> = (0..4096).map(|_| reg.create_entity()).collect();
+        for (i, ent) in level.iter().enumerate() {
+            reg.assign_component(*ent, Debris(i as u32));
+            reg.assign_component(*ent, TestSuiteComponent(i as u32));
+        }
+        if kind == StorageKind::Sparse {
+            assert_eq!(reg.fetch_pool::<Debris>().unwrap().page_size(), 64);
+            assert_eq!(reg.fetch_pool::<Debris>().unwrap().page_count(), 64);
+        }
+
+        // unload the level, keeping a handful of entities
+        for ent in level.iter().skip(8) {
+            reg.destroy_entity(ent);
+        }
+        for ent in level.iter().take(8) {
+            reg.remove_component::<TestSuiteComponent>(*ent);
+        }
+        reg.shrink_to_fit();
+
+        if kind == StorageKind::Sparse {
+            let pool = reg.fetch_pool::<Debris>().unwrap();
+            assert_eq!((pool.page_count(), pool.capacity()), (1, 8));
+            assert!(reg.fetch_pool::<TestSuiteComponent>().is_none());
+        }
+        for (i, ent) in level.iter().take(8).enumerate() {
+            assert_eq!(reg.get_component::<Debris>(*ent), Some(Debris(i as u32)));
+        }
+
+        // storage grows back as entities are created again
+        let ent = reg.create_entity();
+        reg.assign_component(ent, TestSuiteComponent(7));
+        reg.assign_component(ent, Debris(9));
+        assert_eq!(reg.get_component::<Debris>(ent), Some(Debris(9)));
+        assert_eq!(
+            reg.get_component_ref::<TestSuiteComponent>(ent).unwrap().0,
+            7
+        );
+        assert_eq!(reg.num_entities(), 9);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_shrink_to_fit() {
+        shrink_to_fit(StorageKind::Sparse);
+        shrink_to_fit(StorageKind::Archetype);
+    }
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Layer(u32);
+
+    #[derive(RegistryQuery)]
+    #[read_only(Layer)]
+    struct LayerQuery;
+
+    fn sort(kind: StorageKind) {
+        let mut reg = Registry::new(kind);
+        let mut rng = Rng(7);
+        let sprites: Vec<(Entity, u32)> = (0..500)
+            .map(|_| {
+                let ent = reg.create_entity();
+                let layer = rng.below(8) as u32;
+                reg.assign_component(ent, Layer(layer));
+                (ent, layer)
+            })
+            .collect();
+        for (ent, _) in sprites.iter().step_by(7) {
+            reg.assign_component(*ent, TestSuiteComponent(0));
+        }
+
+        reg.sort::<Layer>(|a, b| a.0.cmp(&b.0));
+        let layers: Vec<u32> = reg
+            .query_registry::<LayerQuery>()
+            .map(|layer| layer.0)
+            .collect();
+        assert_eq!(layers.len(), 500);
+        match kind {
+            StorageKind::Sparse => assert!(layers.windows(2).all(|pair| pair[0] <= pair[1])),
+            // each archetype is sorted on its own
+            StorageKind::Archetype => {
+                assert_eq!(
+                    layers.windows(2).filter(|pair| pair[0] > pair[1]).count(),
+                    1
+                )
+            }
+        }
+        for (ent, layer) in sprites.iter() {
+            assert_eq!(reg.get_component::<Layer>(*ent), Some(Layer(*layer)));
+        }
+
+        reg.destroy_entity(&sprites[3].0);
+        assert_eq!(reg.query_registry::<LayerQuery>().count(), 499);
+    }
+
+    #[test]
+    fn test_sort() {
+        sort(StorageKind::Sparse);
+        sort(StorageKind::Archetype);
+    }
+
+    #[test]
+    fn test_owned_components() {
+        owned_components(StorageKind::Sparse);
+        owned_components(StorageKind::Archetype);
+    }
+
+    #[test]
+    fn test_owned_component_drops() {
+        owned_component_drops(StorageKind::Sparse);
+        owned_component_drops(StorageKind::Archetype);
+    }
+
+    fn model(kind: StorageKind, seed: u64) {
+        let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let mut reg = Registry::new(kind);
+        let mut model: HashMap<Entity, (Option<u32>, Option<u32>)> = HashMap::new();
+        let mut handles: Vec<Entity> = Vec::new();
+
+        for _ in 0..1000 {
+            // Operations target stale handles as often as alive ones
+            let ent = match handles.is_empty() {
+                true => Entity::DANGLING,
+                false => handles[rng.below(handles.len())],
+            };
+            let value = rng.next() as u32;
+            let alive = model.contains_key(&ent);
+
+            match rng.below(6) {
+                0 => {
+                    let ent = reg.create_entity();
+                    assert!(model.insert(ent, (None, None)).is_none());
+                    handles.push(ent);
+                }
+                1 => {
+                    assert_eq!(reg.destroy_entity(&ent), alive);
+                    model.remove(&ent);
+                }
+                2 => {
+                    assert_eq!(reg.assign_component(ent, TestSuiteComponent(value)), alive);
+                    if let Some((first, _)) = model.get_mut(&ent) {
+                        *first = Some(value);
+                    }
+                }
+                3 => {
+                    assert_eq!(reg.assign_component(ent, TestSuiteComponent2(value)), alive);
+                    if let Some((_, second)) = model.get_mut(&ent) {
+                        *second = Some(value);
+                    }
+                }
+                4 => {
+                    let expected = model.get_mut(&ent).and_then(|(first, _)| first.take());
+                    assert_eq!(
+                        reg.remove_component::<TestSuiteComponent>(ent).map(|c| c.0),
+                        expected
+                    );
+                }
+                _ => {
+                    let expected = model.get_mut(&ent).and_then(|(_, second)| second.as_mut());
+                    let actual = reg.get_component_mut::<TestSuiteComponent2>(ent);
+                    assert_eq!(actual.as_ref().map(|c| c.0), expected.as_ref().map(|v| **v));
+                    if let (Some(actual), Some(expected)) = (actual, expected) {
+                        actual.0 = value;
+                        *expected = value;
+                    }
+                }
+            }
+
+            assert_eq!(reg.num_entities(), model.len());
+        }
+
+        for ent in &handles {
+            let expected = model.get(ent);
+            assert_eq!(reg.is_alive(*ent), expected.is_some());
+            assert_eq!(
+                reg.get_component::<TestSuiteComponent>(*ent).map(|c| c.0),
+                expected.and_then(|(first, _)| *first)
+            );
+            assert_eq!(
+                reg.get_component::<TestSuiteComponent2>(*ent).map(|c| c.0),
+                expected.and_then(|(_, second)| *second)
+            );
+            assert_eq!(
+                reg.has_component::<TestSuiteComponent2>(*ent),
+                expected.map_or(false, |(_, second)| second.is_some())
+            );
+        }
+
+        let mut alive: Vec<Entity> = reg.iter_entities().collect();
+        alive.sort_unstable();
+        let mut expected: Vec<Entity> = model.keys().copied().collect();
+        expected.sort_unstable();
+        assert_eq!(alive, expected);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_model() {
+        for seed in 1..=8 {
+            model(StorageKind::Sparse, seed);
+            model(StorageKind::Archetype, seed);
+        }
+    }
 }
@@ -0,0 +1,272 @@
+use std::{any::TypeId, collections::BTreeMap, ops::Deref, sync::RwLock};
+
+use super::{
+    component::{component_id, Component},
+    registry::{Entity, Registry},
+};
+
+/// Kind of relationship between two entities, such as `Targets` or `DockedAt`.  The value of the kind is the data of
+/// the pair, so unit structs make relations without data.
+pub trait Relation: Send + Sync + 'static {}
+
+impl<T: Send + Sync + 'static> Relation for T {}
+
+static RELATION_IDS: RwLock<BTreeMap<TypeId, usize>> = RwLock::new(BTreeMap::new());
+
+/// Same as [component_id], but memoizes the identifiers of the components of each relation kind in a map of their
+/// own, which is only written once per kind, as generic components cannot hold a static of their own.
+fn relation_component_id<T: 'static>() -> usize {
+    if let Some(id) = RELATION_IDS.read().unwrap().get(&TypeId::of::<T>()) {
+        return *id;
+    }
+
+    let id = component_id::<T>();
+    RELATION_IDS.write().unwrap().insert(TypeId::of::<T>(), id);
+    id
+}
+
+/// Pairs of kind `R` from an entity, as targets along with the data of the pair, in the order they were added.
+/// Maintained by [Registry::add_relation] and [Registry::remove_relation].
+pub struct Relations<R>(Vec<(Entity, R)>);
+
+impl<R: Relation> Component for Relations<R> {
+    fn id() -> usize {
+        relation_component_id::<Self>()
+    }
+}
+
+impl<R> Relations<R> {
+    pub fn get(&self, target: Entity) -> Option<&R> {
+        self.0
+            .iter()
+            .find(|(other, _)| *other == target)
+            .map(|(_, data)| data)
+    }
+
+    pub fn targets(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.iter().map(|(target, _)| *target)
+    }
+}
+
+impl<R> Deref for Relations<R> {
+    type Target = [(Entity, R)];
+
+    fn deref(&self) -> &[(Entity, R)] {
+        &self.0
+    }
+}
+
+/// Entities with a pair of kind `R` to an entity, so that pairs can be looked up and cleaned up from their target.
+struct Sources<R>(Vec<Entity>, std::marker::PhantomData<fn() -> R>);
+
+impl<R: Relation> Component for Sources<R> {
+    fn id() -> usize {
+        relation_component_id::<Self>()
+    }
+
+    fn cloner() -> Option<fn(&Self) -> Self> {
+        Some(|sources| Sources(sources.0.clone(), Default::default()))
+    }
+}
+
+/// Removal of the pairs of a relation kind from and to an entity about to be destroyed.
+pub(crate) type RelationCleanup = fn(&mut Registry, Entity);
+
+/// Removes every pair of kind `R` from and to an entity about to be destroyed.
+fn clear_relations<R: Relation>(reg: &mut Registry, ent: Entity) {
+    let targets: Vec<Entity> = reg
+        .get_component_ref::<Relations<R>>(ent)
+        .map(|relations| relations.targets().collect())
+        .unwrap_or_default();
+    for target in targets {
+        reg.remove_relation::<R>(ent, target);
+    }
+
+    let sources = reg
+        .get_component_ref::<Sources<R>>(ent)
+        .map(|sources| sources.0.clone())
+        .unwrap_or_default();
+    for source in sources {
+        reg.remove_relation::<R>(source, ent);
+    }
+}
+
+impl Registry {
+    /// Allows the pairs of kind `R` to be cloned into snapshots of this registry.  Taking a snapshot of a registry
+    /// holding pairs of a kind that was not made cloneable fails, as relation kinds are not required to implement
+    /// [Clone].
+    pub fn cloneable_relation<R: Relation + Clone>(&mut self) {
+        self.set_cloner::<Relations<R>>(|relations| Relations(relations.0.clone()));
+    }
+
+    /// Adds a pair of kind `R` from the source to the target, replacing the data of the pair if it already exists.
+    /// The pair is removed once either entity is destroyed.
+    ///
+    /// Returns false if either entity is not alive.
+    pub fn add_relation<R: Relation>(
+        &mut self,
+        source: Entity,
+        relation: R,
+        target: Entity,
+    ) -> bool {
+        if !self.is_alive(source) || !self.is_alive(target) {
+            return false;
+        }
+
+        let id = Relations::<R>::id();
+        let cleanups = self.relation_cleanups_mut();
+        if !cleanups.iter().any(|(other, _)| *other == id) {
+            cleanups.push((id, clear_relations::<R>));
+        }
+
+        if let Some(data) = self.relation_mut::<R>(source, target) {
+            *data = relation;
+            return true;
+        }
+
+        match self.get_component_mut::<Relations<R>>(source) {
+            Some(relations) => relations.0.push((target, relation)),
+            None => {
+                self.assign_component(source, Relations(vec![(target, relation)]));
+            }
+        }
+        match self.get_component_mut::<Sources<R>>(target) {
+            Some(sources) => sources.0.push(source),
+            None => {
+                self.assign_component(target, Sources::<R>(vec![source], Default::default()));
+            }
+        }
+
+        true
+    }
+
+    /// Removes the pair of kind `R` from the source to the target, returning its data.
+    pub fn remove_relation<R: Relation>(&mut self, source: Entity, target: Entity) -> Option<R> {
+        let relations = self.get_component_mut::<Relations<R>>(source)?;
+        let position = relations.0.iter().position(|(other, _)| *other == target)?;
+        let (_, data) = relations.0.remove(position);
+        if relations.is_empty() {
+            self.remove_component::<Relations<R>>(source);
+        }
+
+        if let Some(sources) = self.get_component_mut::<Sources<R>>(target) {
+            sources.0.retain(|ent| *ent != source);
+            if sources.0.is_empty() {
+                self.remove_component::<Sources<R>>(target);
+            }
+        }
+
+        Some(data)
+    }
+
+    pub fn has_relation<R: Relation>(&self, source: Entity, target: Entity) -> bool {
+        self.relation::<R>(source, target).is_some()
+    }
+
+    /// Data of the pair of kind `R` from the source to the target.
+    pub fn relation<R: Relation>(&self, source: Entity, target: Entity) -> Option<&R> {
+        self.get_component_ref::<Relations<R>>(source)?.get(target)
+    }
+
+    /// Fetches a mutable reference to the data of the pair, marking the relations of the source as changed.
+    pub fn relation_mut<R: Relation>(&mut self, source: Entity, target: Entity) -> Option<&mut R> {
+        self.get_component_mut::<Relations<R>>(source)?
+            .0
+            .iter_mut()
+            .find(|(other, _)| *other == target)
+            .map(|(_, data)| data)
+    }
+
+    /// Iterates over the targets of the pairs of kind `R` from the source.
+    pub fn targets<R: Relation>(&self, source: Entity) -> impl Iterator<Item = Entity> + '_ {
+        self.get_component_ref::<Relations<R>>(source)
+            .into_iter()
+            .flat_map(Relations::targets)
+    }
+
+    /// Iterates over the entities with a pair of kind `R` to the target.
+    pub fn sources<R: Relation>(&self, target: Entity) -> impl Iterator<Item = Entity> + '_ {
+        self.get_component_ref::<Sources<R>>(target)
+            .into_iter()
+            .flat_map(|sources| sources.0.iter().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::StorageKind;
+
+    struct Targets;
+
+    #[derive(Debug, PartialEq)]
+    struct DockedAt {
+        bay: u32,
+    }
+
+    fn relations(kind: StorageKind) {
+        let mut reg = Registry::new(kind);
+        let [player, enemy, other, station] = [(); 4].map(|_| reg.create_entity());
+
+        assert!(reg.add_relation(player, Targets, enemy));
+        assert!(reg.add_relation(player, Targets, other));
+        assert!(reg.add_relation(other, Targets, enemy));
+        assert!(reg.add_relation(player, DockedAt { bay: 1 }, station));
+        assert!(reg.add_relation(player, DockedAt { bay: 2 }, station));
+
+        assert_eq!(
+            reg.targets::<Targets>(player).collect::<Vec<_>>(),
+            [enemy, other]
+        );
+        assert_eq!(
+            reg.sources::<Targets>(enemy).collect::<Vec<_>>(),
+            [player, other]
+        );
+        assert_eq!(reg.targets::<Targets>(station).count(), 0);
+        assert_eq!(
+            reg.relation::<DockedAt>(player, station),
+            Some(&DockedAt { bay: 2 })
+        );
+        assert!(!reg.has_relation::<DockedAt>(station, player));
+
+        reg.relation_mut::<DockedAt>(player, station).unwrap().bay = 3;
+        assert_eq!(
+            reg.remove_relation::<DockedAt>(player, station),
+            Some(DockedAt { bay: 3 })
+        );
+        assert!(!reg.has_component::<Relations<DockedAt>>(player));
+        assert_eq!(reg.sources::<DockedAt>(station).count(), 0);
+
+        // destroying a target removes the pairs to it, destroying a source removes its pairs
+        reg.destroy_entity(&enemy);
+        assert_eq!(reg.targets::<Targets>(player).collect::<Vec<_>>(), [other]);
+        assert!(!reg.has_component::<Relations<Targets>>(other));
+        reg.destroy_entity(&player);
+        assert_eq!(reg.sources::<Targets>(other).count(), 0);
+
+        // pairs with a destroyed entity cannot be added, nor leak to the entity recycling its index
+        assert!(!reg.add_relation(other, Targets, enemy));
+        let recycled = reg.create_entity();
+        assert_eq!(recycled.index(), player.index());
+        assert_eq!(reg.targets::<Targets>(recycled).count(), 0);
+        assert_eq!(reg.sources::<Targets>(recycled).count(), 0);
+    }
+
+    #[test]
+    fn test_relations() {
+        relations(StorageKind::Sparse);
+        relations(StorageKind::Archetype);
+    }
+
+    #[test]
+    fn test_relation_ids() {
+        let ids = [
+            Relations::<Targets>::id(),
+            Relations::<DockedAt>::id(),
+            Sources::<Targets>::id(),
+        ];
+        assert_eq!(ids[0], Relations::<Targets>::id());
+        assert_eq!(ids[0], component_id::<Relations<Targets>>());
+        assert!(ids[0] != ids[1] && ids[0] != ids[2] && ids[1] != ids[2]);
+    }
+}
@@ -0,0 +1,286 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    cell::UnsafeCell,
+    collections::BTreeMap,
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+};
+
+use super::access::{Access, BorrowError, BorrowFlags};
+
+/// Global value of a [World](crate::world::World), stored once per type.
+pub trait Resource: Send + Sync + 'static {}
+
+impl<T: Send + Sync + 'static> Resource for T {}
+
+static RESOURCE_IDS: Mutex<BTreeMap<TypeId, usize>> = Mutex::new(BTreeMap::new());
+
+/// Returns the identifier assigned to the resource type `T`.  Identifiers are dense and start at zero, independently
+/// of the identifiers of components.
+pub fn resource_id<T: Resource>() -> usize {
+    let mut ids = RESOURCE_IDS.lock().unwrap();
+    let next = ids.len();
+    *ids.entry(TypeId::of::<T>()).or_insert(next)
+}
+
+struct ResourceData {
+    value: UnsafeCell<Box<dyn Any + Send + Sync>>,
+}
+
+// SAFETY: the value is only reached mutably through `&mut Resources`, or through a borrow of a `ResourcesCell` that
+// excludes every other access to it.
+unsafe impl Sync for ResourceData {}
+
+/// Typed resources, indexed by their [resource_id].
+#[derive(Default)]
+pub struct Resources {
+    resources: Vec<Option<ResourceData>>,
+}
+
+impl Resources {
+    /// Inserts a resource, returning the previous resource of the same type.
+    pub fn insert<T: Resource>(&mut self, resource: T) -> Option<T> {
+        let id = resource_id::<T>();
+        if id >= self.resources.len() {
+            self.resources.resize_with(id + 1, || None);
+        }
+
+        self.resources[id]
+            .replace(ResourceData {
+                value: UnsafeCell::new(Box::new(resource)),
+            })
+            .map(|old| Self::downcast(old.value.into_inner()))
+    }
+
+    pub fn get<T: Resource>(&self) -> Option<&T> {
+        self.data::<T>().map(|data| {
+            // SAFETY: shared access to the resources excludes every mutable borrow of a cell
+            let value = unsafe { &*data.value.get() };
+            value.downcast_ref::<T>().unwrap()
+        })
+    }
+
+    pub fn get_mut<T: Resource>(&mut self) -> Option<&mut T> {
+        self.resources
+            .get_mut(resource_id::<T>())
+            .and_then(Option::as_mut)
+            .map(|data| data.value.get_mut().downcast_mut::<T>().unwrap())
+    }
+
+    pub fn remove<T: Resource>(&mut self) -> Option<T> {
+        self.resources
+            .get_mut(resource_id::<T>())
+            .and_then(Option::take)
+            .map(|data| Self::downcast(data.value.into_inner()))
+    }
+
+    pub fn contains<T: Resource>(&self) -> bool {
+        self.data::<T>().is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.resources.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Grants shared access to the resources, where several resources may be borrowed at once as long as their
+    /// borrows do not conflict.
+    pub fn cell(&mut self) -> ResourcesCell<'_> {
+        ResourcesCell {
+            borrows: BorrowFlags::new(self.resources.len()),
+            resources: self,
+        }
+    }
+
+    fn data<T: Resource>(&self) -> Option<&ResourceData> {
+        self.resources
+            .get(resource_id::<T>())
+            .and_then(Option::as_ref)
+    }
+
+    fn downcast<T: Resource>(value: Box<dyn Any + Send + Sync>) -> T {
+        *value.downcast::<T>().unwrap()
+    }
+}
+
+/// Resources shared between several borrows.  Resources are borrowed at runtime for as long as the [Res] or [ResMut]
+/// returned for them is alive, and conflicting borrows are rejected.
+pub struct ResourcesCell<'w> {
+    resources: &'w Resources,
+    borrows: BorrowFlags,
+}
+
+impl<'w> ResourcesCell<'w> {
+    /// Borrows a resource, failing if it is mutably borrowed.  Returns `None` if there is no resource of the type.
+    pub fn try_get<T: Resource>(&self) -> Result<Option<Res<'_, T>>, BorrowError> {
+        let data = match self.resources.data::<T>() {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+
+        let mut access = Access::default();
+        access.read(resource_id::<T>());
+        self.borrows.acquire(&access)?;
+
+        // SAFETY: the shared borrow excludes every mutable borrow of the resource until it is released
+        let value = unsafe { &*data.value.get() };
+        Ok(Some(Res {
+            value: value.downcast_ref::<T>().unwrap(),
+            borrows: &self.borrows,
+            access,
+        }))
+    }
+
+    /// Borrows a resource mutably, failing if it is borrowed.  Returns `None` if there is no resource of the type.
+    pub fn try_get_mut<T: Resource>(&self) -> Result<Option<ResMut<'_, T>>, BorrowError> {
+        let data = match self.resources.data::<T>() {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+
+        let mut access = Access::default();
+        access.write(resource_id::<T>());
+        self.borrows.acquire(&access)?;
+
+        // SAFETY: the exclusive borrow excludes every other borrow of the resource until it is released
+        let value = unsafe { &mut *data.value.get() };
+        Ok(Some(ResMut {
+            value: value.downcast_mut::<T>().unwrap(),
+            borrows: &self.borrows,
+            access,
+        }))
+    }
+
+    /// Borrows a resource.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource is mutably borrowed.
+    pub fn get<T: Resource>(&self) -> Option<Res<'_, T>> {
+        self.try_get()
+            .unwrap_or_else(|err| panic!("{} ({})", err, type_name::<T>()))
+    }
+
+    /// Borrows a resource mutably.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource is borrowed.
+    pub fn get_mut<T: Resource>(&self) -> Option<ResMut<'_, T>> {
+        self.try_get_mut()
+            .unwrap_or_else(|err| panic!("{} ({})", err, type_name::<T>()))
+    }
+}
+
+/// Resource borrowed from a [ResourcesCell], released when dropped.
+pub struct Res<'c, T> {
+    value: &'c T,
+    borrows: &'c BorrowFlags,
+    access: Access,
+}
+
+impl<'c, T> Deref for Res<'c, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<'c, T> Drop for Res<'c, T> {
+    fn drop(&mut self) {
+        self.borrows.release(&self.access);
+    }
+}
+
+/// Resource mutably borrowed from a [ResourcesCell], released when dropped.
+pub struct ResMut<'c, T> {
+    value: &'c mut T,
+    borrows: &'c BorrowFlags,
+    access: Access,
+}
+
+impl<'c, T> Deref for ResMut<'c, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<'c, T> DerefMut for ResMut<'c, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<'c, T> Drop for ResMut<'c, T> {
+    fn drop(&mut self) {
+        self.borrows.release(&self.access);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct FrameTime(f32);
+
+    #[derive(Debug, PartialEq)]
+    struct Seed(u64);
+
+    #[test]
+    fn test_resources() {
+        let mut resources = Resources::default();
+        assert!(!resources.contains::<FrameTime>());
+        assert_eq!(resources.insert(FrameTime(0.5)), None);
+        assert_eq!(resources.insert(FrameTime(1.0)), Some(FrameTime(0.5)));
+        assert_eq!(resources.get::<FrameTime>(), Some(&FrameTime(1.0)));
+        assert_eq!(resources.get::<Seed>(), None);
+
+        resources.get_mut::<FrameTime>().unwrap().0 += 1.0;
+        resources.insert(Seed(7));
+        assert_eq!(resources.len(), 2);
+
+        assert_eq!(resources.remove::<FrameTime>(), Some(FrameTime(2.0)));
+        assert_eq!(resources.remove::<FrameTime>(), None);
+        assert!(!resources.contains::<FrameTime>());
+        assert!(resources.contains::<Seed>());
+        assert_eq!(resources.len(), 1);
+    }
+
+    #[test]
+    fn test_resources_cell() {
+        let mut resources = Resources::default();
+        resources.insert(FrameTime(0.5));
+        resources.insert(Seed(7));
+
+        let cell = resources.cell();
+        {
+            let time = cell.get::<FrameTime>().unwrap();
+            let again = cell.get::<FrameTime>().unwrap();
+            let mut seed = cell.get_mut::<Seed>().unwrap();
+            seed.0 += (time.0 + again.0) as u64;
+
+            assert_eq!(
+                cell.try_get_mut::<FrameTime>().err(),
+                Some(BorrowError::AlreadyBorrowed(resource_id::<FrameTime>()))
+            );
+            assert_eq!(
+                cell.try_get::<Seed>().err(),
+                Some(BorrowError::AlreadyMutablyBorrowed(resource_id::<Seed>()))
+            );
+        }
+
+        // borrows are released with their guards
+        cell.get_mut::<FrameTime>().unwrap().0 = 2.0;
+        assert_eq!(*cell.get::<Seed>().unwrap(), Seed(8));
+        assert!(cell.get::<u32>().is_none());
+
+        assert_eq!(resources.get::<FrameTime>(), Some(&FrameTime(2.0)));
+    }
+}
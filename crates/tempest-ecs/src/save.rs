@@ -0,0 +1,830 @@
+use std::{collections::HashMap, fmt::Display, fs, path::Path};
+
+use tempest_math::f32::{mat4::Mat4, vec2::Vec2, vec3::Vec3, vec4::Vec4};
+
+pub use tempest_ecs_macros::Persist;
+
+use super::{
+    component::Component,
+    registry::{Entity, Registry},
+    world::World,
+};
+
+const MAGIC: &[u8; 4] = b"TMPW";
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+
+    /// The data is not a saved world, or was written by an unsupported version of the format.
+    InvalidFormat,
+
+    /// The data ended before a value was fully read.
+    UnexpectedEnd,
+
+    /// A value could not be decoded from its bytes.
+    InvalidData(&'static str),
+
+    /// The saved world contains a component type that is not registered.
+    UnknownComponent(String),
+
+    /// The saved world contains a component type under a schema version newer than the registered one.
+    UnsupportedVersion {
+        name: String,
+        version: u32,
+    },
+
+    /// The saved world contains a component type under an older schema version, but no migration is registered.
+    MissingMigration {
+        name: String,
+        version: u32,
+    },
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::InvalidFormat => write!(f, "Data is not a saved world."),
+            Self::UnexpectedEnd => write!(f, "Saved world ended unexpectedly."),
+            Self::InvalidData(reason) => write!(f, "Saved world is corrupted: {}", reason),
+            Self::UnknownComponent(name) => write!(f, "Component {} is not registered.", name),
+            Self::UnsupportedVersion { name, version } => write!(
+                f,
+                "Component {} was saved with newer schema version {}.",
+                name, version
+            ),
+            Self::MissingMigration { name, version } => write!(
+                f,
+                "Component {} has no migration from schema version {}.",
+                name, version
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Little-endian binary output of [Encode].
+#[derive(Default)]
+pub struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Little-endian binary input of [Decode].  Entities read while loading a world are remapped to the entities created
+/// for them.
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    entities: Option<&'a HashMap<Entity, Entity>>,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            position: 0,
+            entities: None,
+        }
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], SaveError> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(SaveError::UnexpectedEnd)?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], SaveError> {
+        Ok(self.read_bytes(N)?.try_into().unwrap())
+    }
+
+    /// Decoder over the next bytes, sharing the entity mapping of this decoder.
+    fn sub_decoder(&mut self, len: usize) -> Result<Decoder<'a>, SaveError> {
+        Ok(Decoder {
+            bytes: self.read_bytes(len)?,
+            position: 0,
+            entities: self.entities,
+        })
+    }
+}
+
+pub trait Encode {
+    fn encode(&self, encoder: &mut Encoder);
+}
+
+pub trait Decode: Sized {
+    fn decode(decoder: &mut Decoder) -> Result<Self, SaveError>;
+}
+
+/// Component saved along with the world.  The name identifies the component type in saved worlds, and must not
+/// change once worlds are saved with it.  The version is incremented whenever the encoding of the component changes.
+pub trait Persist: Component + Encode + Decode {
+    const NAME: &'static str;
+    const VERSION: u32;
+}
+
+macro_rules! number_encoding {
+    ($($T:ty),*) => {
+        $(
+            impl Encode for $T {
+                fn encode(&self, encoder: &mut Encoder) {
+                    encoder.write_bytes(&self.to_le_bytes());
+                }
+            }
+
+            impl Decode for $T {
+                fn decode(decoder: &mut Decoder) -> Result<Self, SaveError> {
+                    Ok(<$T>::from_le_bytes(decoder.read_array()?))
+                }
+            }
+        )*
+    };
+}
+
+number_encoding!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl Encode for usize {
+    fn encode(&self, encoder: &mut Encoder) {
+        (*self as u64).encode(encoder);
+    }
+}
+
+impl Decode for usize {
+    fn decode(decoder: &mut Decoder) -> Result<Self, SaveError> {
+        usize::try_from(u64::decode(decoder)?).map_err(|_| SaveError::InvalidData("Size overflow."))
+    }
+}
+
+impl Encode for isize {
+    fn encode(&self, encoder: &mut Encoder) {
+        (*self as i64).encode(encoder);
+    }
+}
+
+impl Decode for isize {
+    fn decode(decoder: &mut Decoder) -> Result<Self, SaveError> {
+        isize::try_from(i64::decode(decoder)?).map_err(|_| SaveError::InvalidData("Size overflow."))
+    }
+}
+
+impl Encode for bool {
+    fn encode(&self, encoder: &mut Encoder) {
+        (*self as u8).encode(encoder);
+    }
+}
+
+impl Decode for bool {
+    fn decode(decoder: &mut Decoder) -> Result<Self, SaveError> {
+        match u8::decode(decoder)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SaveError::InvalidData("Invalid boolean.")),
+        }
+    }
+}
+
+impl Encode for str {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.len().encode(encoder);
+        encoder.write_bytes(self.as_bytes());
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.as_str().encode(encoder);
+    }
+}
+
+impl Decode for String {
+    fn decode(decoder: &mut Decoder) -> Result<Self, SaveError> {
+        let len = usize::decode(decoder)?;
+        String::from_utf8(decoder.read_bytes(len)?.to_vec())
+            .map_err(|_| SaveError::InvalidData("Invalid UTF-8 string."))
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.len().encode(encoder);
+        self.iter().for_each(|value| value.encode(encoder));
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.as_slice().encode(encoder);
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(decoder: &mut Decoder) -> Result<Self, SaveError> {
+        let len = usize::decode(decoder)?;
+        // The length is not trusted to reserve memory, every element takes at least a byte anyway
+        let mut values = Vec::with_capacity(len.min(decoder.remaining()));
+        for _ in 0..len {
+            values.push(T::decode(decoder)?);
+        }
+        Ok(values)
+    }
+}
+
+impl<T: Encode, const N: usize> Encode for [T; N] {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.iter().for_each(|value| value.encode(encoder));
+    }
+}
+
+impl<T: Decode, const N: usize> Decode for [T; N] {
+    fn decode(decoder: &mut Decoder) -> Result<Self, SaveError> {
+        let values = (0..N)
+            .map(|_| T::decode(decoder))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(values
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("Exactly N values are decoded.")))
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Some(value) => {
+                true.encode(encoder);
+                value.encode(encoder);
+            }
+            None => false.encode(encoder),
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(decoder: &mut Decoder) -> Result<Self, SaveError> {
+        match bool::decode(decoder)? {
+            true => Ok(Some(T::decode(decoder)?)),
+            false => Ok(None),
+        }
+    }
+}
+
+macro_rules! tuple_encoding {
+    ($($T:ident),*) => {
+        impl<$($T: Encode),*> Encode for ($($T,)*) {
+            #[allow(non_snake_case)]
+            fn encode(&self, encoder: &mut Encoder) {
+                let ($($T,)*) = self;
+                $($T.encode(encoder);)*
+            }
+        }
+
+        impl<$($T: Decode),*> Decode for ($($T,)*) {
+            fn decode(decoder: &mut Decoder) -> Result<Self, SaveError> {
+                Ok(($($T::decode(decoder)?,)*))
+            }
+        }
+    };
+}
+
+tuple_encoding!(A);
+tuple_encoding!(A, B);
+tuple_encoding!(A, B, C);
+tuple_encoding!(A, B, C, D);
+
+impl Encode for Entity {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.index().encode(encoder);
+        self.generation().encode(encoder);
+    }
+}
+
+impl Decode for Entity {
+    /// Entities that were not saved along with the world decode to a handle that is never alive.
+    fn decode(decoder: &mut Decoder) -> Result<Self, SaveError> {
+        let saved = Entity::new(u32::decode(decoder)?, u32::decode(decoder)?);
+        Ok(match decoder.entities {
+            Some(entities) => entities.get(&saved).copied().unwrap_or(Entity::DANGLING),
+            None => saved,
+        })
+    }
+}
+
+impl Encode for Vec2 {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.to_array().encode(encoder);
+    }
+}
+
+impl Decode for Vec2 {
+    fn decode(decoder: &mut Decoder) -> Result<Self, SaveError> {
+        Ok(Vec2::from_array(Decode::decode(decoder)?))
+    }
+}
+
+impl Encode for Vec3 {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.to_array().encode(encoder);
+    }
+}
+
+impl Decode for Vec3 {
+    fn decode(decoder: &mut Decoder) -> Result<Self, SaveError> {
+        Ok(Vec3::from_array(Decode::decode(decoder)?))
+    }
+}
+
+impl Encode for Vec4 {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.to_array().encode(encoder);
+    }
+}
+
+impl Decode for Vec4 {
+    fn decode(decoder: &mut Decoder) -> Result<Self, SaveError> {
+        Ok(Vec4::from_array(Decode::decode(decoder)?))
+    }
+}
+
+impl Encode for Mat4 {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.arr.encode(encoder);
+    }
+}
+
+impl Decode for Mat4 {
+    fn decode(decoder: &mut Decoder) -> Result<Self, SaveError> {
+        Ok(Mat4::from_array(Decode::decode(decoder)?))
+    }
+}
+
+type SaveFn = Box<dyn Fn(&Registry, Entity, &mut Encoder) -> bool>;
+type LoadFn = Box<dyn Fn(&mut Registry, Entity, &mut Decoder, u32) -> Result<(), SaveError>>;
+
+struct PersistedType {
+    name: &'static str,
+    version: u32,
+    save: SaveFn,
+    load: LoadFn,
+}
+
+/// Set of component types saved and loaded along with a world.  Components of other types are left out of saved
+/// worlds.
+///
+/// Saved worlds contain every entity alive, and the registered components of each of them.  Loading a world creates
+/// new entities, and remaps the entities referenced by the loaded components to them.  Loaded entities get fresh
+/// handles: the saved handles, generations included, only tell apart the entities of the saved world from the
+/// destroyed entities its components still reference, which load as dangling handles.
+#[derive(Default)]
+pub struct Persistence {
+    types: Vec<PersistedType>,
+}
+
+impl Persistence {
+    /// Registers a component type, which fails to load when saved with an older schema version.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another type is registered with the same name.
+    pub fn register<T: Persist>(&mut self) -> &mut Self {
+        self.register_type::<T>(None::<fn(&mut Decoder, u32) -> Result<T, SaveError>>)
+    }
+
+    /// Registers a component type, along with the migration decoding it from older schema versions.  The migration
+    /// receives the schema version the component was saved with.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another type is registered with the same name.
+    pub fn register_with_migration<T, F>(&mut self, migration: F) -> &mut Self
+    where
+        T: Persist,
+        F: Fn(&mut Decoder, u32) -> Result<T, SaveError> + 'static,
+    {
+        self.register_type::<T>(Some(migration))
+    }
+
+    /// Encodes the entities of the world and their registered components.
+    pub fn save(&self, world: &World) -> Vec<u8> {
+        let reg = world.entities();
+        let entities: Vec<Entity> = reg.iter_entities().collect();
+
+        let mut encoder = Encoder::default();
+        encoder.write_bytes(MAGIC);
+        FORMAT_VERSION.encode(&mut encoder);
+        entities.encode(&mut encoder);
+
+        (self.types.len() as u32).encode(&mut encoder);
+        for tp in &self.types {
+            let mut instances = Encoder::default();
+            let mut count = 0u32;
+            for (position, ent) in entities.iter().enumerate() {
+                let mut component = Encoder::default();
+                if (tp.save)(reg, *ent, &mut component) {
+                    (position as u32).encode(&mut instances);
+                    (component.len() as u32).encode(&mut instances);
+                    instances.write_bytes(&component.bytes);
+                    count += 1;
+                }
+            }
+
+            tp.name.encode(&mut encoder);
+            tp.version.encode(&mut encoder);
+            count.encode(&mut encoder);
+            encoder.write_bytes(&instances.bytes);
+        }
+
+        encoder.into_bytes()
+    }
+
+    pub fn save_to_file(&self, world: &World, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        fs::write(path, self.save(world))?;
+        Ok(())
+    }
+
+    /// Decodes a saved world into a new world.
+    pub fn load(&self, bytes: &[u8]) -> Result<World, SaveError> {
+        let mut world = World::default();
+        self.load_into(&mut world, bytes)?;
+        Ok(world)
+    }
+
+    pub fn load_from_file(&self, path: impl AsRef<Path>) -> Result<World, SaveError> {
+        self.load(&fs::read(path)?)
+    }
+
+    /// Decodes a saved world into an existing world, returning the entities created for the saved entities in the
+    /// order they were saved.  If an error occurs, the created entities are destroyed again, so that the world only
+    /// keeps the entities it had.
+    pub fn load_into(&self, world: &mut World, bytes: &[u8]) -> Result<Vec<Entity>, SaveError> {
+        let mut decoder = Decoder::new(bytes);
+        if decoder.read_bytes(MAGIC.len()).ok() != Some(&MAGIC[..])
+            || u32::decode(&mut decoder)? != FORMAT_VERSION
+        {
+            return Err(SaveError::InvalidFormat);
+        }
+
+        let reg = world.entitites_mut();
+        let saved: Vec<Entity> = Decode::decode(&mut decoder)?;
+        let created: Vec<Entity> = saved.iter().map(|_| reg.create_entity()).collect();
+        let mapping: HashMap<Entity, Entity> = saved
+            .iter()
+            .zip(&created)
+            .map(|(saved, created)| (*saved, *created))
+            .collect();
+        decoder.entities = Some(&mapping);
+
+        if let Err(err) = self.load_components(reg, &mut decoder, &created) {
+            for ent in &created {
+                reg.destroy_entity(ent);
+            }
+            return Err(err);
+        }
+
+        Ok(created)
+    }
+
+    /// Decodes the sections of the saved component types onto the entities created for the saved entities.
+    fn load_components(
+        &self,
+        reg: &mut Registry,
+        decoder: &mut Decoder,
+        created: &[Entity],
+    ) -> Result<(), SaveError> {
+        let type_count = u32::decode(decoder)?;
+        for _ in 0..type_count {
+            let name = String::decode(decoder)?;
+            let version = u32::decode(decoder)?;
+            let count = u32::decode(decoder)?;
+
+            let tp = match self.types.iter().find(|tp| tp.name == name) {
+                Some(tp) => tp,
+                None => return Err(SaveError::UnknownComponent(name)),
+            };
+            if version > tp.version {
+                return Err(SaveError::UnsupportedVersion { name, version });
+            }
+
+            for _ in 0..count {
+                let position = u32::decode(decoder)? as usize;
+                let ent = *created
+                    .get(position)
+                    .ok_or(SaveError::InvalidData("Component of an unknown entity."))?;
+                let len = u32::decode(decoder)? as usize;
+
+                let mut component = decoder.sub_decoder(len)?;
+                (tp.load)(reg, ent, &mut component, version)?;
+                if component.remaining() != 0 {
+                    return Err(SaveError::InvalidData("Component was not fully decoded."));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn register_type<T: Persist>(
+        &mut self,
+        migration: Option<impl Fn(&mut Decoder, u32) -> Result<T, SaveError> + 'static>,
+    ) -> &mut Self {
+        assert!(
+            !self.types.iter().any(|tp| tp.name == T::NAME),
+            "Component {} is registered more than once.",
+            T::NAME
+        );
+
+        self.types.push(PersistedType {
+            name: T::NAME,
+            version: T::VERSION,
+            save: Box::new(|reg, ent, encoder| match reg.get_component_ref::<T>(ent) {
+                Some(component) => {
+                    component.encode(encoder);
+                    true
+                }
+                None => false,
+            }),
+            load: Box::new(move |reg, ent, decoder, version| {
+                let component = if version == T::VERSION {
+                    T::decode(decoder)?
+                } else {
+                    match &migration {
+                        Some(migration) => migration(decoder, version)?,
+                        None => {
+                            return Err(SaveError::MissingMigration {
+                                name: T::NAME.to_owned(),
+                                version,
+                            })
+                        }
+                    }
+                };
+
+                reg.assign_component(ent, component);
+                Ok(())
+            }),
+        });
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hierarchy::{Children, Parent};
+
+    #[derive(Component, Persist, Clone, Debug, PartialEq)]
+    #[persist(name = "health", version = 2)]
+    struct Health {
+        current: u32,
+        max: u32,
+    }
+
+    #[derive(Component, Persist, Clone, Debug, PartialEq)]
+    #[persist(name = "health", version = 1)]
+    struct OldHealth(u16);
+
+    #[derive(Component, Persist, Clone, Debug, PartialEq)]
+    struct Target(Option<Entity>);
+
+    #[derive(Component, Persist, Clone, Debug, PartialEq)]
+    enum State {
+        Idle,
+        Walking { speed: f32, path: Vec<Vec3> },
+        Named(String),
+    }
+
+    #[derive(Component, Clone)]
+    struct Transient;
+
+    fn persistence() -> Persistence {
+        let mut persistence = Persistence::default();
+        persistence
+            .register::<Target>()
+            .register::<State>()
+            .register::<Parent>()
+            .register::<Children>()
+            .register_with_migration::<Health, _>(|decoder, version| {
+                assert_eq!(version, 1);
+                let current = u16::decode(decoder)? as u32;
+                Ok(Health { current, max: 100 })
+            });
+        persistence
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut world = World::default();
+        let reg = world.entitites_mut();
+        let [a, b, c] = [(); 3].map(|_| reg.create_entity());
+
+        // recycle the slot of c, so that the saved entities do not match their positions
+        reg.destroy_entity(&c);
+        let d = reg.create_entity();
+        assert_ne!(c, d);
+
+        reg.assign_component(
+            a,
+            Health {
+                current: 5,
+                max: 10,
+            },
+        );
+        reg.assign_component(a, Target(Some(d)));
+        reg.assign_component(b, Target(Some(c)));
+        reg.assign_component(b, State::Named("bob".to_string()));
+        reg.assign_component(
+            d,
+            State::Walking {
+                speed: 1.5,
+                path: vec![Vec3::X, Vec3::Y],
+            },
+        );
+        reg.assign_component(d, Transient);
+        reg.set_parent(b, a);
+
+        let persistence = persistence();
+        let bytes = persistence.save(&world);
+        let mut loaded = World::default();
+        loaded.entitites_mut().create_entity();
+        let created = persistence.load_into(&mut loaded, &bytes).unwrap();
+
+        let original: Vec<Entity> = world.entities().iter_entities().collect();
+        let remap = |ent: Entity| created[original.iter().position(|e| *e == ent).unwrap()];
+        let reg = loaded.entities();
+        assert_eq!(reg.num_entities(), 4);
+
+        assert_eq!(
+            reg.get_component::<Health>(remap(a)),
+            Some(Health {
+                current: 5,
+                max: 10
+            })
+        );
+        assert_eq!(
+            reg.get_component::<Target>(remap(a)),
+            Some(Target(Some(remap(d))))
+        );
+        // references to destroyed entities stay dangling
+        let dangling = reg.get_component::<Target>(remap(b)).unwrap().0.unwrap();
+        assert!(!reg.is_alive(dangling));
+
+        assert_eq!(
+            reg.get_component::<State>(remap(b)),
+            Some(State::Named("bob".to_string()))
+        );
+        assert_eq!(
+            reg.get_component::<State>(remap(d)),
+            Some(State::Walking {
+                speed: 1.5,
+                path: vec![Vec3::X, Vec3::Y]
+            })
+        );
+        assert!(!reg.has_component::<Transient>(remap(d)));
+        assert_eq!(reg.parent(remap(b)), Some(remap(a)));
+        assert_eq!(reg.children(remap(a)), [remap(b)]);
+    }
+
+    #[test]
+    fn test_migration() {
+        let mut world = World::default();
+        let ent = world.entitites_mut().create_entity();
+        world.entitites_mut().assign_component(ent, OldHealth(42));
+        world.entitites_mut().assign_component(ent, State::Idle);
+
+        let mut old = Persistence::default();
+        old.register::<OldHealth>().register::<State>();
+        let bytes = old.save(&world);
+
+        let loaded = persistence().load(&bytes).unwrap();
+        let ent = loaded.entities().iter_entities().next().unwrap();
+        assert_eq!(
+            loaded.entities().get_component::<Health>(ent),
+            Some(Health {
+                current: 42,
+                max: 100
+            })
+        );
+        assert_eq!(
+            loaded.entities().get_component::<State>(ent),
+            Some(State::Idle)
+        );
+
+        // without a migration, or with a newer version, loading fails
+        let mut strict = Persistence::default();
+        strict.register::<Health>().register::<State>();
+        assert!(matches!(
+            strict.load(&bytes),
+            Err(SaveError::MissingMigration { version: 1, .. })
+        ));
+
+        let newer = strict.save(&loaded);
+        assert!(matches!(
+            old.load(&newer),
+            Err(SaveError::UnsupportedVersion { version: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_data() {
+        let persistence = persistence();
+        assert!(matches!(
+            persistence.load(b"nope"),
+            Err(SaveError::InvalidFormat)
+        ));
+
+        let mut world = World::default();
+        let ent = world.entitites_mut().create_entity();
+        world
+            .entitites_mut()
+            .assign_component(ent, State::Named("truncated".to_string()));
+        let bytes = persistence.save(&world);
+        assert!(matches!(
+            persistence.load(&bytes[..bytes.len() - 1]),
+            Err(SaveError::UnexpectedEnd)
+        ));
+
+        let mut other = Persistence::default();
+        other.register::<Target>();
+        assert!(matches!(
+            other.load(&bytes),
+            Err(SaveError::UnknownComponent(name)) if name == "State"
+        ));
+
+        // a failed load into a world leaves only the entities it had, even once some components were loaded
+        let mut world = World::default();
+        let reg = world.entitites_mut();
+        let [a, b] = [(); 2].map(|_| reg.create_entity());
+        reg.assign_component(a, Target(Some(b)));
+        reg.assign_component(b, State::Idle);
+        reg.set_parent(b, a);
+        let bytes = persistence.save(&world);
+
+        let mut partial = Persistence::default();
+        partial.register::<Target>().register::<Parent>();
+        let mut loaded = World::default();
+        let existing = loaded.entitites_mut().create_entity();
+        assert!(matches!(
+            partial.load_into(&mut loaded, &bytes),
+            Err(SaveError::UnknownComponent(name)) if name == "State"
+        ));
+        assert_eq!(
+            loaded.entities().iter_entities().collect::<Vec<_>>(),
+            [existing]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_file_round_trip() {
+        let path = std::env::temp_dir().join(format!("tempest-save-{}.bin", std::process::id()));
+        let mut world = World::default();
+        let ent = world.entitites_mut().create_entity();
+        world
+            .entitites_mut()
+            .assign_component(ent, Health { current: 1, max: 2 });
+
+        let persistence = persistence();
+        persistence.save_to_file(&world, &path).unwrap();
+        let loaded = persistence.load_from_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        let ent = loaded.entities().iter_entities().next().unwrap();
+        assert_eq!(
+            loaded.entities().get_component::<Health>(ent),
+            Some(Health { current: 1, max: 2 })
+        );
+    }
+}
@@ -0,0 +1,1533 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    fs,
+    iter::Peekable,
+    path::{Path, PathBuf},
+    str::Chars,
+    time::SystemTime,
+};
+
+use tempest_math::f32::{mat4::Mat4, vec2::Vec2, vec3::Vec3, vec4::Vec4};
+
+pub use tempest_ecs_macros::SceneValue;
+
+use super::{
+    component::Component,
+    hierarchy::Parent,
+    registry::{Entity, Registry},
+    save::Persist,
+    transformation::Transformation,
+};
+
+/// Name of an entity instantiated from a scene, by which other entities of the scene reference it.
+#[derive(Component, Persist, Clone, Debug, Eq, PartialEq)]
+#[persist(name = "tempest::Name")]
+pub struct Name(pub String);
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    /// Two entities of the scene have the same name.
+    DuplicateEntity(String),
+
+    /// The scene references an entity that it does not contain.
+    UnknownEntity(String),
+
+    /// The scene contains a component type that is not registered.
+    UnknownComponent(String),
+
+    /// A value does not match the type it is converted to.
+    InvalidValue(String),
+
+    /// A component of the scene could not be converted.
+    InvalidComponent {
+        entity: String,
+        component: String,
+        source: Box<SceneError>,
+    },
+
+    /// An entity of the scene is attached to itself, to one of its descendants, or to an entity that is not alive.
+    InvalidParent {
+        entity: String,
+        parent: String,
+    },
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::Parse {
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}: {}", line, column, message),
+            Self::DuplicateEntity(name) => write!(f, "Entity {} is declared more than once.", name),
+            Self::UnknownEntity(name) => write!(f, "Entity {} is not declared.", name),
+            Self::UnknownComponent(name) => write!(f, "Component {} is not registered.", name),
+            Self::InvalidValue(message) => write!(f, "{}", message),
+            Self::InvalidComponent {
+                entity,
+                component,
+                source,
+            } => write!(f, "{}.{}: {}", entity, component, source),
+            Self::InvalidParent { entity, parent } => {
+                write!(f, "Entity {} cannot be attached to {}.", entity, parent)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::InvalidComponent { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SceneError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Value written in a scene file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// `()`, or nothing after the name of a component or variant.
+    Unit,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    String(String),
+    /// `@name`, a reference to the entity of the scene with that name.
+    Entity(String),
+    /// `[a, b]`
+    List(Vec<Value>),
+    /// `(a, b)`
+    Tuple(Vec<Value>),
+    /// `(x: a, y: b)`
+    Struct(Vec<(String, Value)>),
+    /// `Name`, `Name(a, b)` or `Name(x: a, y: b)`
+    Variant(String, Box<Value>),
+}
+
+impl Value {
+    pub fn expect_unit(&self) -> Result<(), SceneError> {
+        match self {
+            Value::Unit => Ok(()),
+            _ => Err(self.unexpected("()")),
+        }
+    }
+
+    /// Fields of a struct value, which must not have fields other than the known ones.
+    pub fn expect_struct(&self, known: &[&str]) -> Result<&[(String, Value)], SceneError> {
+        let fields = match self {
+            Value::Struct(fields) => fields,
+            _ => return Err(self.unexpected("a struct")),
+        };
+
+        match fields
+            .iter()
+            .find(|(name, _)| !known.contains(&name.as_str()))
+        {
+            Some((name, _)) => Err(SceneError::InvalidValue(format!("Unknown field {}.", name))),
+            None => Ok(fields),
+        }
+    }
+
+    pub fn expect_tuple(&self, len: usize) -> Result<&[Value], SceneError> {
+        match self {
+            Value::Unit if len == 0 => Ok(&[]),
+            Value::Tuple(values) if values.len() == len => Ok(values),
+            _ => Err(self.unexpected(&format!("a tuple of {} values", len))),
+        }
+    }
+
+    pub fn expect_list(&self) -> Result<&[Value], SceneError> {
+        match self {
+            Value::List(values) => Ok(values),
+            _ => Err(self.unexpected("a list")),
+        }
+    }
+
+    pub fn expect_variant(&self) -> Result<(&str, &Value), SceneError> {
+        match self {
+            Value::Variant(name, value) => Ok((name, value)),
+            // Enum components are written `State(Walking)`, where the variant is parsed as a tuple of one value
+            Value::Tuple(values) if values.len() == 1 => values[0].expect_variant(),
+            _ => Err(self.unexpected("a variant")),
+        }
+    }
+
+    pub fn unexpected(&self, expected: &str) -> SceneError {
+        SceneError::InvalidValue(format!("Expected {}, found {}.", expected, self))
+    }
+
+    /// Writes the value after the name of a component or variant, where units are left out.
+    fn fmt_payload(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Unit => Ok(()),
+            Value::Tuple(_) | Value::Struct(_) => write!(f, "{}", self),
+            _ => write!(f, "({})", self),
+        }
+    }
+}
+
+fn fmt_separated<T>(
+    f: &mut std::fmt::Formatter<'_>,
+    values: &[T],
+    mut fmt: impl FnMut(&mut std::fmt::Formatter<'_>, &T) -> std::fmt::Result,
+) -> std::fmt::Result {
+    for (idx, value) in values.iter().enumerate() {
+        if idx > 0 {
+            write!(f, ", ")?;
+        }
+        fmt(f, value)?;
+    }
+    Ok(())
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Unit => write!(f, "()"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::String(value) => write!(f, "{:?}", value),
+            Value::Entity(name) => write!(f, "@{}", EntityName(name)),
+            Value::List(values) => {
+                write!(f, "[")?;
+                fmt_separated(f, values, |f, value| write!(f, "{}", value))?;
+                write!(f, "]")
+            }
+            Value::Tuple(values) => {
+                write!(f, "(")?;
+                fmt_separated(f, values, |f, value| write!(f, "{}", value))?;
+                write!(f, ")")
+            }
+            Value::Struct(fields) => {
+                write!(f, "(")?;
+                fmt_separated(f, fields, |f, (name, value)| {
+                    write!(f, "{}: {}", name, value)
+                })?;
+                write!(f, ")")
+            }
+            Value::Variant(name, value) => {
+                write!(f, "{}", name)?;
+                value.fmt_payload(f)
+            }
+        }
+    }
+}
+
+/// Entity name, quoted when it is not an identifier.
+struct EntityName<'a>(&'a str);
+
+impl<'a> Display for EntityName<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut chars = self.0.chars();
+        let is_ident = chars
+            .next()
+            .map_or(false, |c| c.is_alphabetic() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || c == '_');
+
+        if is_ident {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "{:?}", self.0)
+        }
+    }
+}
+
+/// Names of the entities of an instantiated scene.
+#[derive(Debug, Default)]
+pub struct EntityNames {
+    entities: HashMap<String, Entity>,
+    names: HashMap<Entity, String>,
+}
+
+impl EntityNames {
+    pub fn entity(&self, name: &str) -> Option<Entity> {
+        self.entities.get(name).copied()
+    }
+
+    pub fn name(&self, ent: Entity) -> Option<&str> {
+        self.names.get(&ent).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Entity)> {
+        self.entities
+            .iter()
+            .map(|(name, ent)| (name.as_str(), *ent))
+    }
+
+    fn insert(&mut self, name: String, ent: Entity) {
+        self.names.insert(ent, name.clone());
+        self.entities.insert(name, ent);
+    }
+
+    /// Names every entity of the registry after its [Name] component.  Entities without a name, or with a name
+    /// already taken, are named after their index.
+    fn from_registry(reg: &Registry) -> Self {
+        let mut names = Self::default();
+        let mut unnamed = Vec::new();
+        for ent in reg.iter_entities() {
+            match reg.get_component_ref::<Name>(ent) {
+                Some(name) if names.entity(&name.0).is_none() => names.insert(name.0.clone(), ent),
+                _ => unnamed.push(ent),
+            }
+        }
+
+        for ent in unnamed {
+            let mut name = format!("entity_{}", ent.index());
+            while names.entity(&name).is_some() {
+                name.push('_');
+            }
+            names.insert(name, ent);
+        }
+
+        names
+    }
+}
+
+/// Type convertible to and from the values of a scene file.
+pub trait SceneValue: Sized {
+    fn to_value(&self, names: &EntityNames) -> Value;
+    fn from_value(value: &Value, names: &EntityNames) -> Result<Self, SceneError>;
+}
+
+/// Converts the field of a struct value with the provided name.
+pub fn field<T: SceneValue>(
+    fields: &[(String, Value)],
+    name: &str,
+    names: &EntityNames,
+) -> Result<T, SceneError> {
+    match fields.iter().find(|(field, _)| field == name) {
+        Some((_, value)) => T::from_value(value, names),
+        None => Err(SceneError::InvalidValue(format!("Missing field {}.", name))),
+    }
+}
+
+macro_rules! int_scene_value {
+    ($($T:ty),*) => {
+        $(
+            impl SceneValue for $T {
+                fn to_value(&self, _names: &EntityNames) -> Value {
+                    Value::Int(*self as i128)
+                }
+
+                fn from_value(value: &Value, _names: &EntityNames) -> Result<Self, SceneError> {
+                    match value {
+                        Value::Int(int) => <$T>::try_from(*int).map_err(|_| {
+                            SceneError::InvalidValue(format!("{} does not fit in {}.", int, stringify!($T)))
+                        }),
+                        _ => Err(value.unexpected("an integer")),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+int_scene_value!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl SceneValue for f64 {
+    fn to_value(&self, _names: &EntityNames) -> Value {
+        Value::Float(*self)
+    }
+
+    fn from_value(value: &Value, _names: &EntityNames) -> Result<Self, SceneError> {
+        match value {
+            Value::Float(float) => Ok(*float),
+            Value::Int(int) => Ok(*int as f64),
+            Value::Variant(name, payload) if **payload == Value::Unit => match name.as_str() {
+                "inf" => Ok(f64::INFINITY),
+                "NaN" => Ok(f64::NAN),
+                _ => Err(value.unexpected("a number")),
+            },
+            _ => Err(value.unexpected("a number")),
+        }
+    }
+}
+
+impl SceneValue for f32 {
+    fn to_value(&self, _names: &EntityNames) -> Value {
+        // Going through the shortest decimal representation keeps 0.1 from being written as 0.10000000149011612
+        Value::Float(self.to_string().parse().unwrap())
+    }
+
+    fn from_value(value: &Value, names: &EntityNames) -> Result<Self, SceneError> {
+        f64::from_value(value, names).map(|float| float as f32)
+    }
+}
+
+impl SceneValue for bool {
+    fn to_value(&self, _names: &EntityNames) -> Value {
+        Value::Bool(*self)
+    }
+
+    fn from_value(value: &Value, _names: &EntityNames) -> Result<Self, SceneError> {
+        match value {
+            Value::Bool(value) => Ok(*value),
+            _ => Err(value.unexpected("a boolean")),
+        }
+    }
+}
+
+impl SceneValue for String {
+    fn to_value(&self, _names: &EntityNames) -> Value {
+        Value::String(self.clone())
+    }
+
+    fn from_value(value: &Value, _names: &EntityNames) -> Result<Self, SceneError> {
+        match value {
+            Value::String(value) => Ok(value.clone()),
+            _ => Err(value.unexpected("a string")),
+        }
+    }
+}
+
+impl<T: SceneValue> SceneValue for Vec<T> {
+    fn to_value(&self, names: &EntityNames) -> Value {
+        Value::List(self.iter().map(|value| value.to_value(names)).collect())
+    }
+
+    fn from_value(value: &Value, names: &EntityNames) -> Result<Self, SceneError> {
+        value
+            .expect_list()?
+            .iter()
+            .map(|value| T::from_value(value, names))
+            .collect()
+    }
+}
+
+impl<T: SceneValue> SceneValue for Option<T> {
+    fn to_value(&self, names: &EntityNames) -> Value {
+        match self {
+            Some(value) => Value::Variant(
+                "Some".to_string(),
+                Box::new(Value::Tuple(vec![value.to_value(names)])),
+            ),
+            None => Value::Variant("None".to_string(), Box::new(Value::Unit)),
+        }
+    }
+
+    fn from_value(value: &Value, names: &EntityNames) -> Result<Self, SceneError> {
+        match value.expect_variant()? {
+            ("Some", value) => Ok(Some(T::from_value(&value.expect_tuple(1)?[0], names)?)),
+            ("None", value) => value.expect_unit().map(|_| None),
+            _ => Err(value.unexpected("Some or None")),
+        }
+    }
+}
+
+macro_rules! tuple_scene_value {
+    ($len:literal, $($T:ident $idx:tt),*) => {
+        impl<$($T: SceneValue),*> SceneValue for ($($T,)*) {
+            fn to_value(&self, names: &EntityNames) -> Value {
+                Value::Tuple(vec![$(self.$idx.to_value(names)),*])
+            }
+
+            fn from_value(value: &Value, names: &EntityNames) -> Result<Self, SceneError> {
+                let values = value.expect_tuple($len)?;
+                Ok(($($T::from_value(&values[$idx], names)?,)*))
+            }
+        }
+    };
+}
+
+tuple_scene_value!(1, A 0);
+tuple_scene_value!(2, A 0, B 1);
+tuple_scene_value!(3, A 0, B 1, C 2);
+tuple_scene_value!(4, A 0, B 1, C 2, D 3);
+
+impl SceneValue for Entity {
+    /// Entities that are not alive are written as `()`, and read back as a handle that is never alive.
+    fn to_value(&self, names: &EntityNames) -> Value {
+        match names.name(*self) {
+            Some(name) => Value::Entity(name.to_string()),
+            None => Value::Unit,
+        }
+    }
+
+    fn from_value(value: &Value, names: &EntityNames) -> Result<Self, SceneError> {
+        match value {
+            Value::Entity(name) => names
+                .entity(name)
+                .ok_or_else(|| SceneError::UnknownEntity(name.clone())),
+            Value::Unit => Ok(Entity::DANGLING),
+            _ => Err(value.unexpected("an entity")),
+        }
+    }
+}
+
+macro_rules! float_array_scene_value {
+    ($($T:ty, $len:literal);*) => {
+        $(
+            impl SceneValue for $T {
+                fn to_value(&self, names: &EntityNames) -> Value {
+                    Value::Tuple(self.to_array().iter().map(|float| float.to_value(names)).collect())
+                }
+
+                fn from_value(value: &Value, names: &EntityNames) -> Result<Self, SceneError> {
+                    let mut arr = [0.0; $len];
+                    for (float, value) in arr.iter_mut().zip(value.expect_tuple($len)?) {
+                        *float = f32::from_value(value, names)?;
+                    }
+                    Ok(<$T>::from_array(arr))
+                }
+            }
+        )*
+    };
+}
+
+float_array_scene_value!(Vec2, 2; Vec3, 3; Vec4, 4);
+
+impl SceneValue for Mat4 {
+    fn to_value(&self, names: &EntityNames) -> Value {
+        Value::List(self.arr.iter().map(|float| float.to_value(names)).collect())
+    }
+
+    fn from_value(value: &Value, names: &EntityNames) -> Result<Self, SceneError> {
+        let values = value.expect_list()?;
+        if values.len() != 16 {
+            return Err(value.unexpected("a list of 16 numbers"));
+        }
+
+        let mut arr = [0.0; 16];
+        for (float, value) in arr.iter_mut().zip(values) {
+            *float = f32::from_value(value, names)?;
+        }
+        Ok(Mat4::from_array(arr))
+    }
+}
+
+/// Entity declared in a scene file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneEntity {
+    pub name: String,
+    pub components: Vec<(String, Value)>,
+}
+
+/// Parsed scene file.  Scene files list entities, each with a name and the values of its components:
+///
+/// ```text
+/// // comments run to the end of the line
+/// entity player {
+///     Transformation(position: (0.0, 1.0, 0.0), rotation: (0.0, 0.0, 0.0), scale: (1.0, 1.0, 1.0))
+///     Health(current: 10, max: 10)
+///     Target(Some(@"enemy 1"))
+/// }
+///
+/// entity "enemy 1" {
+///     Parent(@player)
+///     Hostile
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scene {
+    pub entities: Vec<SceneEntity>,
+}
+
+impl Scene {
+    pub fn parse(text: &str) -> Result<Self, SceneError> {
+        Parser::new(text)?.scene()
+    }
+}
+
+impl Display for Scene {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, ent) in self.entities.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+
+            writeln!(f, "entity {} {{", EntityName(&ent.name))?;
+            for (name, value) in &ent.components {
+                write!(f, "    {}", name)?;
+                value.fmt_payload(f)?;
+                writeln!(f)?;
+            }
+            writeln!(f, "}}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    String(String),
+    Int(i128),
+    Float(f64),
+    Symbol(char),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "{}", ident),
+            Token::String(string) => write!(f, "{:?}", string),
+            Token::Int(int) => write!(f, "{}", int),
+            Token::Float(float) => write!(f, "{:?}", float),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: String) -> SceneError {
+        SceneError::Parse {
+            line: self.line,
+            column: self.column,
+            message,
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), SceneError> {
+        while let Some(c) = self.chars.peek().copied() {
+            if c.is_whitespace() {
+                self.bump();
+            } else if c == '/' {
+                self.bump();
+                if self.bump() != Some('/') {
+                    return Err(self.error("Expected a comment.".to_string()));
+                }
+                while !matches!(self.bump(), Some('\n') | None) {}
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the next token along with its line and column.
+    fn next_token(&mut self) -> Result<Option<(Token, usize, usize)>, SceneError> {
+        self.skip_whitespace()?;
+        let (line, column) = (self.line, self.column);
+        let c = match self.bump() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+
+        let token = match c {
+            '(' | ')' | '{' | '}' | '[' | ']' | ':' | ',' | '@' => Token::Symbol(c),
+            '"' => Token::String(self.string()?),
+            c if c.is_ascii_digit() || c == '-' => self.number(c)?,
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(c) = self.chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    self.column += 1;
+                    ident.push(c);
+                }
+                Token::Ident(ident)
+            }
+            _ => return Err(self.error(format!("Unexpected character {:?}.", c))),
+        };
+
+        Ok(Some((token, line, column)))
+    }
+
+    fn string(&mut self) -> Result<String, SceneError> {
+        let mut string = String::new();
+        loop {
+            let c = match self.bump() {
+                Some('"') => return Ok(string),
+                Some('\\') => match self.bump() {
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('0') => '\0',
+                    Some('u') => self.unicode_escape()?,
+                    Some(c @ ('\\' | '"' | '\'')) => c,
+                    _ => return Err(self.error("Invalid escape sequence.".to_string())),
+                },
+                Some(c) => c,
+                None => return Err(self.error("Unterminated string.".to_string())),
+            };
+            string.push(c);
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, SceneError> {
+        if self.bump() != Some('{') {
+            return Err(self.error("Invalid unicode escape.".to_string()));
+        }
+
+        let mut hex = String::new();
+        loop {
+            match self.bump() {
+                Some('}') => break,
+                Some(c) if c.is_ascii_hexdigit() => hex.push(c),
+                _ => return Err(self.error("Invalid unicode escape.".to_string())),
+            }
+        }
+
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("Invalid unicode escape.".to_string()))
+    }
+
+    fn number(&mut self, first: char) -> Result<Token, SceneError> {
+        let mut number = first.to_string();
+        while let Some(c) = self.chars.peek().copied() {
+            let exponent_sign =
+                (c == '-' || c == '+') && matches!(number.chars().last(), Some('e' | 'E'));
+            if !(c.is_ascii_alphanumeric() || c == '.' || c == '_' || exponent_sign) {
+                break;
+            }
+            self.bump();
+            number.push(c);
+        }
+
+        let number = number.replace('_', "");
+        let is_float = number.contains(['.', 'e', 'E']) || number.ends_with("inf");
+        let token = if is_float {
+            number.parse().ok().map(Token::Float)
+        } else {
+            number.parse().ok().map(Token::Int)
+        };
+        token.ok_or_else(|| self.error(format!("Invalid number {}.", number)))
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize, usize)>,
+    position: usize,
+    end: (usize, usize),
+}
+
+impl Parser {
+    fn new(text: &str) -> Result<Self, SceneError> {
+        let mut lexer = Lexer {
+            chars: text.chars().peekable(),
+            line: 1,
+            column: 1,
+        };
+
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token()? {
+            tokens.push(token);
+        }
+
+        Ok(Self {
+            tokens,
+            position: 0,
+            end: (lexer.line, lexer.column),
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _, _)| token)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&Token> {
+        self.tokens
+            .get(self.position + n)
+            .map(|(token, _, _)| token)
+    }
+
+    fn error(&self, message: String) -> SceneError {
+        let (line, column) = self
+            .tokens
+            .get(self.position)
+            .map_or(self.end, |(_, line, column)| (*line, *column));
+        SceneError::Parse {
+            line,
+            column,
+            message,
+        }
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, SceneError> {
+        match self.tokens.get(self.position) {
+            Some((token, _, _)) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => Err(self.error(format!("Expected {}, found the end of the file.", expected))),
+        }
+    }
+
+    fn unexpected<T>(&mut self, expected: &str) -> Result<T, SceneError> {
+        self.position -= 1;
+        let found = self.peek().unwrap().to_string();
+        Err(self.error(format!("Expected {}, found {}.", expected, found)))
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), SceneError> {
+        let expected = format!("'{}'", symbol);
+        match self.next(&expected)? {
+            Token::Symbol(c) if c == symbol => Ok(()),
+            _ => self.unexpected(&expected),
+        }
+    }
+
+    /// Consumes the symbol if it is next.
+    fn eat(&mut self, symbol: char) -> bool {
+        let found = self.peek() == Some(&Token::Symbol(symbol));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn scene(&mut self) -> Result<Scene, SceneError> {
+        let mut scene = Scene::default();
+        while self.peek().is_some() {
+            match self.next("entity")? {
+                Token::Ident(ident) if ident == "entity" => {}
+                _ => return self.unexpected("entity"),
+            }
+
+            let name = self.entity_name()?;
+            self.expect('{')?;
+            let mut components = Vec::new();
+            while !self.eat('}') {
+                let component = match self.next("a component")? {
+                    Token::Ident(ident) => ident,
+                    _ => return self.unexpected("a component"),
+                };
+                components.push((component, self.payload()?));
+            }
+
+            scene.entities.push(SceneEntity { name, components });
+        }
+
+        Ok(scene)
+    }
+
+    fn entity_name(&mut self) -> Result<String, SceneError> {
+        match self.next("an entity name")? {
+            Token::Ident(name) | Token::String(name) => Ok(name),
+            _ => self.unexpected("an entity name"),
+        }
+    }
+
+    /// Parses the optional value after the name of a component or variant.
+    fn payload(&mut self) -> Result<Value, SceneError> {
+        match self.peek() {
+            Some(Token::Symbol('(')) => self.group(),
+            _ => Ok(Value::Unit),
+        }
+    }
+
+    /// Parses a unit, tuple or struct value.
+    fn group(&mut self) -> Result<Value, SceneError> {
+        self.expect('(')?;
+        if self.eat(')') {
+            return Ok(Value::Unit);
+        }
+
+        let is_struct = matches!(
+            (self.peek(), self.peek_nth(1)),
+            (Some(Token::Ident(_)), Some(Token::Symbol(':')))
+        );
+        if is_struct {
+            let fields = self.separated(')', |parser| {
+                let name = match parser.next("a field")? {
+                    Token::Ident(name) => name,
+                    _ => return parser.unexpected("a field"),
+                };
+                parser.expect(':')?;
+                Ok((name, parser.value()?))
+            })?;
+            Ok(Value::Struct(fields))
+        } else {
+            Ok(Value::Tuple(self.separated(')', Self::value)?))
+        }
+    }
+
+    /// Parses comma-separated items up to the closing symbol, allowing a trailing comma.
+    fn separated<T>(
+        &mut self,
+        close: char,
+        mut item: impl FnMut(&mut Self) -> Result<T, SceneError>,
+    ) -> Result<Vec<T>, SceneError> {
+        let mut items = Vec::new();
+        while !self.eat(close) {
+            items.push(item(self)?);
+            if !self.eat(',') {
+                self.expect(close)?;
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    fn value(&mut self) -> Result<Value, SceneError> {
+        match self.next("a value")? {
+            Token::Int(int) => Ok(Value::Int(int)),
+            Token::Float(float) => Ok(Value::Float(float)),
+            Token::String(string) => Ok(Value::String(string)),
+            Token::Symbol('@') => Ok(Value::Entity(self.entity_name()?)),
+            Token::Symbol('[') => Ok(Value::List(self.separated(']', Self::value)?)),
+            Token::Symbol('(') => {
+                self.position -= 1;
+                self.group()
+            }
+            Token::Ident(ident) if ident == "true" => Ok(Value::Bool(true)),
+            Token::Ident(ident) if ident == "false" => Ok(Value::Bool(false)),
+            Token::Ident(ident) => Ok(Value::Variant(ident, Box::new(self.payload()?))),
+            _ => self.unexpected("a value"),
+        }
+    }
+}
+
+type ApplyFn = Box<dyn FnOnce(&mut Registry, Entity)>;
+type WriteFn = Box<dyn Fn(&Registry, Entity, &EntityNames) -> Option<Value>>;
+type ConvertFn = Box<dyn Fn(&Value, &EntityNames) -> Result<ApplyFn, SceneError>>;
+
+struct SceneType {
+    name: &'static str,
+    id: usize,
+    write: WriteFn,
+    convert: ConvertFn,
+    remove: fn(&mut Registry, Entity),
+}
+
+/// Entities instantiated from a scene, along with the components the scene assigned to them.
+#[derive(Debug, Default)]
+pub struct SceneInstance {
+    names: EntityNames,
+    components: HashMap<Entity, Vec<usize>>,
+}
+
+impl SceneInstance {
+    pub fn names(&self) -> &EntityNames {
+        &self.names
+    }
+
+    pub fn entity(&self, name: &str) -> Option<Entity> {
+        self.names.entity(name)
+    }
+}
+
+/// Set of component types read from and written to scene files, by name.  [Transformation] is registered by default,
+/// as well as [Parent], which attaches entities through [Registry::set_parent].
+pub struct SceneFormat {
+    types: Vec<SceneType>,
+}
+
+impl Default for SceneFormat {
+    fn default() -> Self {
+        let mut format = Self { types: Vec::new() };
+        format.register::<Transformation>("Transformation");
+        format.register_with::<Parent>(
+            "Parent",
+            |reg, ent, parent| {
+                let attached = reg.set_parent(ent, parent.get());
+                debug_assert!(attached, "Parents are checked before the scene is applied.");
+            },
+            |reg, ent| {
+                reg.clear_parent(ent);
+            },
+        );
+        format
+    }
+}
+
+impl SceneFormat {
+    /// Registers a component type under the name scene files refer to it by.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another type is registered with the same name.
+    pub fn register<T: Component + SceneValue>(&mut self, name: &'static str) -> &mut Self {
+        self.register_with::<T>(
+            name,
+            |reg, ent, component| {
+                reg.assign_component(ent, component);
+            },
+            |reg, ent| {
+                reg.remove_component::<T>(ent);
+            },
+        )
+    }
+
+    /// Writes every entity of the registry, along with its registered components.  Entities are named after their
+    /// [Name] component.
+    pub fn write(&self, reg: &Registry) -> String {
+        let names = EntityNames::from_registry(reg);
+        let entities = reg
+            .iter_entities()
+            .map(|ent| SceneEntity {
+                name: names.name(ent).unwrap().to_string(),
+                components: self
+                    .types
+                    .iter()
+                    .filter_map(|tp| Some((tp.name.to_string(), (tp.write)(reg, ent, &names)?)))
+                    .collect(),
+            })
+            .collect();
+
+        Scene { entities }.to_string()
+    }
+
+    pub fn write_to_file(&self, reg: &Registry, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        fs::write(path, self.write(reg))?;
+        Ok(())
+    }
+
+    /// Instantiates the scene into the registry.
+    pub fn load(&self, text: &str, reg: &mut Registry) -> Result<SceneInstance, SceneError> {
+        let mut instance = SceneInstance::default();
+        self.apply(&Scene::parse(text)?, reg, &mut instance)?;
+        Ok(instance)
+    }
+
+    pub fn load_from_file(
+        &self,
+        path: impl AsRef<Path>,
+        reg: &mut Registry,
+    ) -> Result<SceneInstance, SceneError> {
+        self.load(&fs::read_to_string(path)?, reg)
+    }
+
+    /// Applies the scene over an instance of a previous version of it.  Entities of the instance are matched to the
+    /// entities of the scene by name.  Entities and registered components the scene no longer declares are destroyed
+    /// and removed, while other components are left as is.
+    ///
+    /// Nothing is applied if an error occurs.
+    pub fn apply(
+        &self,
+        scene: &Scene,
+        reg: &mut Registry,
+        instance: &mut SceneInstance,
+    ) -> Result<(), SceneError> {
+        let mut declared = HashSet::new();
+        for scene_ent in &scene.entities {
+            if !declared.insert(scene_ent.name.as_str()) {
+                return Err(SceneError::DuplicateEntity(scene_ent.name.clone()));
+            }
+        }
+
+        let mut names = EntityNames::default();
+        let mut created = Vec::new();
+        for scene_ent in &scene.entities {
+            let ent = match instance.entity(&scene_ent.name) {
+                Some(ent) if reg.is_alive(ent) => ent,
+                _ => {
+                    let ent = reg.create_entity();
+                    created.push(ent);
+                    ent
+                }
+            };
+            names.insert(scene_ent.name.clone(), ent);
+        }
+
+        let converted = match self.convert(scene, &names, reg, instance) {
+            Ok(converted) => converted,
+            Err(err) => {
+                for ent in created {
+                    reg.destroy_entity(&ent);
+                }
+                return Err(err);
+            }
+        };
+
+        // Stale components are removed, and declared parents detached, before anything is applied, so that attaching
+        // entities in any order never forms a cycle on the way
+        let mut components = HashMap::new();
+        for (scene_ent, converted) in scene.entities.iter().zip(&converted) {
+            let ent = names.entity(&scene_ent.name).unwrap();
+            let types: Vec<usize> = converted.iter().map(|(idx, _)| *idx).collect();
+            for idx in instance.components.get(&ent).into_iter().flatten() {
+                if !types.contains(idx) {
+                    (self.types[*idx].remove)(reg, ent);
+                }
+            }
+            if types.iter().any(|idx| self.types[*idx].id == Parent::id()) {
+                reg.clear_parent(ent);
+            }
+            components.insert(ent, types);
+        }
+
+        for (scene_ent, converted) in scene.entities.iter().zip(converted) {
+            let ent = names.entity(&scene_ent.name).unwrap();
+            if reg.get_component_ref::<Name>(ent).map(|name| &name.0) != Some(&scene_ent.name) {
+                reg.assign_component(ent, Name(scene_ent.name.clone()));
+            }
+            for (_, apply) in converted {
+                apply(reg, ent);
+            }
+        }
+
+        // Removed entities are destroyed last, so that entities attached to them were detached by now
+        for (name, ent) in instance.names.iter() {
+            if names.entity(name).is_none() {
+                reg.destroy_entity(&ent);
+            }
+        }
+
+        instance.names = names;
+        instance.components = components;
+        Ok(())
+    }
+
+    fn convert(
+        &self,
+        scene: &Scene,
+        names: &EntityNames,
+        reg: &Registry,
+        instance: &SceneInstance,
+    ) -> Result<Vec<Vec<(usize, ApplyFn)>>, SceneError> {
+        let converted: Vec<Vec<(usize, ApplyFn)>> = scene
+            .entities
+            .iter()
+            .map(|scene_ent| {
+                scene_ent
+                    .components
+                    .iter()
+                    .map(|(component, value)| {
+                        let idx = self
+                            .types
+                            .iter()
+                            .position(|tp| tp.name == component)
+                            .ok_or_else(|| SceneError::UnknownComponent(component.clone()))?;
+                        let apply = (self.types[idx].convert)(value, names).map_err(|err| {
+                            SceneError::InvalidComponent {
+                                entity: scene_ent.name.clone(),
+                                component: component.clone(),
+                                source: Box::new(err),
+                            }
+                        })?;
+                        Ok((idx, apply))
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, SceneError>>()?;
+
+        self.check_parents(scene, names, reg, instance)?;
+        Ok(converted)
+    }
+
+    /// Checks that the scene attaches its entities to alive entities other than themselves, and that the parents it
+    /// declares, along with those it leaves in place, do not form a cycle.
+    fn check_parents(
+        &self,
+        scene: &Scene,
+        names: &EntityNames,
+        reg: &Registry,
+        instance: &SceneInstance,
+    ) -> Result<(), SceneError> {
+        let parent_type = match self.types.iter().position(|tp| tp.id == Parent::id()) {
+            Some(idx) => idx,
+            None => return Ok(()),
+        };
+        let invalid = |ent: Entity, parent: Entity| SceneError::InvalidParent {
+            entity: names.name(ent).unwrap().to_string(),
+            parent: parent.to_value(names).to_string(),
+        };
+
+        let mut parents = HashMap::new();
+        for scene_ent in &scene.entities {
+            let ent = names.entity(&scene_ent.name).unwrap();
+            let declared = scene_ent
+                .components
+                .iter()
+                .find(|(component, _)| component == self.types[parent_type].name);
+            let parent = match declared {
+                Some((_, value)) => {
+                    let parent = Parent::from_value(value, names)?.get();
+                    if parent == ent || !reg.is_alive(parent) {
+                        return Err(invalid(ent, parent));
+                    }
+                    Some(parent)
+                }
+                // Parents previously assigned by the scene are cleared, others are left in place
+                None if instance
+                    .components
+                    .get(&ent)
+                    .map_or(false, |types| types.contains(&parent_type)) =>
+                {
+                    None
+                }
+                None => reg.parent(ent),
+            };
+            parents.insert(ent, parent);
+        }
+
+        for scene_ent in &scene.entities {
+            let ent = names.entity(&scene_ent.name).unwrap();
+            let mut visited = HashSet::new();
+            let mut ancestor = parents[&ent];
+            while let Some(current) = ancestor {
+                if current == ent {
+                    return Err(invalid(ent, parents[&ent].unwrap()));
+                }
+                if !visited.insert(current) {
+                    break;
+                }
+                ancestor = match parents.get(&current) {
+                    Some(parent) => *parent,
+                    None => reg.parent(current),
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    fn register_with<T: Component + SceneValue>(
+        &mut self,
+        name: &'static str,
+        apply: fn(&mut Registry, Entity, T),
+        remove: fn(&mut Registry, Entity),
+    ) -> &mut Self {
+        assert!(
+            !self.types.iter().any(|tp| tp.name == name),
+            "Component {} is registered more than once.",
+            name
+        );
+
+        self.types.push(SceneType {
+            name,
+            id: T::id(),
+            write: Box::new(|reg, ent, names| {
+                reg.get_component_ref::<T>(ent)
+                    .map(|component| component.to_value(names))
+            }),
+            convert: Box::new(move |value, names| {
+                let component = T::from_value(value, names)?;
+                let apply: ApplyFn = Box::new(move |reg, ent| apply(reg, ent, component));
+                Ok(apply)
+            }),
+            remove,
+        });
+
+        self
+    }
+}
+
+/// Scene file applied to a live registry, and re-applied whenever the file is modified.
+pub struct SceneWatcher {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+    instance: SceneInstance,
+}
+
+impl SceneWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            stamp: None,
+            instance: SceneInstance::default(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn instance(&self) -> &SceneInstance {
+        &self.instance
+    }
+
+    /// Applies the scene file if it was modified since the last poll, returning whether it was applied.  The first
+    /// poll always applies it.  Meant to be called once per frame, which `AppBuilder::with_scene` of tempest-core
+    /// does.
+    ///
+    /// A file that fails to load is not retried until it is modified again, and leaves the previously applied scene
+    /// in place.
+    pub fn poll(&mut self, format: &SceneFormat, reg: &mut Registry) -> Result<bool, SceneError> {
+        let metadata = fs::metadata(&self.path)?;
+        let stamp = (metadata.modified()?, metadata.len());
+        if self.stamp == Some(stamp) {
+            return Ok(false);
+        }
+
+        self.stamp = Some(stamp);
+        let scene = Scene::parse(&fs::read_to_string(&self.path)?)?;
+        format.apply(&scene, reg, &mut self.instance)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::StorageKind;
+
+    #[derive(Component, SceneValue, Clone, Debug, PartialEq)]
+    struct Health {
+        current: u32,
+        max: u32,
+    }
+
+    #[derive(Component, SceneValue, Clone, Debug, PartialEq)]
+    struct Target(Option<Entity>);
+
+    #[derive(Component, SceneValue, Clone, Debug, PartialEq)]
+    struct Hostile;
+
+    #[derive(Component, SceneValue, Clone, Debug, PartialEq)]
+    enum State {
+        Idle,
+        Walking { speed: f32, path: Vec<Vec3> },
+        Named(String),
+    }
+
+    const SCENE: &str = r#"
+        // the player
+        entity player {
+            Transformation(position: (0.0, 1.5, 0.0), rotation: (0.0, 0.0, 0.0), scale: (1.0, 1.0, 1.0))
+            Health(current: 10, max: 10,)
+            Target(Some(@"enemy 1"))
+            State(Walking(speed: 0.1, path: [(1.0, 0.0, 0.0), (0.0, -2e1, 0.0)]))
+        }
+
+        entity "enemy 1" {
+            Parent(@player)
+            Hostile
+            State(Named("bob \"the\" enemy"))
+            Target(None)
+        }
+    "#;
+
+    fn format() -> SceneFormat {
+        let mut format = SceneFormat::default();
+        format
+            .register::<Health>("Health")
+            .register::<Target>("Target")
+            .register::<Hostile>("Hostile")
+            .register::<State>("State");
+        format
+    }
+
+    fn load_and_write(kind: StorageKind) {
+        let format = format();
+        let mut reg = Registry::new(kind);
+        let instance = format.load(SCENE, &mut reg).unwrap();
+        let player = instance.entity("player").unwrap();
+        let enemy = instance.entity("enemy 1").unwrap();
+
+        assert_eq!(
+            reg.get_component_ref::<Transformation>(player)
+                .unwrap()
+                .position,
+            Vec3::new(0.0, 1.5, 0.0)
+        );
+        assert_eq!(
+            reg.get_component::<Health>(player),
+            Some(Health {
+                current: 10,
+                max: 10
+            })
+        );
+        assert_eq!(
+            reg.get_component::<Target>(player),
+            Some(Target(Some(enemy)))
+        );
+        assert_eq!(
+            reg.get_component::<State>(player),
+            Some(State::Walking {
+                speed: 0.1,
+                path: vec![Vec3::X, Vec3::new(0.0, -20.0, 0.0)]
+            })
+        );
+        assert_eq!(reg.parent(enemy), Some(player));
+        assert!(reg.has_component::<Hostile>(enemy));
+        assert_eq!(
+            reg.get_component::<State>(enemy),
+            Some(State::Named("bob \"the\" enemy".to_string()))
+        );
+        assert_eq!(
+            reg.get_component::<Name>(enemy),
+            Some(Name("enemy 1".to_string()))
+        );
+
+        // writing the registry back gives the same scene
+        let written = format.write(&reg);
+        assert_eq!(Scene::parse(&written).unwrap(), {
+            let mut scene = Scene::parse(SCENE).unwrap();
+            for ent in &mut scene.entities {
+                ent.components
+                    .sort_by_key(|(name, _)| format.types.iter().position(|tp| tp.name == name));
+            }
+            scene
+        });
+
+        let mut copy = Registry::new(kind);
+        let instance = format.load(&written, &mut copy).unwrap();
+        assert_eq!(format.write(&copy), written);
+        assert_eq!(
+            copy.get_component::<Target>(instance.entity("player").unwrap()),
+            Some(Target(instance.entity("enemy 1")))
+        );
+    }
+
+    #[test]
+    fn test_load_and_write() {
+        load_and_write(StorageKind::Sparse);
+        load_and_write(StorageKind::Archetype);
+    }
+
+    #[test]
+    fn test_errors() {
+        let format = format();
+        let mut reg = Registry::default();
+
+        let err = format
+            .load("entity a {\n    Health(current: 1,, max: 2)\n}", &mut reg)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            SceneError::Parse {
+                line: 2,
+                column: 23,
+                ..
+            }
+        ));
+
+        assert!(matches!(
+            format.load("entity a {} entity a {}", &mut reg),
+            Err(SceneError::DuplicateEntity(name)) if name == "a"
+        ));
+        assert!(matches!(
+            format.load("entity a { Mana(1) }", &mut reg),
+            Err(SceneError::UnknownComponent(name)) if name == "Mana"
+        ));
+
+        let err = format
+            .load("entity a { Health(current: -1, max: 2) }", &mut reg)
+            .unwrap_err();
+        assert!(matches!(err, SceneError::InvalidComponent { .. }));
+        assert_eq!(err.to_string(), "a.Health: -1 does not fit in u32.");
+
+        let err = format
+            .load("entity a { Target(Some(@b)) }", &mut reg)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "a.Target: Entity b is not declared.");
+
+        // failed loads leave nothing behind
+        assert_eq!(reg.num_entities(), 0);
+    }
+
+    #[test]
+    fn test_invalid_parents() {
+        let format = format();
+        let mut reg = Registry::default();
+
+        let err = format
+            .load("entity a { Parent(@a) }", &mut reg)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Entity a cannot be attached to @a.");
+        let err = format
+            .load(
+                "entity a { Parent(@b) } entity b { Parent(@c) } entity c { Parent(@a) }",
+                &mut reg,
+            )
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Entity a cannot be attached to @b.");
+        let err = format
+            .load("entity a { Parent(()) }", &mut reg)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Entity a cannot be attached to ().");
+        assert_eq!(reg.num_entities(), 0);
+
+        // attaching to a descendant is fine when the descendant is detached by the same scene
+        let mut instance = format
+            .load("entity a { Parent(@b) } entity b {}", &mut reg)
+            .unwrap();
+        let [a, b] = ["a", "b"].map(|name| instance.entity(name).unwrap());
+        let swapped = Scene::parse("entity b { Parent(@a) } entity a {}").unwrap();
+        format.apply(&swapped, &mut reg, &mut instance).unwrap();
+        assert_eq!(reg.parent(b), Some(a));
+        assert_eq!(reg.parent(a), None);
+
+        // parents the scene leaves in place count as well
+        let mut instance = format.load("entity c {} entity d {}", &mut reg).unwrap();
+        let [c, d] = ["c", "d"].map(|name| instance.entity(name).unwrap());
+        let outside = reg.create_entity();
+        reg.set_parent(outside, c);
+        reg.set_parent(d, outside);
+        let cycle = Scene::parse("entity c { Parent(@d) } entity d {}").unwrap();
+        let err = format.apply(&cycle, &mut reg, &mut instance).unwrap_err();
+        assert_eq!(err.to_string(), "Entity c cannot be attached to @d.");
+        assert_eq!(reg.parent(c), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_hot_reload() {
+        let path = std::env::temp_dir().join(format!("tempest-scene-{}.scene", std::process::id()));
+        let format = format();
+        let mut reg = Registry::default();
+        let mut watcher = SceneWatcher::new(&path);
+
+        fs::write(&path, SCENE).unwrap();
+        assert!(watcher.poll(&format, &mut reg).unwrap());
+        assert!(!watcher.poll(&format, &mut reg).unwrap());
+        let player = watcher.instance().entity("player").unwrap();
+        let enemy = watcher.instance().entity("enemy 1").unwrap();
+        let unrelated = reg.create_entity();
+
+        // the enemy is removed, and the player loses its state and changes health
+        fs::write(
+            &path,
+            "entity player {\n    Health(current: 5, max: 10)\n}\nentity other {}\n",
+        )
+        .unwrap();
+        assert!(watcher.poll(&format, &mut reg).unwrap());
+        assert_eq!(watcher.instance().entity("player"), Some(player));
+        assert!(!reg.is_alive(enemy));
+        assert!(reg.is_alive(unrelated));
+        assert!(reg.is_alive(watcher.instance().entity("other").unwrap()));
+        assert_eq!(
+            reg.get_component::<Health>(player),
+            Some(Health {
+                current: 5,
+                max: 10
+            })
+        );
+        assert!(!reg.has_component::<State>(player));
+        assert!(!reg.has_component::<Target>(player));
+
+        // invalid files keep the previous scene
+        fs::write(&path, "entity player { Health(current: \"5\", max: 10) }").unwrap();
+        assert!(watcher.poll(&format, &mut reg).is_err());
+        assert!(!watcher.poll(&format, &mut reg).unwrap());
+        assert_eq!(
+            reg.get_component::<Health>(player),
+            Some(Health {
+                current: 5,
+                max: 10
+            })
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,944 @@
+use std::{
+    any::type_name,
+    collections::HashMap,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use super::{
+    access::Access,
+    commands::Commands,
+    component::Component,
+    event::{Event, EventWriter, Events},
+    graph::{Graph, NodeIndex},
+    registry::{QueryBorrow, RegistryQuery},
+    resource::{resource_id, Res, ResMut, Resource},
+    world::{World, WorldCell},
+};
+
+type SystemFn = Box<dyn FnMut(&mut SystemContext) + Send>;
+
+/// Named unit of work run by a [Schedule].  A system declares the components it reads and writes, which decides the
+/// systems it may run in parallel with, and how it is ordered relative to other systems.
+pub struct System {
+    name: String,
+    run: SystemFn,
+    access: Access,
+    resources: Access,
+    before: Vec<String>,
+    after: Vec<String>,
+    commands: Commands,
+    last_run: u64,
+}
+
+impl System {
+    pub fn new<F: FnMut(&mut SystemContext) + Send + 'static>(name: &str, run: F) -> Self {
+        Self {
+            name: name.to_owned(),
+            run: Box::new(run),
+            access: Access::default(),
+            resources: Access::default(),
+            before: Vec::new(),
+            after: Vec::new(),
+            commands: Commands::default(),
+            last_run: 0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn access(&self) -> &Access {
+        &self.access
+    }
+
+    /// Resources read and written by the system, identified by their [resource_id].
+    pub fn resource_access(&self) -> &Access {
+        &self.resources
+    }
+
+    pub fn reads<T: Component>(mut self) -> Self {
+        self.access.read(T::id());
+        self
+    }
+
+    pub fn writes<T: Component>(mut self) -> Self {
+        self.access.write(T::id());
+        self
+    }
+
+    pub fn reads_resource<T: Resource>(mut self) -> Self {
+        self.resources.read(resource_id::<T>());
+        self
+    }
+
+    pub fn writes_resource<T: Resource>(mut self) -> Self {
+        self.resources.write(resource_id::<T>());
+        self
+    }
+
+    /// Declares reading the events of the type, see [SystemContext::events].
+    pub fn reads_events<T: Event>(self) -> Self {
+        self.reads_resource::<Events<T>>()
+    }
+
+    /// Declares sending events of the type, see [SystemContext::event_writer].
+    pub fn writes_events<T: Event>(self) -> Self {
+        self.writes_resource::<Events<T>>()
+    }
+
+    /// Declares every component read or written by the query.
+    pub fn query<Q: for<'r> RegistryQuery<'r>>(mut self) -> Self {
+        Q::access(&mut self.access);
+        self
+    }
+
+    /// Requires the system to run before the system with the provided name.
+    pub fn before(mut self, system: &str) -> Self {
+        self.before.push(system.to_owned());
+        self
+    }
+
+    /// Requires the system to run after the system with the provided name.
+    pub fn after(mut self, system: &str) -> Self {
+        self.after.push(system.to_owned());
+        self
+    }
+}
+
+/// Data available to a system while it runs.
+pub struct SystemContext<'a> {
+    world: &'a WorldCell<'a>,
+    commands: &'a mut Commands,
+    access: &'a Access,
+    resources: &'a Access,
+    name: &'a str,
+    last_run: u64,
+    this_run: u64,
+}
+
+impl<'a> SystemContext<'a> {
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// Tick at which the system last started running.  Queries of the system report the components added or changed
+    /// since then.
+    pub fn last_run(&self) -> u64 {
+        self.last_run
+    }
+
+    /// Borrows the components of the query.  Components it reaches mutably are marked as changed at the tick the
+    /// system started running at, so that the next run of the system does not report its own changes, even when
+    /// other systems started running meanwhile.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the query accesses components the system did not declare.
+    pub fn query<Q: for<'r> RegistryQuery<'r>>(&self) -> QueryBorrow<'a, Q> {
+        let mut access = Access::default();
+        Q::access(&mut access);
+        assert!(
+            access.is_covered_by(self.access),
+            "System {} accesses components it did not declare.",
+            self.name
+        );
+
+        self.world
+            .query::<Q>()
+            .since(self.last_run)
+            .change_tick(self.this_run)
+    }
+
+    /// Borrows a resource, returning `None` if the world has no resource of the type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system did not declare reading or writing the resource.
+    pub fn resource<T: Resource>(&self) -> Option<Res<'a, T>> {
+        let mut access = Access::default();
+        access.read(resource_id::<T>());
+        assert!(
+            access.is_covered_by(self.resources),
+            "System {} reads a resource it did not declare.",
+            self.name
+        );
+
+        self.world.resource::<T>()
+    }
+
+    /// Borrows a resource mutably, returning `None` if the world has no resource of the type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system did not declare writing the resource.
+    pub fn resource_mut<T: Resource>(&self) -> Option<ResMut<'a, T>> {
+        let mut access = Access::default();
+        access.write(resource_id::<T>());
+        assert!(
+            access.is_covered_by(self.resources),
+            "System {} writes a resource it did not declare.",
+            self.name
+        );
+
+        self.world.resource_mut::<T>()
+    }
+
+    /// Borrows the events of the type, to be read with an [EventReader](crate::event::EventReader) kept by the
+    /// system.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the event type is not registered in the world, or if the system did not declare reading it.
+    pub fn events<T: Event>(&self) -> Res<'a, Events<T>> {
+        self.resource::<Events<T>>()
+            .unwrap_or_else(|| panic!("Event {} is not registered.", type_name::<T>()))
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the event type is not registered in the world, or if the system did not declare writing it.
+    pub fn event_writer<T: Event>(&self) -> EventWriter<'a, T> {
+        let events = self
+            .resource_mut::<Events<T>>()
+            .unwrap_or_else(|| panic!("Event {} is not registered.", type_name::<T>()));
+        EventWriter::new(events)
+    }
+
+    /// Command buffer of the system, applied once every system of the schedule has run.
+    pub fn commands(&mut self) -> &mut Commands {
+        self.commands
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ScheduleError {
+    /// Several systems share the same name.
+    DuplicateSystem(String),
+
+    /// A system is ordered relative to a system that is not part of the schedule.
+    UnknownSystem { system: String, dependency: String },
+
+    /// The ordering constraints form a cycle, listed in order and ending with the system it started from.
+    Cycle(Vec<String>),
+}
+
+impl Display for ScheduleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateSystem(name) => write!(f, "System {} is declared more than once.", name),
+            Self::UnknownSystem { system, dependency } => write!(
+                f,
+                "System {} is ordered relative to unknown system {}.",
+                system, dependency
+            ),
+            Self::Cycle(path) => {
+                write!(f, "Systems are ordered in a cycle: {}.", path.join(" -> "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+/// Pair of systems with conflicting accesses, but no ordering constraint between them.  Such systems run in the order
+/// they were added to the schedule.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Ambiguity {
+    pub first: String,
+    pub second: String,
+    pub conflict: Conflict,
+}
+
+/// Data accessed by two systems, and written by at least one of them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Conflict {
+    Component(usize),
+    Resource(usize),
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Component(id) => write!(f, "component {}", id),
+            Self::Resource(id) => write!(f, "resource {}", id),
+        }
+    }
+}
+
+/// Set of systems run together.  Systems run as soon as every system ordered before them has completed, in parallel
+/// with any other system they do not conflict with.
+#[derive(Default)]
+pub struct Schedule {
+    systems: Vec<System>,
+    graph: Graph<String>,
+    dependencies: Vec<usize>,
+    order: Vec<NodeIndex>,
+    ambiguities: Vec<Ambiguity>,
+    built: bool,
+    thread_pool: Option<rayon::ThreadPool>,
+}
+
+impl Schedule {
+    /// Runs the systems on the provided thread pool instead of the global one.
+    pub fn with_thread_pool(mut self, pool: rayon::ThreadPool) -> Self {
+        self.thread_pool = Some(pool);
+        self
+    }
+
+    pub fn add_system(&mut self, system: System) -> &mut Self {
+        self.systems.push(system);
+        self.built = false;
+        self
+    }
+
+    pub fn systems(&self) -> impl Iterator<Item = &System> {
+        self.systems.iter()
+    }
+
+    /// Dependency graph of the systems as of the last build, including the edges ordering ambiguous systems.  Nodes
+    /// are named after their system, and share its index.
+    pub fn graph(&self) -> &Graph<String> {
+        &self.graph
+    }
+
+    /// Ambiguities found by the last build of the schedule.
+    pub fn ambiguities(&self) -> &[Ambiguity] {
+        &self.ambiguities
+    }
+
+    /// Builds the dependency graph of the systems, returning the ambiguous orderings it contains.
+    pub fn build(&mut self) -> Result<&[Ambiguity], ScheduleError> {
+        let mut names = HashMap::new();
+        for (idx, system) in self.systems.iter().enumerate() {
+            if names.insert(system.name.as_str(), idx).is_some() {
+                return Err(ScheduleError::DuplicateSystem(system.name.clone()));
+            }
+        }
+
+        let count = self.systems.len();
+        let mut graph = Graph::new();
+        for system in &self.systems {
+            graph.add_node(system.name.clone());
+        }
+
+        for (idx, system) in self.systems.iter().enumerate() {
+            let idx = NodeIndex::new(idx);
+            let lookup = |dependency: &String| match names.get(dependency.as_str()) {
+                Some(other) => Ok(NodeIndex::new(*other)),
+                None => Err(ScheduleError::UnknownSystem {
+                    system: system.name.clone(),
+                    dependency: dependency.clone(),
+                }),
+            };
+
+            for before in &system.before {
+                graph.add_edge(idx, lookup(before)?);
+            }
+            for after in &system.after {
+                graph.add_edge(lookup(after)?, idx);
+            }
+        }
+
+        let order = graph.topo_sort().map_err(|cycle| {
+            ScheduleError::Cycle(
+                cycle
+                    .path()
+                    .iter()
+                    .map(|idx| graph.node(*idx).clone())
+                    .collect(),
+            )
+        })?;
+
+        // Conflicting systems without a path between them are ordered as they were added, which never closes a cycle
+        let mut reachable = reachability(&graph, &order);
+        let mut ambiguities = Vec::new();
+        for first in 0..count {
+            for second in first + 1..count {
+                if reachable[first][second] || reachable[second][first] {
+                    continue;
+                }
+
+                let (lhs, rhs) = (&self.systems[first], &self.systems[second]);
+                let conflict = lhs
+                    .access
+                    .find_conflict(&rhs.access)
+                    .map(Conflict::Component)
+                    .or_else(|| {
+                        lhs.resources
+                            .find_conflict(&rhs.resources)
+                            .map(Conflict::Resource)
+                    });
+                if let Some(conflict) = conflict {
+                    ambiguities.push(Ambiguity {
+                        first: lhs.name.clone(),
+                        second: rhs.name.clone(),
+                        conflict,
+                    });
+
+                    graph.add_edge(NodeIndex::new(first), NodeIndex::new(second));
+                    let reached = reachable[second].clone();
+                    for (source, row) in reachable.iter_mut().enumerate() {
+                        if source == first || row[first] {
+                            row[second] = true;
+                            merge(row, &reached);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.order = graph
+            .topo_sort()
+            .expect("Ordering ambiguities introduced a cycle.");
+        self.dependencies = graph.in_degrees();
+        self.graph = graph;
+        self.ambiguities = ambiguities;
+        self.built = true;
+
+        Ok(&self.ambiguities)
+    }
+
+    /// Runs every system of the schedule once, then applies the commands they recorded in the order of the schedule.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the schedule cannot be built, or if a system panics.
+    pub fn run(&mut self, world: &mut World) {
+        if !self.built {
+            if let Err(err) = self.build() {
+                panic!("{}", err);
+            }
+        }
+
+        {
+            let cell = world.cell();
+            let systems: Vec<_> = self.systems.iter_mut().map(Mutex::new).collect();
+            let remaining: Vec<_> = self
+                .dependencies
+                .iter()
+                .map(|count| AtomicUsize::new(*count))
+                .collect();
+
+            let execution = Execution {
+                cell: &cell,
+                systems: &systems,
+                remaining: &remaining,
+                graph: &self.graph,
+            };
+
+            let roots = self
+                .dependencies
+                .iter()
+                .enumerate()
+                .filter(|(_, count)| **count == 0)
+                .map(|(idx, _)| idx);
+
+            match &self.thread_pool {
+                Some(pool) => pool.scope(|scope| roots.for_each(|idx| execution.spawn(scope, idx))),
+                None => rayon::scope(|scope| roots.for_each(|idx| execution.spawn(scope, idx))),
+            }
+
+            // The commands must be seen as changes by the next run of every system
+            cell.registry().advance_tick();
+        }
+
+        for idx in &self.order {
+            self.systems[idx.index()]
+                .commands
+                .apply(world.entitites_mut());
+        }
+    }
+}
+
+/// State shared by the threads running a schedule.
+struct Execution<'a, 'w> {
+    cell: &'a WorldCell<'w>,
+    systems: &'a [Mutex<&'a mut System>],
+    remaining: &'a [AtomicUsize],
+    graph: &'a Graph<String>,
+}
+
+impl<'a, 'w> Execution<'a, 'w> {
+    fn spawn<'s>(&'s self, scope: &rayon::Scope<'s>, idx: usize) {
+        scope.spawn(move |scope| {
+            self.run_system(idx);
+
+            for dependent in self.graph.neighbors(NodeIndex::new(idx)) {
+                if self.remaining[dependent.index()].fetch_sub(1, Ordering::AcqRel) == 1 {
+                    self.spawn(scope, dependent.index());
+                }
+            }
+        });
+    }
+
+    fn run_system(&self, idx: usize) {
+        let mut system = self.systems[idx].lock().unwrap();
+        let system = &mut **system;
+        let this_run = self.cell.registry().advance_tick();
+
+        let mut ctx = SystemContext {
+            world: self.cell,
+            commands: &mut system.commands,
+            access: &system.access,
+            resources: &system.resources,
+            name: &system.name,
+            last_run: system.last_run,
+            this_run,
+        };
+        (system.run)(&mut ctx);
+
+        system.last_run = this_run;
+    }
+}
+
+/// Computes which nodes can be reached from every node, given a topological order of the graph.
+fn reachability(graph: &Graph<String>, order: &[NodeIndex]) -> Vec<Vec<bool>> {
+    let mut reachable = vec![vec![false; graph.len()]; graph.len()];
+
+    for node in order.iter().rev() {
+        for target in graph.neighbors(*node) {
+            let reached = reachable[target.index()].clone();
+            reachable[node.index()][target.index()] = true;
+            merge(&mut reachable[node.index()], &reached);
+        }
+    }
+
+    reachable
+}
+
+fn merge(reachable: &mut [bool], other: &[bool]) {
+    reachable
+        .iter_mut()
+        .zip(other)
+        .for_each(|(lhs, rhs)| *lhs |= *rhs);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::AtomicBool, Arc},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use tempest_ecs_macros::{Component, RegistryQuery};
+
+    use super::*;
+    use crate::event::EventReader;
+
+    #[derive(Component)]
+    struct Position(u32);
+
+    #[derive(Component)]
+    struct Velocity(u32);
+
+    #[derive(Component)]
+    struct Health;
+
+    #[derive(RegistryQuery)]
+    #[read_write(Position)]
+    #[read_only(Velocity)]
+    struct MovementQuery;
+
+    #[derive(RegistryQuery)]
+    #[read_only(Position)]
+    #[changed(Position)]
+    struct MovedQuery;
+
+    type Log = Arc<Mutex<Vec<&'static str>>>;
+
+    fn logging(log: &Log, name: &'static str) -> System {
+        let log = log.clone();
+        System::new(name, move |_| log.lock().unwrap().push(name))
+    }
+
+    fn thread_pool(threads: usize) -> rayon::ThreadPool {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_explicit_ordering() {
+        let log = Log::default();
+        let mut schedule = Schedule::default().with_thread_pool(thread_pool(4));
+        schedule
+            .add_system(logging(&log, "render").after("physics"))
+            .add_system(logging(&log, "physics").after("input"))
+            .add_system(logging(&log, "input").before("render"));
+
+        let mut world = World::default();
+        for _ in 0..3 {
+            schedule.run(&mut world);
+        }
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["input", "physics", "render"].repeat(3)
+        );
+    }
+
+    #[test]
+    fn test_schedule_errors() {
+        let mut schedule = Schedule::default();
+        schedule
+            .add_system(System::new("a", |_| {}))
+            .add_system(System::new("a", |_| {}));
+        assert_eq!(
+            schedule.build().err(),
+            Some(ScheduleError::DuplicateSystem("a".to_string()))
+        );
+
+        let mut schedule = Schedule::default();
+        schedule.add_system(System::new("a", |_| {}).after("b"));
+        assert_eq!(
+            schedule.build().err(),
+            Some(ScheduleError::UnknownSystem {
+                system: "a".to_string(),
+                dependency: "b".to_string()
+            })
+        );
+
+        let mut schedule = Schedule::default();
+        schedule
+            .add_system(System::new("a", |_| {}).before("b"))
+            .add_system(System::new("b", |_| {}).before("c"))
+            .add_system(System::new("c", |_| {}).before("b"))
+            .add_system(System::new("d", |_| {}).after("c"));
+        assert_eq!(
+            schedule.build().err(),
+            Some(ScheduleError::Cycle(
+                ["c", "b", "c"].map(str::to_string).to_vec()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_ambiguities() {
+        let log = Log::default();
+        let mut schedule = Schedule::default().with_thread_pool(thread_pool(4));
+        schedule
+            .add_system(logging(&log, "heal").writes::<Health>())
+            .add_system(logging(&log, "move").query::<MovementQuery>())
+            .add_system(logging(&log, "damage").writes::<Health>())
+            .add_system(logging(&log, "display").reads::<Health>().after("heal"))
+            .add_system(logging(&log, "track").reads::<Position>().after("move"));
+
+        let ambiguities = schedule.build().unwrap();
+        assert_eq!(
+            ambiguities,
+            [
+                Ambiguity {
+                    first: "heal".to_string(),
+                    second: "damage".to_string(),
+                    conflict: Conflict::Component(Health::id()),
+                },
+                Ambiguity {
+                    first: "damage".to_string(),
+                    second: "display".to_string(),
+                    conflict: Conflict::Component(Health::id()),
+                },
+            ]
+        );
+
+        // the implicit orderings are part of the dependency graph
+        let graph = schedule.graph();
+        assert!(graph.has_edge(NodeIndex::new(0), NodeIndex::new(2)));
+        assert!(graph.has_edge(NodeIndex::new(2), NodeIndex::new(3)));
+        assert!(graph.to_dot().contains("2 [label=\"damage\"];"));
+
+        let mut world = World::default();
+        schedule.run(&mut world);
+
+        let log = log.lock().unwrap();
+        let position = |name| log.iter().position(|entry| *entry == name).unwrap();
+        assert!(position("heal") < position("damage"));
+        assert!(position("damage") < position("display"));
+        assert!(position("move") < position("track"));
+    }
+
+    #[test]
+    fn test_parallel_execution() {
+        // Both systems only finish once the other one has started, which requires them to run at the same time
+        let started = Arc::new(AtomicUsize::new(0));
+        let overlapped = Arc::new(AtomicBool::new(true));
+        let rendezvous = |name| {
+            let started = started.clone();
+            let overlapped = overlapped.clone();
+            System::new(name, move |_| {
+                started.fetch_add(1, Ordering::SeqCst);
+                let deadline = Instant::now() + Duration::from_secs(5);
+                while started.load(Ordering::SeqCst) < 2 {
+                    if Instant::now() > deadline {
+                        overlapped.store(false, Ordering::SeqCst);
+                        return;
+                    }
+                    thread::yield_now();
+                }
+            })
+        };
+
+        let mut schedule = Schedule::default().with_thread_pool(thread_pool(2));
+        schedule
+            .add_system(rendezvous("move").query::<MovementQuery>())
+            .add_system(rendezvous("damage").writes::<Health>().reads::<Velocity>());
+        assert!(schedule.build().unwrap().is_empty());
+
+        schedule.run(&mut World::default());
+        assert!(overlapped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_systems_mutate_world() {
+        let mut world = World::default();
+        let registry = world.entitites_mut();
+        for i in 0..4 {
+            let ent = registry.create_entity();
+            registry.assign_component(ent, Position(i));
+            registry.assign_component(ent, Velocity(i % 2));
+        }
+
+        let moved = Arc::new(AtomicUsize::new(0));
+        let seen = moved.clone();
+
+        let mut schedule = Schedule::default().with_thread_pool(thread_pool(4));
+        schedule
+            .add_system(
+                System::new("move", |ctx| {
+                    let mut query = ctx.query::<MovementQuery>();
+                    for (velocity, position) in query.iter() {
+                        if velocity.0 > 0 {
+                            position.0 += velocity.0;
+                        }
+                    }
+                })
+                .query::<MovementQuery>(),
+            )
+            .add_system(
+                System::new("count", move |ctx| {
+                    let count = ctx.query::<MovedQuery>().iter().count();
+                    seen.store(count, Ordering::SeqCst);
+                })
+                .query::<MovedQuery>()
+                .after("move"),
+            )
+            .add_system(
+                System::new("spawn", |ctx| {
+                    let ent = ctx.commands().spawn();
+                    ctx.commands().insert(ent, Position(100));
+                })
+                .before("move"),
+            );
+
+        // Everything counts as changed on the first run
+        schedule.run(&mut world);
+        assert_eq!(moved.load(Ordering::SeqCst), 4);
+        assert_eq!(world.entities().num_entities(), 5);
+
+        // The movement query marks every position it reaches, and the spawned position is applied after the counting
+        // system last ran
+        world.increment_tick();
+        schedule.run(&mut world);
+        assert_eq!(moved.load(Ordering::SeqCst), 5);
+        assert_eq!(world.entities().num_entities(), 6);
+    }
+
+    #[test]
+    fn test_own_changes_across_parallel_systems() {
+        let mut world = World::default();
+        let registry = world.entitites_mut();
+        for i in 0..4 {
+            let ent = registry.create_entity();
+            registry.assign_component(ent, Position(i));
+            registry.assign_component(ent, Velocity(1));
+        }
+
+        let wait_for = |flag: &AtomicBool, overlapped: &AtomicBool| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !flag.load(Ordering::SeqCst) {
+                if Instant::now() > deadline {
+                    overlapped.store(false, Ordering::SeqCst);
+                    return;
+                }
+                thread::yield_now();
+            }
+        };
+        let move_started = Arc::new(AtomicBool::new(false));
+        let heal_started = Arc::new(AtomicBool::new(false));
+        let overlapped = Arc::new(AtomicBool::new(true));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+
+        // The healing system only starts once the movement system is running, and the movement system only writes
+        // positions once the healing system started, which advances the tick of the registry in between
+        let mut schedule = Schedule::default().with_thread_pool(thread_pool(2));
+        let (started, other, overlap, log) = (
+            move_started.clone(),
+            heal_started.clone(),
+            overlapped.clone(),
+            seen.clone(),
+        );
+        schedule.add_system(
+            System::new("move", move |ctx| {
+                let moved = ctx.query::<MovedQuery>().iter().count();
+                log.lock().unwrap().push(moved);
+                started.store(true, Ordering::SeqCst);
+                wait_for(&other, &overlap);
+
+                for (velocity, position) in ctx.query::<MovementQuery>().iter() {
+                    position.0 += velocity.0;
+                }
+            })
+            .query::<MovementQuery>()
+            .query::<MovedQuery>(),
+        );
+        let (started, overlap) = (move_started.clone(), overlapped.clone());
+        schedule.add_system(System::new("gate", move |_| wait_for(&started, &overlap)));
+        let started = heal_started.clone();
+        schedule.add_system(
+            System::new("heal", move |_| started.store(true, Ordering::SeqCst))
+                .writes::<Health>()
+                .after("gate"),
+        );
+
+        for _ in 0..2 {
+            move_started.store(false, Ordering::SeqCst);
+            heal_started.store(false, Ordering::SeqCst);
+            schedule.run(&mut world);
+        }
+
+        // The positions written by the first run are not reported as changed to the second one
+        assert!(overlapped.load(Ordering::SeqCst));
+        assert_eq!(*seen.lock().unwrap(), [4, 0]);
+    }
+
+    #[test]
+    fn test_system_resources() {
+        struct FrameTime(u32);
+        struct Elapsed(u32);
+
+        let mut world = World::default();
+        world.insert_resource(FrameTime(2));
+        world.insert_resource(Elapsed(0));
+
+        let mut schedule = Schedule::default().with_thread_pool(thread_pool(4));
+        schedule
+            .add_system(
+                System::new("elapse", |ctx| {
+                    let time = ctx.resource::<FrameTime>().unwrap();
+                    ctx.resource_mut::<Elapsed>().unwrap().0 += time.0;
+                })
+                .reads_resource::<FrameTime>()
+                .writes_resource::<Elapsed>(),
+            )
+            .add_system(
+                System::new("move", |ctx| {
+                    let time = ctx.resource::<FrameTime>().unwrap();
+                    for (velocity, position) in ctx.query::<MovementQuery>().iter() {
+                        position.0 += velocity.0 * time.0;
+                    }
+                })
+                .query::<MovementQuery>()
+                .reads_resource::<FrameTime>(),
+            )
+            .add_system(
+                System::new("reset", |ctx| ctx.resource_mut::<Elapsed>().unwrap().0 = 0)
+                    .writes_resource::<Elapsed>(),
+            );
+
+        assert_eq!(
+            schedule.build().unwrap(),
+            [Ambiguity {
+                first: "elapse".to_string(),
+                second: "reset".to_string(),
+                conflict: Conflict::Resource(resource_id::<Elapsed>()),
+            }]
+        );
+
+        let ent = world.entitites_mut().create_entity();
+        world.entitites_mut().assign_component(ent, Position(1));
+        world.entitites_mut().assign_component(ent, Velocity(3));
+        world.get_resource_mut::<Elapsed>().unwrap().0 = 5;
+
+        schedule.run(&mut world);
+        assert_eq!(world.get_resource::<Elapsed>().unwrap().0, 0);
+        assert_eq!(
+            world
+                .entities()
+                .get_component_ref::<Position>(ent)
+                .unwrap()
+                .0,
+            7
+        );
+    }
+
+    #[test]
+    fn test_system_events() {
+        struct Died;
+
+        let mut world = World::default();
+        world.add_event::<Died>();
+
+        // every reader sees the events once, whether it runs before or after the writer
+        let seen = Log::default();
+        let reader = |name: &'static str| {
+            let seen = seen.clone();
+            let mut events = EventReader::<Died>::default();
+            System::new(name, move |ctx| {
+                let count = events.read(&ctx.events::<Died>()).count();
+                seen.lock().unwrap().extend((0..count).map(|_| name));
+            })
+            .reads_events::<Died>()
+        };
+
+        let mut schedule = Schedule::default().with_thread_pool(thread_pool(4));
+        schedule
+            .add_system(reader("early").before("kill"))
+            .add_system(
+                System::new("kill", |ctx| ctx.event_writer().send_batch([Died, Died]))
+                    .writes_events::<Died>(),
+            )
+            .add_system(reader("late").after("kill"));
+
+        for _ in 0..3 {
+            world.update_events();
+            schedule.run(&mut world);
+        }
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.iter().filter(|name| **name == "early").count(), 4);
+        assert_eq!(seen.iter().filter(|name| **name == "late").count(), 6);
+        assert_eq!(world.events::<Died>().unwrap().len(), 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_undeclared_resource() {
+        let mut world = World::default();
+        world.insert_resource(0u32);
+
+        let mut schedule = Schedule::default();
+        schedule.add_system(
+            System::new("count", |ctx| {
+                *ctx.resource_mut::<u32>().unwrap() += 1;
+            })
+            .reads_resource::<u32>(),
+        );
+        schedule.run(&mut world);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_undeclared_access() {
+        let mut schedule = Schedule::default();
+        schedule.add_system(System::new("move", |ctx| {
+            ctx.query::<MovementQuery>();
+        }));
+        schedule.run(&mut World::default());
+    }
+}
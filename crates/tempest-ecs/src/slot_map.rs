@@ -4,7 +4,7 @@ use std::{
     ptr::NonNull,
 };
 
-#[derive(Clone, Copy, Eq, Debug, PartialEq)]
+#[derive(Clone, Copy, Eq, Debug, PartialEq, Hash)]
 pub struct SlotMapKey {
     pub index: u32,
     pub generation: u32,
@@ -30,21 +30,22 @@ impl SlotMapKey {
     }
 }
 
+// The map owns its values, the raw pointers only opt it out of the auto traits.
+unsafe impl<T: Send> Send for SlotMap<T> {}
+unsafe impl<T: Sync> Sync for SlotMap<T> {}
+
 impl<T> Drop for SlotMap<T> {
     fn drop(&mut self) {
-        match self.jump {
-            Some(ptr) => {
-                unsafe {
-                    let jump_layout = Layout::array::<SlotMapKey>(self.capacity).unwrap();
-                    // no drop needed
-                    alloc::dealloc(ptr.as_ptr() as *mut u8, jump_layout);
-                }
+        if let Some(ptr) = self.jump {
+            unsafe {
+                let jump_layout = Layout::array::<SlotMapKey>(self.capacity).unwrap();
+                // no drop needed
+                alloc::dealloc(ptr.as_ptr() as *mut u8, jump_layout);
             }
-            _ => {}
-        };
+        }
 
-        match self.values {
-            Some(ptr) => unsafe {
+        if let Some(ptr) = self.values {
+            unsafe {
                 let value_layout = Layout::array::<T>(self.capacity).unwrap();
                 if needs_drop::<T>() {
                     for i in 0..self.len {
@@ -52,17 +53,15 @@ impl<T> Drop for SlotMap<T> {
                     }
                 }
                 alloc::dealloc(ptr.as_ptr() as *mut u8, value_layout);
-            },
-            _ => {}
+            }
         }
 
-        match self.erase {
-            Some(ptr) => unsafe {
+        if let Some(ptr) = self.erase {
+            unsafe {
                 let erase_layout = Layout::array::<u32>(self.capacity).unwrap();
                 // no drop needed
                 alloc::dealloc(ptr.as_ptr() as *mut u8, erase_layout);
-            },
-            _ => {}
+            }
         }
     }
 }
@@ -218,20 +217,24 @@ impl<T> SlotMap<T> {
             let idx_to_erase = trampoline.index as usize;
             let value_removed = if idx_to_erase != self.len() - 1 {
                 unsafe {
-                    let back_erase = self
+                    let back_erase = *self
                         .erase
                         .unwrap_unchecked()
                         .as_ptr()
-                        .add(self.len() - 1)
-                        .as_ref()
-                        .unwrap();
+                        .add(self.len() - 1);
                     self.jump
                         .unwrap_unchecked()
                         .as_ptr()
-                        .add(*back_erase as usize)
+                        .add(back_erase as usize)
                         .as_mut()
                         .unwrap()
                         .index = idx_to_erase as u32;
+                    // The back value moves into the erased position, along with the slot pointing to it
+                    self.erase
+                        .unwrap_unchecked()
+                        .as_ptr()
+                        .add(idx_to_erase)
+                        .write(back_erase);
 
                     let value_removed = self
                         .values
@@ -240,14 +243,6 @@ impl<T> SlotMap<T> {
                         .add(idx_to_erase)
                         .read();
 
-                    if needs_drop::<T>() {
-                        self.values
-                            .unwrap_unchecked()
-                            .as_ptr()
-                            .add(idx_to_erase)
-                            .drop_in_place();
-                    }
-
                     self.values
                         .unwrap_unchecked()
                         .as_ptr()
@@ -264,18 +259,11 @@ impl<T> SlotMap<T> {
                 }
             } else {
                 unsafe {
-                    let value = self
-                        .values
-                        .unwrap_unchecked()
-                        .as_ptr()
-                        .add(self.len() - 1)
-                        .read();
                     self.values
                         .unwrap_unchecked()
                         .as_ptr()
                         .add(self.len() - 1)
-                        .drop_in_place();
-                    value
+                        .read()
                 }
             };
 
@@ -301,36 +289,41 @@ impl<T> SlotMap<T> {
         }
     }
 
-    pub fn at_index(&self, index: usize) -> Option<T> {
+    pub fn at_index_ref(&self, index: usize) -> Option<&T> {
         if index >= self.len {
             return None;
         }
 
         match self.values {
-            Some(values) => unsafe { Some(values.as_ptr().add(index).read()) },
+            Some(values) => unsafe { values.as_ptr().add(index).as_ref() },
             None => None,
         }
     }
 
-    pub fn at_index_ref(&self, index: usize) -> Option<&T> {
+    pub fn at_index_mut(&mut self, index: usize) -> Option<&mut T> {
         if index >= self.len {
             return None;
         }
 
         match self.values {
-            Some(values) => unsafe { values.as_ptr().add(index).as_ref() },
+            Some(values) => unsafe { values.as_ptr().add(index).as_mut() },
             None => None,
         }
     }
 
-    pub fn at_index_mut(&self, index: usize) -> Option<&mut T> {
+    /// Key of the value stored at the provided dense index.
+    pub fn key_at_index(&self, index: usize) -> Option<SlotMapKey> {
         if index >= self.len {
             return None;
         }
 
-        match self.values {
-            Some(values) => unsafe { values.as_ptr().add(index).as_mut() },
-            None => None,
+        unsafe {
+            let slot = *self.erase.unwrap_unchecked().as_ptr().add(index);
+            let trampoline = *self.jump.unwrap_unchecked().as_ptr().add(slot as usize);
+            Some(SlotMapKey {
+                index: slot,
+                generation: trampoline.generation,
+            })
         }
     }
 
@@ -362,8 +355,11 @@ impl<T> SlotMap<T> {
 
             unsafe {
                 let old_ptr = self.jump.unwrap_unchecked().as_ptr() as *mut u8;
-                let new_ptr =
-                    alloc::realloc(old_ptr, old_layout, aligned_request * size_of::<SlotMapKey>()) as *mut SlotMapKey;
+                let new_ptr = alloc::realloc(
+                    old_ptr,
+                    old_layout,
+                    aligned_request * size_of::<SlotMapKey>(),
+                ) as *mut SlotMapKey;
 
                 for i in self.len()..aligned_request {
                     new_ptr.add(i).write(SlotMapKey {
@@ -444,16 +440,28 @@ impl<'a, T> Iterator for SlotMapValues<'a, T> {
     }
 }
 
+impl<T: Clone> SlotMap<T> {
+    pub fn at_index(&self, index: usize) -> Option<T> {
+        self.at_index_ref(index).cloned()
+    }
+}
+
 impl<T> SlotMap<T> {
     pub fn values(&self) -> SlotMapValues<'_, T> {
         SlotMapValues {
-            slot_map: &self,
+            slot_map: self,
             index: 0,
         }
     }
 }
 
 #[cfg(test)]
+#[allow(
+    clippy::bool_assert_comparison,
+    clippy::unnecessary_cast,
+    clippy::map_clone,
+    clippy::needless_borrow
+)]
 mod tests {
     use super::*;
 
@@ -471,6 +479,30 @@ mod tests {
         assert_eq!(*value, 42);
     }
 
+    #[test]
+    fn test_key_at_index() {
+        let mut map: SlotMap<u32> = SlotMap::new();
+        let keys: Vec<SlotMapKey> = (0..4).map(|i| map.insert(i)).collect();
+        map.remove(keys[1]);
+        let reinserted = map.insert(10);
+
+        let mut found: Vec<(SlotMapKey, u32)> = (0..map.len())
+            .map(|idx| {
+                (
+                    map.key_at_index(idx).unwrap(),
+                    *map.at_index_ref(idx).unwrap(),
+                )
+            })
+            .collect();
+        found.sort_by_key(|(_, value)| *value);
+        assert_eq!(
+            found,
+            [(keys[0], 0), (keys[2], 2), (keys[3], 3), (reinserted, 10)]
+        );
+        assert_ne!(reinserted, keys[1]);
+        assert_eq!(map.key_at_index(map.len()), None);
+    }
+
     #[test]
     fn test_insert_multiple() {
         let mut map: SlotMap<u32> = SlotMap::new();
@@ -530,6 +562,26 @@ mod tests {
         assert_eq!(map.remove(key2), None);
     }
 
+    #[test]
+    fn test_remove_after_moved_value() {
+        let mut map = SlotMap::new();
+        let key1 = map.insert(1);
+        let key2 = map.insert(2);
+        let key3 = map.insert(3);
+        let key4 = map.insert(4);
+
+        // The last value moves into the slot of the removed one and must keep resolving through its key
+        assert_eq!(map.remove(key2), Some(2));
+        assert_eq!(map.remove(key3), Some(3));
+        assert_eq!(map.remove(key1), Some(1));
+
+        let key5 = map.insert(5);
+        assert_eq!(map.get(key4), Some(&4));
+        assert_eq!(map.get(key5), Some(&5));
+        assert_eq!(map.remove(key4), Some(4));
+        assert_eq!(map.len(), 1);
+    }
+
     #[test]
     fn test_keys_are_invalidated_on_remove() {
         let mut map = SlotMap::new();
@@ -0,0 +1,219 @@
+use std::fmt::Display;
+
+use super::{registry::Registry, world::World};
+
+/// Copy of the entities and components of a registry, restored with [Registry::restore].
+///
+/// Snapshots keep the state of the entity allocator, so that the same entities are created after restoring, and the
+/// layout of the component storage, so that queries iterate in the same order.  Taking a snapshot clones every
+/// component, so it takes time and memory linear in the number of components, while restoring moves the storage of
+/// the snapshot into the registry.  Only the sparse pages of component pools, which map entities to their components,
+/// are shared with the registry until either writes to them.
+pub struct Snapshot {
+    registry: Registry,
+}
+
+/// Error returned when a registry cannot be snapshot.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SnapshotError {
+    /// The registry holds components of a type that cannot be cloned, such as a type not implementing [Clone] or the
+    /// pairs of a relation kind not registered with [Registry::cloneable_relation].
+    Uncloneable(&'static str),
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Uncloneable(name) => write!(
+                f,
+                "Component {} cannot be snapshot, as it cannot be cloned.",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl Registry {
+    /// Takes a snapshot of the entities and components of the registry.  Hooks are not part of the snapshot.
+    ///
+    /// Fails if the registry holds components that cannot be cloned, see [SnapshotError::Uncloneable].
+    pub fn snapshot(&self) -> Result<Snapshot, SnapshotError> {
+        Ok(Snapshot {
+            registry: self.snapshot_state()?,
+        })
+    }
+
+    /// Restores the entities and components of the snapshot, along with the tick it was taken at.  Hooks registered
+    /// on the registry are kept, but do not run for the components replaced by the snapshot.  The snapshot is moved
+    /// into the registry rather than copied, so take another one to restore the same state again.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.restore_state(snapshot.registry);
+    }
+}
+
+impl World {
+    /// Takes a snapshot of the entities and components of the world.  Resources and events are not part of it.
+    ///
+    /// Fails if the world holds components that cannot be cloned, see [SnapshotError::Uncloneable].
+    pub fn snapshot(&self) -> Result<Snapshot, SnapshotError> {
+        self.entities().snapshot()
+    }
+
+    /// Restores the entities and components of the snapshot, leaving resources and events untouched.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.entitites_mut().restore(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use tempest_ecs_macros::RegistryQuery;
+
+    use super::*;
+    use crate::{
+        component::Component,
+        registry::{Entity, StorageKind},
+        relation::Relations,
+    };
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Name(String);
+
+    #[derive(Component)]
+    struct Handle;
+
+    #[derive(Clone)]
+    struct Follows;
+
+    struct Owns;
+
+    #[derive(RegistryQuery)]
+    #[read_only(Position)]
+    struct PositionQuery;
+
+    fn state(world: &World) -> (Vec<Entity>, Vec<Position>, Vec<Option<Name>>, u64) {
+        let reg = world.entities();
+        let entities: Vec<Entity> = reg.iter_entities().collect();
+        let positions = reg.query_registry::<PositionQuery>().cloned().collect();
+        let names = entities
+            .iter()
+            .map(|ent| reg.get_component::<Name>(*ent))
+            .collect();
+        (entities, positions, names, world.tick())
+    }
+
+    fn rollback(kind: StorageKind) {
+        let mut world = World::new(kind);
+        world.entitites_mut().cloneable_relation::<Follows>();
+        let removed = Arc::new(AtomicUsize::new(0));
+        let counter = removed.clone();
+        world.entitites_mut().on_remove::<Position>(move |_, _| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let reg = world.entitites_mut();
+        let entities: Vec<Entity> = (0..64).map(|_| reg.create_entity()).collect();
+        for (i, ent) in entities.iter().enumerate() {
+            reg.assign_component(*ent, Position(i as i32));
+            if i % 3 == 0 {
+                reg.assign_component(*ent, Name(format!("ent {}", i)));
+            }
+        }
+        for ent in entities.iter().step_by(5) {
+            reg.destroy_entity(ent);
+        }
+        reg.add_relation(entities[1], Follows, entities[2]);
+        world.increment_tick();
+
+        let snapshot = world.snapshot().unwrap();
+        let before = state(&world);
+        let removals = removed.load(Ordering::Relaxed);
+
+        let reg = world.entitites_mut();
+        let spawned = reg.create_entity();
+        reg.assign_component(spawned, Position(-1));
+        reg.destroy_entity(&entities[2]);
+        reg.remove_component::<Name>(entities[3]);
+        reg.get_component_mut::<Position>(entities[4]).unwrap().0 = 100;
+        reg.assign_component(entities[7], Name("renamed".to_string()));
+        world.increment_tick();
+        assert_ne!(state(&world), before);
+
+        world.restore(snapshot);
+        assert_eq!(state(&world), before);
+        assert_eq!(removed.load(Ordering::Relaxed), removals + 1);
+        assert!(world
+            .entities()
+            .has_relation::<Follows>(entities[1], entities[2]));
+
+        // the allocator is restored too, so the same entity is created again, and handles created since are dead
+        assert!(!world.entities().is_alive(spawned));
+        let snapshot = world.snapshot().unwrap();
+        let recreated = world.entitites_mut().create_entity();
+        assert_eq!(recreated, spawned);
+
+        // the restored state can be snapshot and restored again, and hooks still run afterwards
+        world.restore(snapshot);
+        assert_eq!(state(&world), before);
+        world.entitites_mut().destroy_entity(&entities[1]);
+        assert_eq!(removed.load(Ordering::Relaxed), removals + 2);
+    }
+
+    #[test]
+    fn test_rollback() {
+        rollback(StorageKind::Sparse);
+        rollback(StorageKind::Archetype);
+    }
+
+    #[test]
+    fn test_uncloneable_component() {
+        let mut world = World::default();
+        let ent = world.entitites_mut().create_entity();
+        world.entitites_mut().assign_component(ent, Handle);
+        assert_eq!(
+            world.snapshot().err(),
+            Some(SnapshotError::Uncloneable(std::any::type_name::<Handle>()))
+        );
+
+        // storage left empty once the components are removed does not need to be cloned
+        world.entitites_mut().remove_component::<Handle>(ent);
+        assert!(world.snapshot().is_ok());
+    }
+
+    #[test]
+    fn test_uncloneable_relation() {
+        let mut world = World::default();
+        let [owner, owned] = [(); 2].map(|_| world.entitites_mut().create_entity());
+        world.entitites_mut().add_relation(owner, Owns, owned);
+        assert_eq!(
+            world.snapshot().err(),
+            Some(SnapshotError::Uncloneable(std::any::type_name::<
+                Relations<Owns>,
+            >()))
+        );
+
+        // relation kinds are made cloneable per registry
+        let mut other = World::default();
+        other.entitites_mut().cloneable_relation::<Follows>();
+        world.entitites_mut().add_relation(owner, Follows, owned);
+        world.entitites_mut().remove_relation::<Owns>(owner, owned);
+        assert_eq!(
+            world.snapshot().err(),
+            Some(SnapshotError::Uncloneable(std::any::type_name::<
+                Relations<Follows>,
+            >()))
+        );
+        world.entitites_mut().cloneable_relation::<Follows>();
+        assert!(world.snapshot().is_ok());
+    }
+}
@@ -2,4 +2,4 @@ pub trait SparseTableIndex: Clone + Copy + Eq + PartialEq {
     fn index(self) -> u32;
     fn tombstone() -> Self;
     fn from_raw(value: u32) -> Self;
-}
\ No newline at end of file
+}
@@ -1,28 +1,32 @@
 use std::{
     alloc::{self, Layout},
+    cmp::Ordering,
     marker::PhantomData,
-    mem::{needs_drop, size_of},
+    mem::needs_drop,
     ptr::{self, NonNull},
+    sync::Arc,
 };
 
 use super::sparse_index::SparseTableIndex;
 
-pub struct SparseMap<K: SparseTableIndex, V, const PAGE_SIZE: usize> {
+/// Number of keys covered by each sparse page of maps created with [Default].
+pub const DEFAULT_PAGE_SIZE: usize = 1024;
+
+pub struct SparseMap<K: SparseTableIndex, V> {
     packed_keys: NonNull<K>,
     packed_values: NonNull<V>,
-    sparse_keys: Vec<Box<[u32; PAGE_SIZE]>>,
+    /// Sparse pages, allocated once a key falls within them and shared between clones of the map until either writes
+    /// to them.
+    sparse_keys: Vec<Option<Arc<[u32]>>>,
+    /// Base two logarithm of the number of keys covered by each sparse page.
+    page_shift: u32,
     cap: usize,
     len: usize,
     key_marker: PhantomData<K>,
     value_marker: PhantomData<V>,
 }
 
-pub struct SparseMapIterator<
-    'a,
-    K: 'a + SparseTableIndex,
-    V: 'a + Copy + Clone,
-    const PAGE_SIZE: usize,
-> {
+pub struct SparseMapIterator<'a, K: 'a + SparseTableIndex, V: 'a> {
     keys: NonNull<K>,
     values: NonNull<V>,
     index: usize,
@@ -31,12 +35,7 @@ pub struct SparseMapIterator<
     value_marker: PhantomData<&'a V>,
 }
 
-pub struct SparseMapMutIterator<
-    'a,
-    K: 'a + SparseTableIndex,
-    V: 'a + Copy + Clone,
-    const PAGE_SIZE: usize,
-> {
+pub struct SparseMapMutIterator<'a, K: 'a + SparseTableIndex, V: 'a> {
     keys: NonNull<K>,
     values: NonNull<V>,
     index: usize,
@@ -45,201 +44,214 @@ pub struct SparseMapMutIterator<
     value_marker: PhantomData<&'a V>,
 }
 
-pub struct IntoIter<K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> {
-    map: SparseMap<K, V, PAGE_SIZE>,
+pub struct IntoIter<K: SparseTableIndex, V> {
+    map: SparseMap<K, V>,
 }
 
-impl<K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> Default
-    for SparseMap<K, V, PAGE_SIZE>
-{
+// The map owns its keys and values, the raw pointers only opt it out of the auto traits.
+unsafe impl<K: SparseTableIndex + Send, V: Send> Send for SparseMap<K, V> {}
+unsafe impl<K: SparseTableIndex + Sync, V: Sync> Sync for SparseMap<K, V> {}
+
+impl<K: SparseTableIndex, V> Default for SparseMap<K, V> {
     fn default() -> Self {
-        Self {
-            packed_keys: NonNull::dangling(),
-            packed_values: NonNull::dangling(),
-            sparse_keys: Vec::default(),
-            cap: 0,
-            len: 0,
-            key_marker: PhantomData,
-            value_marker: PhantomData,
-        }
+        Self::with_page_size(DEFAULT_PAGE_SIZE)
     }
 }
 
-impl<K: SparseTableIndex, V, const PAGE_SIZE: usize> Drop for SparseMap<K, V, PAGE_SIZE> {
+impl<K: SparseTableIndex, V> Drop for SparseMap<K, V> {
     fn drop(&mut self) {
-        if self.cap > 0 {
+        unsafe {
             if needs_drop::<K>() {
                 for i in 0..self.len {
-                    unsafe {
-                        self.packed_keys.as_ptr().add(i).drop_in_place();
-                    }
+                    self.packed_keys.as_ptr().add(i).drop_in_place();
                 }
             }
 
             if needs_drop::<V>() {
                 for i in 0..self.len {
-                    unsafe {
-                        self.packed_values.as_ptr().add(i).drop_in_place();
-                    }
+                    self.packed_values.as_ptr().add(i).drop_in_place();
                 }
             }
 
-            let packed_key_layout = Layout::array::<K>(self.cap).unwrap();
-            unsafe {
-                alloc::dealloc(self.packed_keys.as_ptr() as *mut u8, packed_key_layout);
-            }
-
-            let packed_value_layout = Layout::array::<V>(self.cap).unwrap();
-            unsafe {
-                alloc::dealloc(self.packed_values.as_ptr() as *mut u8, packed_value_layout);
-            }
-
-            self.sparse_keys.clear();
-            self.cap = 0;
             self.len = 0;
+            self.resize_packed(0);
         }
     }
 }
 
-impl<K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> SparseMap<K, V, PAGE_SIZE> {
-    pub fn insert(&mut self, key: K, value: V) {
-        if self.len >= self.cap || key.index() as usize >= self.cap {
-            self.grow_allocation(Some(key.index() as usize + 1));
+/// Reallocates an array from `old` to `new` elements.  Arrays of zero bytes are never allocated.
+///
+/// # Safety
+///
+/// The array must have been allocated with `old` elements, and the elements past `new` must have been moved out.
+unsafe fn reallocate<T>(ptr: NonNull<T>, old: usize, new: usize) -> NonNull<T> {
+    let old_layout = Layout::array::<T>(old).unwrap();
+    let new_layout = Layout::array::<T>(new).expect("Capacity overflow.");
+
+    if new_layout.size() == 0 {
+        if old_layout.size() > 0 {
+            alloc::dealloc(ptr.as_ptr() as *mut u8, old_layout);
         }
+        return NonNull::dangling();
+    }
 
-        let sparse_page_index = self.get_page(key);
-        let sparse_page_offset = self.get_offset(key);
+    let raw = if old_layout.size() == 0 {
+        alloc::alloc(new_layout)
+    } else {
+        alloc::realloc(ptr.as_ptr() as *mut u8, old_layout, new_layout.size())
+    };
 
-        let page = &mut self.sparse_keys[sparse_page_index];
-        let vacant = unsafe { *page.as_ptr().add(sparse_page_offset) == K::tombstone().index() };
-        if vacant {
-            page[sparse_page_offset] = self.len as u32;
-            unsafe {
-                self.packed_keys.as_ptr().add(self.len).write(key);
-                self.packed_values.as_ptr().add(self.len).write(value);
-            }
-            self.len += 1;
-        }
+    match NonNull::new(raw as *mut T) {
+        Some(p) => p,
+        None => alloc::handle_alloc_error(new_layout),
     }
+}
 
-    pub fn contains(&self, key: K) -> bool {
-        let sparse_page_index = self.get_page(key);
-        let sparse_page_offset = self.get_offset(key);
-        if sparse_page_index < self.sparse_keys.len() {
-            unsafe {
-                self.sparse_keys[sparse_page_index]
-                    .as_ptr()
-                    .add(sparse_page_offset)
-                    .as_ref()
-                    .or(Some(&K::tombstone().index()))
-                    .map(|k| !k.eq(&K::tombstone().index()))
-                    .unwrap_or(false)
-            }
-        } else {
-            false
-        }
+/// Page writable by its map, copied first if it is shared with a clone of the map.
+fn page_mut(page: &mut Arc<[u32]>) -> &mut [u32] {
+    if Arc::get_mut(page).is_none() {
+        *page = Arc::from(&page[..]);
     }
 
-    pub fn get(&self, key: K) -> Option<&V> {
-        let (sparse_page_index, sparse_page_offset) = (self.get_page(key), self.get_offset(key));
+    Arc::get_mut(page).expect("Copied page is still shared.")
+}
 
-        if sparse_page_index < self.sparse_keys.len() {
-            unsafe {
-                let trampoline = *self.sparse_keys[sparse_page_index]
-                    .as_ptr()
-                    .add(sparse_page_offset);
+impl<K: SparseTableIndex, V> SparseMap<K, V> {
+    /// Creates a map whose sparse pages each cover `page_size` keys.  Smaller pages waste less memory on sparsely
+    /// populated maps, larger ones take fewer allocations to cover densely populated maps.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the page size is not a power of two.
+    pub fn with_page_size(page_size: usize) -> Self {
+        assert!(
+            page_size.is_power_of_two(),
+            "Page size {} is not a power of two.",
+            page_size
+        );
 
-                if trampoline != K::tombstone().index() && {
-                    *self.packed_keys.as_ptr().add(trampoline as usize)
-                }
-                .eq(&key)
-                {
-                    self.packed_values
-                        .as_ptr()
-                        .add(trampoline as usize)
-                        .as_ref()
-                } else {
-                    None
-                }
-            }
-        } else {
-            None
+        Self {
+            packed_keys: NonNull::dangling(),
+            packed_values: NonNull::dangling(),
+            sparse_keys: Vec::default(),
+            page_shift: page_size.trailing_zeros(),
+            cap: 0,
+            len: 0,
+            key_marker: PhantomData,
+            value_marker: PhantomData,
         }
     }
 
-    pub fn get_mut(&self, key: K) -> Option<&mut V> {
-        let (sparse_page_index, sparse_page_offset) = (self.get_page(key), self.get_offset(key));
+    pub fn page_size(&self) -> usize {
+        1 << self.page_shift
+    }
 
-        if sparse_page_index < self.sparse_keys.len() {
-            unsafe {
-                let trampoline = *self.sparse_keys[sparse_page_index]
-                    .as_ptr()
-                    .add(sparse_page_offset);
+    /// Number of sparse pages currently allocated.
+    pub fn page_count(&self) -> usize {
+        self.sparse_keys.iter().flatten().count()
+    }
 
-                if trampoline != K::tombstone().index() && {
-                    *self.packed_keys.as_ptr().add(trampoline as usize)
-                }
-                .eq(&key)
-                {
-                    self.packed_values
-                        .as_ptr()
-                        .add(trampoline as usize)
-                        .as_mut()
-                } else {
-                    None
-                }
-            }
-        } else {
-            None
+    pub fn insert(&mut self, key: K, value: V) {
+        let (page, offset) = (self.get_page(key), self.get_offset(key));
+        let vacant = match self.sparse_keys.get(page) {
+            Some(Some(page)) => page[offset] == K::tombstone().index(),
+            _ => true,
+        };
+        if !vacant {
+            return;
         }
-    }
 
-    pub fn remove(&mut self, key: K) -> Option<V> {
-        let (sparse_page_index, sparse_page_offset) = (self.get_page(key), self.get_offset(key));
+        if self.len == self.cap {
+            unsafe { self.resize_packed((self.cap * 2).max(4)) };
+        }
 
-        if sparse_page_index < self.sparse_keys.len() {
-            let trampoline = self.sparse_keys[sparse_page_index][sparse_page_offset] as usize;
-            if trampoline != K::tombstone().index() as usize
-                && unsafe { *self.packed_keys.as_ptr().add(trampoline) }.eq(&key)
-            {
-                self.sparse_keys[sparse_page_index][sparse_page_offset] = K::tombstone().index();
+        if page >= self.sparse_keys.len() {
+            self.sparse_keys.resize(page + 1, None);
+        }
+        let page_size = self.page_size();
+        let page = self.sparse_keys[page]
+            .get_or_insert_with(|| vec![K::tombstone().index(); page_size].into());
 
-                unsafe {
-                    let back_key = *self.packed_keys.as_ptr().add(self.len - 1);
-                    let back_value = *self.packed_values.as_ptr().add(self.len - 1);
+        page_mut(page)[offset] = self.len as u32;
+        unsafe {
+            self.packed_keys.as_ptr().add(self.len).write(key);
+            self.packed_values.as_ptr().add(self.len).write(value);
+        }
+        self.len += 1;
+    }
 
-                    let removed_value = *self.packed_values.as_ptr().add(trampoline);
+    /// Index of the key in the packed arrays, if the key is in the map.
+    pub fn position(&self, key: K) -> Option<usize> {
+        let page = self.sparse_keys.get(self.get_page(key))?.as_ref()?;
+        let index = page[self.get_offset(key)];
 
-                    (*self.packed_keys.as_ptr().add(trampoline)) = back_key;
-                    (*self.packed_values.as_ptr().add(trampoline)) = back_value;
+        let present = index != K::tombstone().index()
+            && unsafe { *self.packed_keys.as_ptr().add(index as usize) } == key;
+        present.then_some(index as usize)
+    }
 
-                    if needs_drop::<K>() {
-                        self.packed_keys.as_ptr().add(self.len - 1).drop_in_place();
-                    }
+    pub fn contains(&self, key: K) -> bool {
+        self.position(key).is_some()
+    }
+
+    pub fn get(&self, key: K) -> Option<&V> {
+        let index = self.position(key)?;
+        unsafe { self.packed_values.as_ptr().add(index).as_ref() }
+    }
 
-                    if needs_drop::<V>() {
-                        self.packed_values
-                            .as_ptr()
-                            .add(self.len - 1)
-                            .drop_in_place();
-                    }
+    pub fn get_mut(&mut self, key: K) -> Option<&mut V> {
+        unsafe { self.get_unchecked_mut(key) }
+    }
 
-                    self.len -= 1;
+    /// Fetches a mutable reference to a value through a shared reference to the map.
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that no other reference to the value is alive for the lifetime of the returned one.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn get_unchecked_mut(&self, key: K) -> Option<&mut V> {
+        let index = self.position(key)?;
+        self.packed_values.as_ptr().add(index).as_mut()
+    }
 
-                    if !self.is_empty() {
-                        let (last_index, last_offset) =
-                            (self.get_page(back_key), self.get_offset(back_key));
-                        self.sparse_keys[last_index][last_offset] = trampoline as u32;
-                    }
+    pub fn remove(&mut self, key: K) -> Option<V> {
+        let index = self.position(key)?;
+        let (page, offset) = (self.get_page(key), self.get_offset(key));
+        if let Some(page) = self.sparse_keys[page].as_mut() {
+            page_mut(page)[offset] = K::tombstone().index();
+        }
 
-                    Some(removed_value)
-                }
-            } else {
-                None
+        Some(unsafe { self.swap_remove_packed(index) })
+    }
+
+    /// Moves the value out of the packed slot, then moves the back of the packed arrays into the vacated slot.  The
+    /// sparse entry of the removed key must already have been cleared by the caller.
+    unsafe fn swap_remove_packed(&mut self, index: usize) -> V {
+        let back = self.len - 1;
+        let removed_value = self.packed_values.as_ptr().add(index).read();
+
+        if index != back {
+            let back_key = *self.packed_keys.as_ptr().add(back);
+            ptr::copy_nonoverlapping(
+                self.packed_keys.as_ptr().add(back),
+                self.packed_keys.as_ptr().add(index),
+                1,
+            );
+            ptr::copy_nonoverlapping(
+                self.packed_values.as_ptr().add(back),
+                self.packed_values.as_ptr().add(index),
+                1,
+            );
+
+            let (last_index, last_offset) = (self.get_page(back_key), self.get_offset(back_key));
+            if let Some(page) = self.sparse_keys[last_index].as_mut() {
+                page_mut(page)[last_offset] = index as u32;
             }
-        } else {
-            None
         }
+
+        self.len -= 1;
+
+        removed_value
     }
 
     pub fn len(&self) -> usize {
@@ -254,7 +266,114 @@ impl<K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> SparseMap<K,
         self.len == 0
     }
 
-    pub fn iter(&self) -> SparseMapIterator<'_, K, V, PAGE_SIZE> {
+    /// Reserves capacity for at least `additional` more values.
+    pub fn reserve(&mut self, additional: usize) {
+        let requested = self.len + additional;
+        if requested > self.cap {
+            unsafe { self.resize_packed(requested) };
+        }
+    }
+
+    /// Releases the sparse pages left without any key, and shrinks the packed arrays to the number of values.
+    pub fn shrink_to_fit(&mut self) {
+        let tombstone = K::tombstone().index();
+        for page in self.sparse_keys.iter_mut() {
+            if page
+                .as_ref()
+                .map_or(false, |page| page.iter().all(|index| *index == tombstone))
+            {
+                *page = None;
+            }
+        }
+
+        while let Some(None) = self.sparse_keys.last() {
+            self.sparse_keys.pop();
+        }
+        self.sparse_keys.shrink_to_fit();
+
+        if self.cap > self.len {
+            unsafe { self.resize_packed(self.len) };
+        }
+    }
+
+    /// Swaps the values at two indices of the packed arrays, along with their keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either index is out of bounds.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        assert!(
+            a < self.len && b < self.len,
+            "Swapped indices {} and {} are out of bounds of {} values.",
+            a,
+            b,
+            self.len
+        );
+        if a == b {
+            return;
+        }
+
+        unsafe {
+            ptr::swap(
+                self.packed_keys.as_ptr().add(a),
+                self.packed_keys.as_ptr().add(b),
+            );
+            ptr::swap(
+                self.packed_values.as_ptr().add(a),
+                self.packed_values.as_ptr().add(b),
+            );
+        }
+        self.set_sparse(a);
+        self.set_sparse(b);
+    }
+
+    /// Sorts the packed arrays with the comparator, so that iteration visits the values in order.  The sort is stable,
+    /// and keys keep mapping to their values.
+    pub fn sort_by(&mut self, mut compare: impl FnMut(&V, &V) -> Ordering) {
+        let values = self.as_value_slice();
+        let mut order: Vec<usize> = (0..values.len()).collect();
+        order.sort_by(|a, b| compare(&values[*a], &values[*b]));
+
+        // Each cycle of the permutation is walked once, carrying the value of its first index along until the index
+        // it belongs to is reached
+        for start in 0..order.len() {
+            let mut current = start;
+            while order[current] != current {
+                let next = order[current];
+                order[current] = current;
+                if next == start {
+                    break;
+                }
+
+                unsafe {
+                    ptr::swap(
+                        self.packed_keys.as_ptr().add(current),
+                        self.packed_keys.as_ptr().add(next),
+                    );
+                    ptr::swap(
+                        self.packed_values.as_ptr().add(current),
+                        self.packed_values.as_ptr().add(next),
+                    );
+                }
+                current = next;
+            }
+        }
+
+        for index in 0..self.len {
+            self.set_sparse(index);
+        }
+    }
+
+    /// Points the sparse entry of the key at the index of the packed arrays to that index.
+    fn set_sparse(&mut self, index: usize) {
+        let key = unsafe { *self.packed_keys.as_ptr().add(index) };
+        let (page, offset) = (self.get_page(key), self.get_offset(key));
+        if let Some(page) = self.sparse_keys[page].as_mut() {
+            page_mut(page)[offset] = index as u32;
+        }
+    }
+
+    pub fn iter(&self) -> SparseMapIterator<'_, K, V> {
         SparseMapIterator {
             keys: self.packed_keys,
             values: self.packed_values,
@@ -265,7 +384,7 @@ impl<K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> SparseMap<K,
         }
     }
 
-    pub fn iter_mut(&mut self) -> SparseMapMutIterator<'_, K, V, PAGE_SIZE> {
+    pub fn iter_mut(&mut self) -> SparseMapMutIterator<'_, K, V> {
         SparseMapMutIterator {
             keys: self.packed_keys,
             values: self.packed_values,
@@ -308,15 +427,6 @@ impl<K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> SparseMap<K,
         }
     }
 
-    #[inline]
-    pub fn at_index(&self, index: usize) -> Option<V> {
-        if index >= self.len {
-            return None;
-        }
-
-        unsafe { Some(self.packed_values.as_ptr().add(index).read()) }
-    }
-
     #[inline]
     pub fn at_index_ref(&self, index: usize) -> Option<&V> {
         if index >= self.len {
@@ -327,7 +437,7 @@ impl<K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> SparseMap<K,
     }
 
     #[inline]
-    pub fn at_index_mut(&self, index: usize) -> Option<&mut V> {
+    pub fn at_index_mut(&mut self, index: usize) -> Option<&mut V> {
         if index >= self.len {
             return None;
         }
@@ -335,188 +445,91 @@ impl<K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> SparseMap<K,
         unsafe { self.packed_values.as_ptr().add(index).as_mut() }
     }
 
-    fn grow_allocation(&mut self, requested_size: Option<usize>) {
-        let request = requested_size
-            .or_else(|| {
-                let size = if self.cap == 0 { 1 } else { self.cap * 2 };
-                Some(size)
-            })
-            .unwrap();
+    /// Fetches a mutable reference to the value at the index of the packed arrays through a shared reference to the map.
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that no other reference to the value is alive for the lifetime of the returned one.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn at_index_unchecked_mut(&self, index: usize) -> Option<&mut V> {
+        if index >= self.len {
+            return None;
+        }
 
-        self.grow_sparse_allocation(request);
-        self.cap = self.grow_packed_allocation(request);
+        self.packed_values.as_ptr().add(index).as_mut()
     }
 
-    fn grow_packed_allocation(&mut self, requested_size: usize) -> usize {
-        if requested_size < self.cap {
-            return self.cap;
-        }
+    /// Clones the map with the provided function, keeping the packed arrays in the same order and the capacity the
+    /// same.  Sparse pages are shared with the clone until either map writes to them.
+    pub fn clone_with(&self, clone: impl Fn(&V) -> V) -> Self {
+        let mut map = Self::with_page_size(self.page_size());
+        map.sparse_keys = self.sparse_keys.clone();
+        unsafe { map.resize_packed(self.cap) };
 
-        let aligned_request = {
-            let d = requested_size / PAGE_SIZE;
-            let r = requested_size % PAGE_SIZE;
-            if r > 0 && PAGE_SIZE > 0 {
-                d + 1
-            } else {
-                d
+        for index in 0..self.len {
+            unsafe {
+                let value = clone(&*self.packed_values.as_ptr().add(index));
+                map.packed_keys
+                    .as_ptr()
+                    .add(index)
+                    .write(*self.packed_keys.as_ptr().add(index));
+                map.packed_values.as_ptr().add(index).write(value);
             }
-        } * PAGE_SIZE;
-
-        let new_key_layout = Layout::array::<K>(aligned_request).unwrap();
-
-        let new_key_ptr = if self.cap == 0 {
-            unsafe { alloc::alloc(new_key_layout) as *mut K }
-        } else {
-            let old_layout = Layout::array::<K>(self.cap).unwrap();
-            let old_ptr = self.packed_keys.as_ptr() as *mut u8;
-
-            unsafe { alloc::realloc(old_ptr, old_layout, aligned_request * size_of::<K>()) as *mut K }
-        };
-
-        self.packed_keys = match NonNull::new(new_key_ptr) {
-            Some(p) => p,
-            None => alloc::handle_alloc_error(new_key_layout),
-        };
-
-        let new_value_layout = Layout::array::<V>(aligned_request).unwrap();
-
-        let new_value_ptr = if self.cap == 0 {
-            unsafe { alloc::alloc(new_value_layout) as *mut V }
-        } else {
-            let old_layout = Layout::array::<K>(self.cap).unwrap();
-            let old_ptr = self.packed_values.as_ptr() as *mut u8;
-
-            unsafe { alloc::realloc(old_ptr, old_layout, aligned_request * size_of::<V>()) as *mut V }
-        };
-
-        self.packed_values = match NonNull::new(new_value_ptr as *mut V) {
-            Some(p) => p,
-            None => alloc::handle_alloc_error(new_value_layout),
-        };
-
-        aligned_request
-    }
-
-    fn grow_sparse_allocation(&mut self, requested_size: usize) {
-        if requested_size < self.cap {
-            return;
+            map.len += 1;
         }
 
-        let requested_page_count = {
-            let d = requested_size / PAGE_SIZE;
-            let r = requested_size % PAGE_SIZE;
-            if r > 0 && PAGE_SIZE > 0 {
-                d + 1
-            } else {
-                d
-            }
-        };
+        map
+    }
 
-        for _ in self.sparse_keys.len()..requested_page_count {
-            let page_ptr = Box::new([K::tombstone().index(); PAGE_SIZE]);
-            self.sparse_keys.push(page_ptr);
-        }
+    /// Reallocates the packed arrays to hold `cap` values.
+    ///
+    /// # Safety
+    ///
+    /// The capacity must not be lower than the number of values.
+    unsafe fn resize_packed(&mut self, cap: usize) {
+        self.packed_keys = reallocate(self.packed_keys, self.cap, cap);
+        self.packed_values = reallocate(self.packed_values, self.cap, cap);
+        self.cap = cap;
     }
 
     fn get_page(&self, value: K) -> usize {
-        self.get_page_raw(value.index() as usize)
+        value.index() as usize >> self.page_shift
     }
 
-    fn get_page_raw(&self, value: usize) -> usize {
-        value / PAGE_SIZE
+    fn get_offset(&self, value: K) -> usize {
+        value.index() as usize & (self.page_size() - 1)
     }
+}
 
-    fn get_offset(&self, value: K) -> usize {
-        self.get_offset_raw(value.index() as usize)
+impl<K: SparseTableIndex, V: Clone> Clone for SparseMap<K, V> {
+    fn clone(&self) -> Self {
+        self.clone_with(V::clone)
     }
+}
 
-    fn get_offset_raw(&self, value: usize) -> usize {
-        value % PAGE_SIZE
+impl<K: SparseTableIndex, V: Clone> SparseMap<K, V> {
+    #[inline]
+    pub fn at_index(&self, index: usize) -> Option<V> {
+        self.at_index_ref(index).cloned()
     }
 }
 
-impl<K: SparseTableIndex, V: Clone + Copy + PartialEq, const PAGE_SIZE: usize>
-    SparseMap<K, V, PAGE_SIZE>
-{
+impl<K: SparseTableIndex, V: PartialEq> SparseMap<K, V> {
     pub fn contains_pair(&self, key: K, value: &V) -> bool {
-        let sparse_page_index = self.get_page(key);
-        let sparse_page_offset = self.get_offset(key);
-        if sparse_page_index < self.sparse_keys.len() {
-            unsafe {
-                let trampoline = *self.sparse_keys[sparse_page_index]
-                    .as_ptr()
-                    .add(sparse_page_offset);
-
-                if trampoline != K::tombstone().index() && {
-                    *self.packed_keys.as_ptr().add(trampoline as usize)
-                }
-                .eq(&key)
-                {
-                    { *self.packed_values.as_ptr().add(trampoline as usize) }.eq(&value)
-                } else {
-                    false
-                }
-            }
-        } else {
-            false
-        }
+        self.get(key).map_or(false, |other| other.eq(value))
     }
 
     pub fn remove_pair(&mut self, key: K, value: &V) -> bool {
-        let (sparse_page_index, sparse_page_offset) = (self.get_page(key), self.get_offset(key));
-
-        if sparse_page_index < self.sparse_keys.len() {
-            let trampoline = self.sparse_keys[sparse_page_index][sparse_page_offset] as usize;
-            if trampoline != K::tombstone().index() as usize
-                && unsafe { *self.packed_keys.as_ptr().add(trampoline) }.eq(&key)
-            {
-                self.sparse_keys[sparse_page_index][sparse_page_offset] = K::tombstone().index();
-
-                unsafe {
-                    let back_key = *self.packed_keys.as_ptr().add(self.len - 1);
-                    let back_value = *self.packed_values.as_ptr().add(self.len - 1);
-
-                    let removed_value = *self.packed_values.as_ptr().add(trampoline);
-                    if !removed_value.eq(value) {
-                        false
-                    } else {
-                        (*self.packed_keys.as_ptr().add(trampoline)) = back_key;
-                        (*self.packed_values.as_ptr().add(trampoline)) = back_value;
-
-                        if needs_drop::<K>() {
-                            self.packed_keys.as_ptr().add(self.len - 1).drop_in_place();
-                        }
-
-                        if needs_drop::<V>() {
-                            self.packed_values
-                                .as_ptr()
-                                .add(self.len - 1)
-                                .drop_in_place();
-                        }
-
-                        self.len -= 1;
-
-                        if !self.is_empty() {
-                            let (last_index, last_offset) =
-                                (self.get_page(back_key), self.get_offset(back_key));
-                            self.sparse_keys[last_index][last_offset] = trampoline as u32;
-                        }
-
-                        true
-                    }
-                }
-            } else {
-                false
-            }
+        if self.contains_pair(key, value) {
+            self.remove(key);
+            true
         } else {
             false
         }
     }
 }
 
-impl<'a, K: 'a + SparseTableIndex, V: 'a + Copy + Clone, const PAGE_SIZE: usize> Iterator
-    for SparseMapIterator<'a, K, V, PAGE_SIZE>
-{
+impl<'a, K: 'a + SparseTableIndex, V: 'a> Iterator for SparseMapIterator<'a, K, V> {
     type Item = (&'a K, &'a V);
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -572,9 +585,7 @@ impl<'a, K: 'a + SparseTableIndex, V: 'a + Copy + Clone, const PAGE_SIZE: usize>
     }
 }
 
-impl<K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> Iterator
-    for IntoIter<K, V, PAGE_SIZE>
-{
+impl<K: SparseTableIndex, V> Iterator for IntoIter<K, V> {
     type Item = (K, V);
 
     #[inline]
@@ -582,10 +593,8 @@ impl<K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> Iterator
         if self.map.is_empty() {
             None
         } else {
-            let first_key = self.map.as_keys_slice()[0];
-            let first_value = self.map.as_value_slice()[0];
-            self.map.remove(first_key);
-            Some((first_key, first_value))
+            let back_key = self.map.as_keys_slice()[self.map.len() - 1];
+            self.map.remove(back_key).map(|value| (back_key, value))
         }
     }
 
@@ -595,11 +604,9 @@ impl<K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> Iterator
     }
 }
 
-impl<K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> IntoIterator
-    for SparseMap<K, V, PAGE_SIZE>
-{
+impl<K: SparseTableIndex, V> IntoIterator for SparseMap<K, V> {
     type Item = (K, V);
-    type IntoIter = IntoIter<K, V, PAGE_SIZE>;
+    type IntoIter = IntoIter<K, V>;
 
     #[inline]
     fn into_iter(self) -> Self::IntoIter {
@@ -607,11 +614,9 @@ impl<K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> IntoIterator
     }
 }
 
-impl<'a, K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> IntoIterator
-    for &'a SparseMap<K, V, PAGE_SIZE>
-{
+impl<'a, K: SparseTableIndex, V> IntoIterator for &'a SparseMap<K, V> {
     type Item = (&'a K, &'a V);
-    type IntoIter = SparseMapIterator<'a, K, V, PAGE_SIZE>;
+    type IntoIter = SparseMapIterator<'a, K, V>;
 
     #[inline]
     fn into_iter(self) -> Self::IntoIter {
@@ -619,11 +624,9 @@ impl<'a, K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> IntoItera
     }
 }
 
-impl<'a, K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> IntoIterator
-    for &'a mut SparseMap<K, V, PAGE_SIZE>
-{
+impl<'a, K: SparseTableIndex, V> IntoIterator for &'a mut SparseMap<K, V> {
     type Item = (&'a K, &'a mut V);
-    type IntoIter = SparseMapMutIterator<'a, K, V, PAGE_SIZE>;
+    type IntoIter = SparseMapMutIterator<'a, K, V>;
 
     #[inline]
     fn into_iter(self) -> Self::IntoIter {
@@ -631,9 +634,7 @@ impl<'a, K: SparseTableIndex, V: Copy + Clone, const PAGE_SIZE: usize> IntoItera
     }
 }
 
-impl<'a, K: 'a + SparseTableIndex, V: 'a + Copy + Clone, const PAGE_SIZE: usize> Iterator
-    for SparseMapMutIterator<'a, K, V, PAGE_SIZE>
-{
+impl<'a, K: 'a + SparseTableIndex, V: 'a> Iterator for SparseMapMutIterator<'a, K, V> {
     type Item = (&'a K, &'a mut V);
 
     #[inline]
@@ -691,8 +692,12 @@ impl<'a, K: 'a + SparseTableIndex, V: 'a + Copy + Clone, const PAGE_SIZE: usize>
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
+    use std::{cell::Cell, rc::Rc};
+
     use super::*;
+    use crate::entities::tests::Rng;
 
     #[derive(Clone, Copy, Debug, Eq, PartialEq)]
     struct SimpleKey {
@@ -715,7 +720,7 @@ mod tests {
 
     #[test]
     fn test_insert() {
-        let mut map = SparseMap::<SimpleKey, i32, 1024>::default();
+        let mut map = SparseMap::<SimpleKey, i32>::default();
         let key1 = SimpleKey { id: 1 };
         let key2 = SimpleKey { id: 2 };
         let value1 = 42;
@@ -732,7 +737,7 @@ mod tests {
 
     #[test]
     fn test_remove() {
-        let mut map = SparseMap::<SimpleKey, i32, 1024>::default();
+        let mut map = SparseMap::<SimpleKey, i32>::default();
         let key1 = SimpleKey { id: 1 };
         let key2 = SimpleKey { id: 2 };
         let value1 = 42;
@@ -752,7 +757,7 @@ mod tests {
 
     #[test]
     fn test_contains() {
-        let mut map = SparseMap::<SimpleKey, i32, 1024>::default();
+        let mut map = SparseMap::<SimpleKey, i32>::default();
         let key1 = SimpleKey { id: 1 };
         let key2 = SimpleKey { id: 2 };
         let value1 = 42;
@@ -764,7 +769,7 @@ mod tests {
     }
     #[test]
     fn test_iter() {
-        let mut map = SparseMap::<SimpleKey, i32, 1024>::default();
+        let mut map = SparseMap::<SimpleKey, i32>::default();
         map.insert(SimpleKey { id: 1 }, 10);
         map.insert(SimpleKey { id: 3 }, 30);
         map.insert(SimpleKey { id: 5 }, 50);
@@ -780,7 +785,7 @@ mod tests {
 
     #[test]
     fn test_iter_for_loop() {
-        let mut map = SparseMap::<SimpleKey, i32, 16>::default();
+        let mut map = SparseMap::<SimpleKey, i32>::with_page_size(16);
         map.insert(SimpleKey { id: 3 }, 30);
         map.insert(SimpleKey { id: 1 }, 10);
         map.insert(SimpleKey { id: 5 }, 50);
@@ -809,7 +814,7 @@ mod tests {
 
     #[test]
     fn test_iter_mut_for_loop() {
-        let mut map = SparseMap::<SimpleKey, u32, 1024>::default();
+        let mut map = SparseMap::<SimpleKey, u32>::default();
         map.insert(SimpleKey { id: 1 }, 10);
         map.insert(SimpleKey { id: 2 }, 20);
         map.insert(SimpleKey { id: 3 }, 30);
@@ -825,7 +830,7 @@ mod tests {
 
     #[test]
     fn test_into_iter_mut_for_loop() {
-        let mut map = SparseMap::<SimpleKey, u32, 1024>::default();
+        let mut map = SparseMap::<SimpleKey, u32>::default();
         map.insert(SimpleKey { id: 1 }, 10);
         map.insert(SimpleKey { id: 2 }, 20);
         map.insert(SimpleKey { id: 3 }, 30);
@@ -841,7 +846,7 @@ mod tests {
 
     #[test]
     fn test_iter_mut() {
-        let mut map = SparseMap::<SimpleKey, i32, 1024>::default();
+        let mut map = SparseMap::<SimpleKey, i32>::default();
         map.insert(SimpleKey { id: 1 }, 10);
         map.insert(SimpleKey { id: 3 }, 30);
         map.insert(SimpleKey { id: 5 }, 50);
@@ -857,7 +862,7 @@ mod tests {
 
     #[test]
     fn test_get_existing_key() {
-        let mut map = SparseMap::<SimpleKey, u32, 1024>::default();
+        let mut map = SparseMap::<SimpleKey, u32>::default();
         let key = SimpleKey { id: 42 };
         let value = 12345;
         map.insert(key, value);
@@ -868,7 +873,7 @@ mod tests {
 
     #[test]
     fn test_get_nonexisting_key() {
-        let mut map = SparseMap::<SimpleKey, u32, 1024>::default();
+        let mut map = SparseMap::<SimpleKey, u32>::default();
         let key = SimpleKey { id: 42 };
         let value = 12345;
         map.insert(key, value);
@@ -879,7 +884,7 @@ mod tests {
 
     #[test]
     fn test_get_tombstone_key() {
-        let mut map = SparseMap::<SimpleKey, u32, 1024>::default();
+        let mut map = SparseMap::<SimpleKey, u32>::default();
         let key = SimpleKey { id: 42 };
         let value = 12345;
         map.insert(key, value);
@@ -889,4 +894,171 @@ mod tests {
         let result = map.get(tombstone_key);
         assert_eq!(result, None);
     }
+
+    #[derive(Debug)]
+    struct DropCounter(Rc<Cell<usize>>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_remove_drops_owned_values_once() {
+        let drops = Rc::new(Cell::new(0));
+        let mut map = SparseMap::<SimpleKey, DropCounter>::with_page_size(16);
+        for id in 0..4 {
+            map.insert(SimpleKey { id }, DropCounter(drops.clone()));
+        }
+
+        let removed = map.remove(SimpleKey { id: 1 });
+        assert_eq!(drops.get(), 0);
+        drop(removed);
+        assert_eq!(drops.get(), 1);
+
+        assert!(map.contains(SimpleKey { id: 3 }));
+        assert_eq!(map.as_keys_slice()[1], SimpleKey { id: 3 });
+
+        drop(map);
+        assert_eq!(drops.get(), 4);
+    }
+
+    #[test]
+    fn test_into_iter_drops_remaining_values() {
+        let drops = Rc::new(Cell::new(0));
+        let mut map = SparseMap::<SimpleKey, DropCounter>::with_page_size(16);
+        for id in 0..3 {
+            map.insert(SimpleKey { id }, DropCounter(drops.clone()));
+        }
+
+        let mut iter = map.into_iter();
+        let first = iter.next();
+        assert!(first.is_some());
+        assert_eq!(drops.get(), 0);
+
+        drop(iter);
+        assert_eq!(drops.get(), 2);
+        drop(first);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn test_owned_values_survive_growth() {
+        let mut map = SparseMap::<SimpleKey, String>::with_page_size(4);
+        for id in 0..32 {
+            map.insert(SimpleKey { id }, format!("value {}", id));
+        }
+
+        for id in 0..32 {
+            assert_eq!(map.get(SimpleKey { id }), Some(&format!("value {}", id)));
+        }
+
+        assert!(map.remove_pair(SimpleKey { id: 7 }, &"value 7".to_string()));
+        assert!(!map.remove_pair(SimpleKey { id: 8 }, &"value 7".to_string()));
+        assert_eq!(map.len(), 31);
+    }
+
+    #[test]
+    fn test_clone_shares_pages() {
+        let mut map = SparseMap::<SimpleKey, String>::with_page_size(4);
+        for id in [9, 2, 5, 0] {
+            map.insert(SimpleKey { id }, id.to_string());
+        }
+        map.remove(SimpleKey { id: 2 });
+
+        let mut clone = map.clone();
+        assert_eq!(clone.capacity(), map.capacity());
+        assert!(map.iter().eq(clone.iter()));
+        let shared = |lhs: &SparseMap<SimpleKey, String>,
+                      rhs: &SparseMap<SimpleKey, String>,
+                      page: usize| {
+            Arc::ptr_eq(
+                lhs.sparse_keys[page].as_ref().unwrap(),
+                rhs.sparse_keys[page].as_ref().unwrap(),
+            )
+        };
+        assert!((0..3).all(|page| shared(&map, &clone, page)));
+
+        // writing to a page copies it, leaving the original map and the other pages untouched
+        clone.remove(SimpleKey { id: 9 });
+        assert!(!shared(&map, &clone, 2));
+        assert!(shared(&map, &clone, 0));
+        assert_eq!(map.get(SimpleKey { id: 9 }), Some(&"9".to_string()));
+        assert_eq!(clone.get(SimpleKey { id: 9 }), None);
+        assert_eq!(clone.get(SimpleKey { id: 5 }), Some(&"5".to_string()));
+    }
+
+    #[test]
+    fn test_sort_by() {
+        let mut map = SparseMap::<SimpleKey, String>::with_page_size(8);
+        let mut rng = Rng(11);
+        for id in 0..100 {
+            map.insert(SimpleKey { id: id * 3 }, format!("{:02}", rng.below(50)));
+        }
+        map.remove(SimpleKey { id: 30 });
+
+        // ties keep the order of the packed arrays
+        let mut expected: Vec<(u32, String)> = map
+            .iter()
+            .map(|(key, value)| (key.id, value.clone()))
+            .collect();
+        map.sort_by(|lhs, rhs| lhs.cmp(rhs));
+        expected.sort_by(|lhs, rhs| lhs.1.cmp(&rhs.1));
+        let sorted: Vec<(u32, String)> = map
+            .iter()
+            .map(|(key, value)| (key.id, value.clone()))
+            .collect();
+        assert_eq!(sorted, expected);
+        for (id, value) in expected.iter() {
+            assert_eq!(map.get(SimpleKey { id: *id }), Some(value));
+        }
+
+        map.swap(0, 10);
+        assert_eq!(map.as_keys_slice()[0].id, expected[10].0);
+        assert_eq!(map.position(SimpleKey { id: expected[0].0 }), Some(10));
+        assert_eq!(
+            map.remove(SimpleKey { id: expected[0].0 }),
+            Some(expected[0].1.clone())
+        );
+        assert_eq!(map.len(), 98);
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let mut map = SparseMap::<SimpleKey, String>::with_page_size(16);
+        for id in 0..256 {
+            map.insert(SimpleKey { id }, id.to_string());
+        }
+        assert_eq!(map.page_count(), 16);
+
+        // pages are only allocated once a key lands in them
+        map.insert(SimpleKey { id: 1000 }, "far".to_string());
+        assert_eq!(map.page_count(), 17);
+
+        for id in (0..256).filter(|id| !(40..50).contains(id)) {
+            map.remove(SimpleKey { id });
+        }
+        map.remove(SimpleKey { id: 1000 });
+        assert_eq!(map.page_count(), 17);
+
+        map.shrink_to_fit();
+        assert_eq!(map.page_count(), 2);
+        assert_eq!(map.sparse_keys.len(), 4);
+        assert_eq!(map.capacity(), 10);
+        for id in 0..256 {
+            assert_eq!(map.contains(SimpleKey { id }), (40..50).contains(&id));
+        }
+
+        // released pages are allocated again when needed
+        map.insert(SimpleKey { id: 200 }, "200".to_string());
+        assert_eq!(map.get(SimpleKey { id: 200 }), Some(&"200".to_string()));
+        assert_eq!(map.page_count(), 3);
+
+        for id in (40..50).chain([200]) {
+            map.remove(SimpleKey { id });
+        }
+        map.shrink_to_fit();
+        assert_eq!((map.page_count(), map.capacity()), (0, 0));
+    }
 }
@@ -181,7 +181,7 @@ impl<T: SparseTableIndex, const PAGE_SIZE: usize> SparseSet<T, PAGE_SIZE> {
             }
         };
 
-        self.packed = match NonNull::new(new_ptr as *mut T) {
+        self.packed = match NonNull::new(new_ptr) {
             Some(p) => p,
             None => alloc::handle_alloc_error(new_layout),
         };
@@ -220,6 +220,7 @@ impl<T: SparseTableIndex, const PAGE_SIZE: usize> SparseSet<T, PAGE_SIZE> {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
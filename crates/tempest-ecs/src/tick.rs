@@ -0,0 +1,65 @@
+/// Ticks of the world at which a component was added to its entity, and last reached through a mutable accessor.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ComponentTicks {
+    added: u64,
+    changed: u64,
+}
+
+impl ComponentTicks {
+    pub(crate) fn new(tick: u64) -> Self {
+        Self {
+            added: tick,
+            changed: tick,
+        }
+    }
+
+    pub fn added(&self) -> u64 {
+        self.added
+    }
+
+    pub fn changed(&self) -> u64 {
+        self.changed
+    }
+
+    /// Checks if the component was added after the provided tick.
+    pub fn is_added(&self, since: u64) -> bool {
+        self.added > since
+    }
+
+    /// Checks if the component was added or mutably accessed after the provided tick.
+    pub fn is_changed(&self, since: u64) -> bool {
+        self.changed > since
+    }
+
+    pub(crate) fn mark_changed(&mut self, tick: u64) {
+        self.changed = tick;
+    }
+}
+
+/// Component value stored alongside its change ticks in a pool slot.
+pub struct Tracked<T> {
+    pub(crate) value: T,
+    pub(crate) ticks: ComponentTicks,
+}
+
+impl<T> Tracked<T> {
+    pub(crate) fn new(value: T, tick: u64) -> Self {
+        Self {
+            value,
+            ticks: ComponentTicks::new(tick),
+        }
+    }
+
+    /// Marks the component as changed before handing out the mutable reference to it.
+    pub(crate) fn value_mut(&mut self, tick: u64) -> &mut T {
+        self.ticks.mark_changed(tick);
+        &mut self.value
+    }
+
+    pub(crate) fn clone_with(&self, clone: fn(&T) -> T) -> Self {
+        Self {
+            value: clone(&self.value),
+            ticks: self.ticks,
+        }
+    }
+}
@@ -1,14 +1,291 @@
+use std::{
+    collections::HashSet,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+
 use tempest_math::f32::{mat4::Mat4, vec3::Vec3};
 
-use crate::component::Component;
+use crate::{
+    access::Access,
+    component::Component,
+    hierarchy::Parent,
+    registry::{Entity, PoolKeys, QueryIterator, ReadOnlyRegistryQuery, Registry, RegistryQuery},
+    save::Persist,
+    scene::SceneValue,
+};
 
-#[derive(Component)]
+/// Transformation of an entity relative to its parent, or to the world for entities without a parent.
+#[derive(Clone, Copy, Debug, PartialEq, Component, Persist, SceneValue)]
+#[persist(name = "tempest::Transformation")]
 pub struct Transformation {
-    pub mat: Mat4,
+    pub position: Vec3,
+    /// Rotation in radians around the X, then the Y, then the Z axis.
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+
+impl Default for Transformation {
+    fn default() -> Self {
+        Self::new(Vec3::ZERO, Vec3::ZERO, Vec3::ONE)
+    }
 }
 
 impl Transformation {
-    pub fn new(_position: Vec3, _euler_rotation: Vec3, _scale: Vec3) -> Self {
-        todo!("Implement new transformation")
+    pub fn new(position: Vec3, euler_rotation: Vec3, scale: Vec3) -> Self {
+        Self {
+            position,
+            rotation: euler_rotation,
+            scale,
+        }
+    }
+
+    pub fn from_position(position: Vec3) -> Self {
+        Self {
+            position,
+            ..Default::default()
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_euler_translation(self.scale, self.rotation, self.position)
+    }
+}
+
+/// Transformation of an entity relative to the world, computed from the [Transformation] of the entity and of its
+/// ancestors by [TransformPropagation].
+#[derive(Clone, Copy, Debug, PartialEq, Component)]
+pub struct GlobalTransformation {
+    mat: Mat4,
+}
+
+impl GlobalTransformation {
+    pub fn matrix(&self) -> Mat4 {
+        self.mat
+    }
+}
+
+/// Query yielding the entities whose component of type `T` changed since the tick of the query.
+struct Changed<T>(PhantomData<T>);
+
+impl<'r, T: Component> RegistryQuery<'r> for Changed<T> {
+    type Result = Entity;
+
+    fn contains(it: QueryIterator, reg: &'r Registry) -> bool {
+        reg.contains_component_from_iter::<T>(it)
+    }
+
+    unsafe fn fetch(it: QueryIterator, reg: &'r Registry) -> Option<Self::Result> {
+        if reg.is_component_changed_from_iter::<T>(it) {
+            reg.entity_from_iter(it)
+        } else {
+            None
+        }
+    }
+
+    fn access(access: &mut Access) {
+        access.read(T::id());
+    }
+
+    fn driver(reg: &'r Registry) -> Option<PoolKeys<'r>> {
+        PoolKeys::smallest([reg.pool_keys::<T>()])
+    }
+}
+
+unsafe impl<'r, T: Component> ReadOnlyRegistryQuery<'r> for Changed<T> {}
+
+/// Pass updating the [GlobalTransformation] of every entity with a [Transformation].  Only the subtrees below an
+/// entity whose transformation or parent changed, or was removed, since the last run are updated.  Entities without a
+/// transformation pass the global transformation of their closest transformed ancestor down to their children.
+///
+/// Removals are recorded by hooks registered on the first run, so a pass must always run on the same registry.
+#[derive(Default)]
+pub struct TransformPropagation {
+    last_run: u64,
+    removed: Option<Arc<Mutex<Vec<Entity>>>>,
+}
+
+impl TransformPropagation {
+    /// Updates the global transformations, returning the number of entities updated.
+    pub fn run(&mut self, reg: &mut Registry) -> usize {
+        let removed = self.removed.get_or_insert_with(|| {
+            let removed = Arc::new(Mutex::new(Vec::new()));
+            Self::record_removals::<Transformation>(reg, &removed);
+            Self::record_removals::<Parent>(reg, &removed);
+            removed
+        });
+
+        let mut dirty: HashSet<Entity> = removed.lock().unwrap().drain(..).collect();
+        dirty.extend(
+            reg.query_registry::<Changed<Transformation>>()
+                .since(self.last_run),
+        );
+        dirty.extend(reg.query_registry::<Changed<Parent>>().since(self.last_run));
+
+        // Dirty entities below another dirty entity are updated along with it
+        let roots: Vec<Entity> = dirty
+            .iter()
+            .filter(|ent| {
+                reg.is_alive(**ent)
+                    && !reg
+                        .ancestors(**ent)
+                        .any(|ancestor| dirty.contains(&ancestor))
+            })
+            .copied()
+            .collect();
+
+        let mut updated = 0;
+        for root in roots {
+            let parent = Self::transformed_parent(reg, root)
+                .and_then(|ancestor| reg.get_component_ref::<GlobalTransformation>(ancestor))
+                .map(GlobalTransformation::matrix)
+                .unwrap_or_else(Mat4::identity);
+            updated += Self::update(reg, root, parent);
+        }
+
+        self.last_run = reg.tick();
+        reg.advance_tick();
+        updated
+    }
+
+    fn record_removals<T: Component>(reg: &mut Registry, removed: &Arc<Mutex<Vec<Entity>>>) {
+        let removed = Arc::downgrade(removed);
+        reg.on_remove::<T>(move |ent, _| {
+            if let Some(removed) = removed.upgrade() {
+                removed.lock().unwrap().push(ent);
+            }
+        });
+    }
+
+    fn transformed_parent(reg: &Registry, ent: Entity) -> Option<Entity> {
+        reg.ancestors(ent)
+            .find(|ancestor| reg.has_component::<Transformation>(*ancestor))
     }
-}
\ No newline at end of file
+
+    /// Updates the subtree of the entity depth-first, with an explicit stack so that deep hierarchies do not overflow
+    /// the call stack.
+    fn update(reg: &mut Registry, ent: Entity, parent: Mat4) -> usize {
+        let mut updated = 0;
+        let mut stack = vec![(ent, parent)];
+        while let Some((ent, parent)) = stack.pop() {
+            let mat = match reg.get_component_ref::<Transformation>(ent) {
+                Some(local) => {
+                    let global = GlobalTransformation {
+                        mat: parent * local.matrix(),
+                    };
+                    match reg.get_component_mut::<GlobalTransformation>(ent) {
+                        Some(current) => *current = global,
+                        None => {
+                            reg.assign_component(ent, global);
+                        }
+                    }
+
+                    updated += 1;
+                    global.mat
+                }
+                None => {
+                    reg.remove_component::<GlobalTransformation>(ent);
+                    parent
+                }
+            };
+
+            stack.extend(reg.children(ent).iter().rev().map(|child| (*child, mat)));
+        }
+
+        updated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::StorageKind;
+
+    fn global_position(reg: &Registry, ent: Entity) -> Vec3 {
+        reg.get_component_ref::<GlobalTransformation>(ent)
+            .unwrap()
+            .matrix()
+            .transform_point(Vec3::ZERO)
+    }
+
+    fn propagation(kind: StorageKind) {
+        let mut reg = Registry::new(kind);
+        let [root, arm, hand, other] = [(); 4].map(|_| reg.create_entity());
+        let pivot = reg.create_entity();
+
+        reg.assign_component(root, Transformation::from_position(Vec3::X));
+        reg.assign_component(
+            arm,
+            Transformation::new(Vec3::Y, Vec3::ZERO, Vec3::broadcast(2.0)),
+        );
+        reg.assign_component(hand, Transformation::from_position(Vec3::Y));
+        reg.assign_component(other, Transformation::from_position(Vec3::Z));
+        reg.set_parent(arm, root);
+        reg.set_parent(pivot, arm);
+        reg.set_parent(hand, pivot);
+
+        let mut propagation = TransformPropagation::default();
+        assert_eq!(propagation.run(&mut reg), 4);
+        assert_eq!(global_position(&reg, root), Vec3::X);
+        assert_eq!(global_position(&reg, arm), Vec3::new(1.0, 1.0, 0.0));
+        // the pivot has no transformation, and the hand is scaled by the arm
+        assert!(!reg.has_component::<GlobalTransformation>(pivot));
+        assert_eq!(global_position(&reg, hand), Vec3::new(1.0, 3.0, 0.0));
+
+        // nothing changed
+        assert_eq!(propagation.run(&mut reg), 0);
+
+        // only the subtree below the changed entity is updated
+        reg.get_component_mut::<Transformation>(arm).unwrap().scale = Vec3::ONE;
+        assert_eq!(propagation.run(&mut reg), 2);
+        assert_eq!(global_position(&reg, hand), Vec3::new(1.0, 2.0, 0.0));
+
+        // reparenting moves the subtree along
+        reg.set_parent(pivot, other);
+        assert_eq!(propagation.run(&mut reg), 1);
+        assert_eq!(global_position(&reg, hand), Vec3::new(0.0, 1.0, 1.0));
+
+        reg.clear_parent(pivot);
+        reg.clear_parent(arm);
+        assert_eq!(propagation.run(&mut reg), 2);
+        assert_eq!(global_position(&reg, arm), Vec3::Y);
+        assert_eq!(global_position(&reg, hand), Vec3::Y);
+    }
+
+    #[test]
+    fn test_propagation() {
+        propagation(StorageKind::Sparse);
+        propagation(StorageKind::Archetype);
+    }
+
+    fn removed_transformation(kind: StorageKind) {
+        let mut reg = Registry::new(kind);
+        let [root, arm, hand] = [(); 3].map(|_| reg.create_entity());
+
+        reg.assign_component(root, Transformation::from_position(Vec3::X));
+        reg.assign_component(arm, Transformation::from_position(Vec3::Y * 10.0));
+        reg.assign_component(hand, Transformation::from_position(Vec3::Z));
+        reg.set_parent(arm, root);
+        reg.set_parent(hand, arm);
+
+        let mut propagation = TransformPropagation::default();
+        assert_eq!(propagation.run(&mut reg), 3);
+        assert_eq!(global_position(&reg, hand), Vec3::new(1.0, 10.0, 1.0));
+
+        // the hand now hangs from the root, and the arm loses its stale global transformation
+        reg.remove_component::<Transformation>(arm);
+        assert_eq!(propagation.run(&mut reg), 1);
+        assert!(!reg.has_component::<GlobalTransformation>(arm));
+        assert_eq!(global_position(&reg, hand), Vec3::new(1.0, 0.0, 1.0));
+
+        reg.assign_component(arm, Transformation::from_position(Vec3::Y * 10.0));
+        assert_eq!(propagation.run(&mut reg), 2);
+        assert_eq!(global_position(&reg, hand), Vec3::new(1.0, 10.0, 1.0));
+    }
+
+    #[test]
+    fn test_removed_transformation() {
+        removed_transformation(StorageKind::Sparse);
+        removed_transformation(StorageKind::Archetype);
+    }
+}
@@ -1,18 +1,50 @@
-use super::registry::Registry;
+use std::{
+    any::{type_name, TypeId},
+    collections::HashSet,
+};
+
+use super::{
+    access::BorrowError,
+    event::{Event, Events},
+    registry::{QueryBorrow, Registry, RegistryCell, RegistryQuery, StorageKind},
+    resource::{Res, ResMut, Resource, Resources, ResourcesCell},
+};
 
 pub struct World {
     entities: Registry,
+    resources: Resources,
+    /// Event types registered with [World::add_event], which may outlive their [Events] resource.
+    event_types: HashSet<TypeId>,
+    event_updates: Vec<fn(&mut Resources)>,
 }
 
 impl Default for World {
     fn default() -> Self {
-        Self {
-            entities: Default::default(),
-        }
+        Self::new(StorageKind::default())
     }
 }
 
 impl World {
+    /// Creates an empty world whose registry stores components using the provided strategy.
+    pub fn new(storage: StorageKind) -> Self {
+        Self {
+            entities: Registry::new(storage),
+            resources: Resources::default(),
+            event_types: HashSet::new(),
+            event_updates: Vec::new(),
+        }
+    }
+
+    /// Current tick of the world, used to detect which components were added or changed.
+    pub fn tick(&self) -> u64 {
+        self.entities.tick()
+    }
+
+    /// Advances the tick of the world.  Called once per update by the application.
+    pub fn increment_tick(&mut self) -> u64 {
+        self.entities.increment_tick()
+    }
+
     pub fn entities(&self) -> &Registry {
         &self.entities
     }
@@ -20,4 +52,238 @@ impl World {
     pub fn entitites_mut(&mut self) -> &mut Registry {
         &mut self.entities
     }
+
+    /// Inserts a resource, returning the previous resource of the same type.
+    pub fn insert_resource<T: Resource>(&mut self, resource: T) -> Option<T> {
+        self.resources.insert(resource)
+    }
+
+    pub fn get_resource<T: Resource>(&self) -> Option<&T> {
+        self.resources.get()
+    }
+
+    pub fn get_resource_mut<T: Resource>(&mut self) -> Option<&mut T> {
+        self.resources.get_mut()
+    }
+
+    pub fn remove_resource<T: Resource>(&mut self) -> Option<T> {
+        self.resources.remove()
+    }
+
+    pub fn contains_resource<T: Resource>(&self) -> bool {
+        self.resources.contains::<T>()
+    }
+
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
+    pub fn resources_mut(&mut self) -> &mut Resources {
+        &mut self.resources
+    }
+
+    /// Registers the events of the type, stored as an [Events] resource updated by [World::update_events].  The
+    /// resource is inserted if missing, and an existing one is kept.
+    pub fn add_event<T: Event>(&mut self) {
+        if !self.resources.contains::<Events<T>>() {
+            self.resources.insert(Events::<T>::default());
+        }
+
+        if self.event_types.insert(TypeId::of::<T>()) {
+            self.event_updates.push(|resources| {
+                if let Some(events) = resources.get_mut::<Events<T>>() {
+                    events.update();
+                }
+            });
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the event type is not registered.
+    pub fn send_event<T: Event>(&mut self, event: T) {
+        match self.resources.get_mut::<Events<T>>() {
+            Some(events) => events.send(event),
+            None => panic!("Event {} is not registered.", type_name::<T>()),
+        }
+    }
+
+    pub fn events<T: Event>(&self) -> Option<&Events<T>> {
+        self.resources.get()
+    }
+
+    /// Updates the queue of every registered event type, dropping the events sent two updates ago.  Called once per
+    /// update by the application.
+    pub fn update_events(&mut self) {
+        for update in &self.event_updates {
+            update(&mut self.resources);
+        }
+    }
+
+    /// Borrows the registry and the resources at the same time.
+    pub fn split_mut(&mut self) -> (&mut Registry, &mut Resources) {
+        (&mut self.entities, &mut self.resources)
+    }
+
+    /// Grants shared access to the components and resources of the world, which are borrowed at runtime.
+    pub fn cell(&mut self) -> WorldCell<'_> {
+        WorldCell {
+            registry: self.entities.cell(),
+            resources: self.resources.cell(),
+        }
+    }
+}
+
+/// World shared between several queries and resource borrows.  Components and resources are tracked separately, so
+/// borrowing a resource never conflicts with a query.
+pub struct WorldCell<'w> {
+    registry: RegistryCell<'w>,
+    resources: ResourcesCell<'w>,
+}
+
+impl<'w> WorldCell<'w> {
+    pub fn registry(&self) -> &RegistryCell<'w> {
+        &self.registry
+    }
+
+    pub fn resources(&self) -> &ResourcesCell<'w> {
+        &self.resources
+    }
+
+    /// See [RegistryCell::try_query].
+    pub fn try_query<T: for<'r> RegistryQuery<'r>>(
+        &self,
+    ) -> Result<QueryBorrow<'_, T>, BorrowError> {
+        self.registry.try_query()
+    }
+
+    /// See [RegistryCell::query].
+    pub fn query<T: for<'r> RegistryQuery<'r>>(&self) -> QueryBorrow<'_, T> {
+        self.registry.query()
+    }
+
+    /// See [ResourcesCell::get].
+    pub fn resource<T: Resource>(&self) -> Option<Res<'_, T>> {
+        self.resources.get()
+    }
+
+    /// See [ResourcesCell::get_mut].
+    pub fn resource_mut<T: Resource>(&self) -> Option<ResMut<'_, T>> {
+        self.resources.get_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempest_ecs_macros::RegistryQuery;
+
+    use super::*;
+    use crate::{component::Component, event::EventReader};
+
+    #[derive(Component)]
+    struct Position(f32);
+
+    #[derive(Component)]
+    struct Velocity(f32);
+
+    #[derive(RegistryQuery)]
+    #[read_write(Position)]
+    #[read_only(Velocity)]
+    struct MovementQuery;
+
+    struct FrameTime(f32);
+
+    #[derive(Default)]
+    struct Moved(usize);
+
+    #[test]
+    fn test_resources_with_components() {
+        let mut world = World::default();
+        world.insert_resource(FrameTime(0.5));
+        world.insert_resource(Moved::default());
+        assert!(world.contains_resource::<FrameTime>());
+
+        let registry = world.entitites_mut();
+        for i in 0..3 {
+            let ent = registry.create_entity();
+            registry.assign_component(ent, Position(0.0));
+            registry.assign_component(ent, Velocity(i as f32));
+        }
+
+        {
+            let cell = world.cell();
+            let time = cell.resource::<FrameTime>().unwrap();
+            let mut moved = cell.resource_mut::<Moved>().unwrap();
+            for (velocity, position) in cell.query::<MovementQuery>().iter() {
+                position.0 += velocity.0 * time.0;
+                moved.0 += 1;
+            }
+        }
+
+        let (registry, resources) = world.split_mut();
+        for (velocity, position) in registry.query_registry_mut::<MovementQuery>() {
+            position.0 += velocity.0 * resources.get::<FrameTime>().unwrap().0;
+            resources.get_mut::<Moved>().unwrap().0 += 1;
+        }
+
+        let positions: f32 = world
+            .entities()
+            .query_registry::<PositionQuery>()
+            .map(|position| position.0)
+            .sum();
+        assert_eq!(positions, 3.0);
+        assert_eq!(world.remove_resource::<Moved>().unwrap().0, 6);
+        assert!(world.get_resource::<Moved>().is_none());
+    }
+
+    #[test]
+    fn test_world_events() {
+        struct Resized(u32);
+
+        let mut world = World::default();
+        world.add_event::<Resized>();
+        world.add_event::<Resized>();
+
+        let mut reader = EventReader::default();
+        world.send_event(Resized(1));
+        world.update_events();
+        world.send_event(Resized(2));
+
+        let sizes: Vec<u32> = reader
+            .read(world.events::<Resized>().unwrap())
+            .map(|event| event.0)
+            .collect();
+        assert_eq!(sizes, [1, 2]);
+
+        world.update_events();
+        world.update_events();
+        assert!(world.events::<Resized>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_event_resource_lifetime() {
+        struct Resized;
+
+        // events inserted as a resource beforehand still expire
+        let mut world = World::default();
+        world.insert_resource(Events::<Resized>::default());
+        world.add_event::<Resized>();
+        world.send_event(Resized);
+        world.update_events();
+        world.update_events();
+        assert!(world.events::<Resized>().unwrap().is_empty());
+
+        // events added again after their resource was removed are kept for two updates, not one
+        world.remove_resource::<Events<Resized>>();
+        world.add_event::<Resized>();
+        world.send_event(Resized);
+        world.update_events();
+        assert_eq!(world.events::<Resized>().unwrap().len(), 1);
+        world.update_events();
+        assert!(world.events::<Resized>().unwrap().is_empty());
+    }
+
+    #[derive(RegistryQuery)]
+    #[read_only(Position)]
+    struct PositionQuery;
 }
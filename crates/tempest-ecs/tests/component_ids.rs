@@ -0,0 +1,52 @@
+//! Components defined in this test crate must not share identifiers with the components defined by `tempest_ecs`
+//! itself, even though both crates are expanded by separate invocations of the derive macro.
+
+use tempest_ecs::{
+    component::{component_id, Component},
+    registry::{Registry, StorageKind},
+    transformation::Transformation,
+};
+use tempest_math::f32::mat4::Mat4;
+
+#[derive(Component, Clone)]
+struct Health(u32);
+
+#[derive(Component, Clone)]
+struct Velocity;
+
+#[test]
+fn ids_are_unique_across_crates() {
+    let ids = [Transformation::id(), Health::id(), Velocity::id()];
+
+    for (i, lhs) in ids.iter().enumerate() {
+        for rhs in ids.iter().skip(i + 1) {
+            assert_ne!(lhs, rhs);
+        }
+    }
+
+    assert_eq!(Health::id(), component_id::<Health>());
+    assert_eq!(Transformation::id(), component_id::<Transformation>());
+}
+
+fn pools_are_not_shared(kind: StorageKind) {
+    let mut reg = Registry::new(kind);
+    let ent = reg.create_entity();
+
+    reg.assign_component(ent, Transformation::default());
+    reg.assign_component(ent, Health(10));
+
+    assert!(reg.has_component::<Transformation>(ent));
+    assert!(reg.has_component::<Health>(ent));
+    assert!(!reg.has_component::<Velocity>(ent));
+    assert_eq!(reg.get_component::<Health>(ent).map(|h| h.0), Some(10));
+    assert_eq!(
+        reg.get_component::<Transformation>(ent).map(|t| t.matrix()),
+        Some(Mat4::identity())
+    );
+}
+
+#[test]
+fn components_from_both_crates_share_a_registry() {
+    pools_are_not_shared(StorageKind::Sparse);
+    pools_are_not_shared(StorageKind::Archetype);
+}
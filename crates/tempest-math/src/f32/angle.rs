@@ -1,4 +1,4 @@
-use std::f32::consts::PI;
+use std::{f32::consts::PI, fmt::Display};
 
 pub trait Angle {
     fn normalize(self) -> Self;
@@ -34,9 +34,9 @@ impl From<Degrees> for Radians {
     }
 }
 
-impl ToString for Radians {
-    fn to_string(&self) -> String {
-        format!("{} Radians", self.value)
+impl Display for Radians {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} Radians", self.value)
     }
 }
 
@@ -60,8 +60,8 @@ impl From<Radians> for Degrees {
     }
 }
 
-impl ToString for Degrees {
-    fn to_string(&self) -> String {
-        format!("{} Degrees", self.value)
+impl Display for Degrees {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} Degrees", self.value)
     }
 }
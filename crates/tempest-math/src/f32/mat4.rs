@@ -1,6 +1,8 @@
+use std::ops::{Mul, MulAssign};
+
 use bytemuck::{Pod, Zeroable};
 
-use super::vec4::Vec4;
+use super::{vec3::Vec3, vec4::Vec4};
 
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 #[repr(C, align(16))]
@@ -51,4 +53,136 @@ impl Mat4 {
     pub const fn identity() -> Self {
         Self::diagonal(Vec4::ONE)
     }
+    pub const fn from_translation(translation: Vec3) -> Self {
+        Self::from_cols(
+            Vec4::X,
+            Vec4::Y,
+            Vec4::Z,
+            Vec4::new(translation.x, translation.y, translation.z, 1.0),
+        )
+    }
+
+    pub const fn from_scale(scale: Vec3) -> Self {
+        Self::diagonal(Vec4::new(scale.x, scale.y, scale.z, 1.0))
+    }
+
+    /// Rotation by the provided angles in radians around the X, then the Y, then the Z axis.
+    pub fn from_euler(angles: Vec3) -> Self {
+        let (sx, cx) = angles.x.sin_cos();
+        let (sy, cy) = angles.y.sin_cos();
+        let (sz, cz) = angles.z.sin_cos();
+
+        Self::from_cols(
+            Vec4::new(cy * cz, cy * sz, -sy, 0.0),
+            Vec4::new(sx * sy * cz - cx * sz, sx * sy * sz + cx * cz, sx * cy, 0.0),
+            Vec4::new(cx * sy * cz + sx * sz, cx * sy * sz - sx * cz, cx * cy, 0.0),
+            Vec4::W,
+        )
+    }
+
+    /// Transformation scaling, then rotating with [Mat4::from_euler], then translating.
+    pub fn from_scale_euler_translation(scale: Vec3, angles: Vec3, translation: Vec3) -> Self {
+        Self::from_translation(translation) * Self::from_euler(angles) * Self::from_scale(scale)
+    }
+
+    pub const fn col(&self, idx: usize) -> Vec4 {
+        Vec4::new(
+            self.arr[idx * 4],
+            self.arr[idx * 4 + 1],
+            self.arr[idx * 4 + 2],
+            self.arr[idx * 4 + 3],
+        )
+    }
+
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        let v = *self * Vec4::new(point.x, point.y, point.z, 1.0);
+        Vec3::new(v.x, v.y, v.z)
+    }
+
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        let v = *self * Vec4::new(vector.x, vector.y, vector.z, 0.0);
+        Vec3::new(v.x, v.y, v.z)
+    }
+}
+
+impl Mul<Mat4> for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Self::Output {
+        let mut arr = [0.0; 16];
+        for col in 0..4 {
+            for row in 0..4 {
+                arr[col * 4 + row] = (0..4)
+                    .map(|k| self.arr[k * 4 + row] * rhs.arr[col * 4 + k])
+                    .sum();
+            }
+        }
+        Self { arr }
+    }
+}
+
+impl MulAssign<Mat4> for Mat4 {
+    fn mul_assign(&mut self, rhs: Mat4) {
+        *self = *self * rhs;
+    }
+}
+
+impl Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+
+    fn mul(self, rhs: Vec4) -> Self::Output {
+        self.col(0) * rhs.x + self.col(1) * rhs.y + self.col(2) * rhs.z + self.col(3) * rhs.w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    fn assert_close(lhs: Vec3, rhs: Vec3) {
+        assert!(lhs.distance_between(rhs) < 1e-5, "{:?} != {:?}", lhs, rhs);
+    }
+
+    #[test]
+    fn test_multiplication() {
+        let translate = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0));
+        let scale = Mat4::from_scale(Vec3::broadcast(2.0));
+        assert_eq!(Mat4::identity() * translate, translate);
+        assert_eq!(translate * Mat4::identity(), translate);
+
+        let mut combined = translate;
+        combined *= scale;
+        assert_eq!(
+            combined.transform_point(Vec3::ONE),
+            Vec3::new(3.0, 4.0, 5.0)
+        );
+        assert_eq!(
+            (scale * translate).transform_point(Vec3::ONE),
+            Vec3::new(4.0, 6.0, 8.0)
+        );
+        assert_eq!(combined.transform_vector(Vec3::ONE), Vec3::broadcast(2.0));
+    }
+
+    #[test]
+    fn test_rotation() {
+        let z = Mat4::from_euler(Vec3::new(0.0, 0.0, FRAC_PI_2));
+        assert_close(z.transform_vector(Vec3::X), Vec3::Y);
+
+        let x = Mat4::from_euler(Vec3::new(FRAC_PI_2, 0.0, 0.0));
+        assert_close(x.transform_vector(Vec3::Y), Vec3::Z);
+
+        // X is applied before Z
+        let xz = Mat4::from_euler(Vec3::new(FRAC_PI_2, 0.0, FRAC_PI_2));
+        assert_close(xz.transform_vector(Vec3::Y), Vec3::Z);
+        assert_close(xz.transform_vector(Vec3::Z), Vec3::X);
+
+        let trs = Mat4::from_scale_euler_translation(
+            Vec3::broadcast(2.0),
+            Vec3::new(0.0, 0.0, FRAC_PI_2),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        assert_close(trs.transform_point(Vec3::X), Vec3::new(0.0, 2.0, 1.0));
+    }
 }
@@ -221,9 +221,9 @@ impl Index<usize> for Vec2 {
     fn index(&self, index: usize) -> &Self::Output {
         assert!(index < 2);
         if index == 0 {
-            return &self.x;
+            &self.x
         } else {
-            return &self.y;
+            &self.y
         }
     }
 }
@@ -1,11 +1,11 @@
 use tempest_ecs::{
     registry::{Registry, RegistryQuery},
-    transformation::Transformation,
+    transformation::GlobalTransformation,
 };
 use winit::window::Window;
 
 #[derive(RegistryQuery)]
-#[read_only(Transformation)]
+#[read_only(GlobalTransformation)]
 struct RendererQuery;
 
 pub struct Renderer {
@@ -56,8 +56,7 @@ impl Renderer {
             .formats
             .iter()
             .copied()
-            .filter(|f| f.describe().srgb)
-            .next()
+            .find(|f| f.describe().srgb)
             .unwrap_or(surface_capabilities.formats[0]);
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -149,8 +148,9 @@ impl Renderer {
             .create_view(&wgpu::TextureViewDescriptor::default());
 
         let query = reg.query_registry::<RendererQuery>();
-        for _ in query {
-            // TODO: Query entities with transformation, renderable mesh, and material
+        for transformation in query {
+            let _model = transformation.matrix();
+            // TODO: Query entities with renderable mesh and material
             // Write to buffers
         }
 
@@ -97,7 +97,7 @@ impl ShaderPreprocessor {
         let mut include_state = Mutex::new(HashSet::<String>::new());
         _ = include_state
             .get_mut()
-            .and_then(|state| Ok(state.insert(base.to_string())));
+            .map(|state| state.insert(base.to_string()));
 
         let mut hb_reg = Handlebars::new();
         hb_reg.set_strict_mode(true);
@@ -118,7 +118,7 @@ impl ShaderPreprocessor {
         let contents = self
             .sources
             .get(base)
-            .ok_or_else(|| RenderError::new(format!("Shader base template is not registered.")))?;
+            .ok_or_else(|| RenderError::new("Shader base template is not registered."))?;
 
         let va_counts: usize = if let Some(vertex_buffers) = vertex_buffers {
             vertex_buffers.attributes.len()
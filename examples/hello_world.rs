@@ -4,6 +4,7 @@ use tempest_ecs::{component::Component, registry::RegistryQuery};
 #[derive(Component)]
 struct AppComponent;
 
+#[allow(dead_code)]
 #[derive(Component)]
 struct AppComponent2;
 